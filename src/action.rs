@@ -0,0 +1,168 @@
+use crate::SearchDirection;
+use crate::ShiftDirection;
+use crossterm::event::KeyCode;
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Motion {
+    Left,
+    Right,
+    Up,
+    Down,
+    PageUp,
+    PageDown,
+    Home,
+    End,
+    LineStart,
+    LineEnd,
+    WordForward,
+    WordBackward,
+    WordEnd,
+    Line,
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Operator {
+    Delete,
+    Change,
+    Yank,
+    Shift(ShiftDirection),
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Action {
+    Move(Motion, usize),
+    MoveVisualLine(Motion),
+    Operate(Operator, Motion, usize),
+    OperateSelection(Operator),
+    ShiftSelection(ShiftDirection),
+    ToggleComment(usize),
+    OpenLine { below: bool },
+    JoinLines(usize),
+    Paste { after: bool, count: usize },
+    InsertChar(char),
+    InsertNewline,
+    InsertTab,
+    DeleteForward,
+    DeleteBackward,
+    InsertMode,
+    NormalMode,
+    ToggleVisual,
+    SelectSubword { around: bool },
+    SelectRegister(char),
+    Undo,
+    Redo,
+    GotoLine(usize),
+    GotoLinePrompt,
+    GotoPercent(usize),
+    MatchBracket,
+    SetMark(char),
+    JumpToMark(char),
+    JumpBack,
+    JumpForward,
+    RecordMacro(char),
+    StopRecording,
+    ReplayMacro(char, usize),
+    CommandLine,
+    Search,
+    SearchNext { reverse: bool },
+    SearchWord(SearchDirection),
+    LineJump,
+    SymbolJump,
+    FindFile,
+    ToggleFileTree,
+    ToggleLineNumbers,
+    Save,
+    Quit,
+}
+
+impl Motion {
+    pub fn from_key(c: char) -> Option<Self> {
+        match c {
+            'h' => Some(Self::Left),
+            'l' => Some(Self::Right),
+            'k' => Some(Self::Up),
+            'j' => Some(Self::Down),
+            '0' => Some(Self::LineStart),
+            '$' => Some(Self::LineEnd),
+            'w' => Some(Self::WordForward),
+            'b' => Some(Self::WordBackward),
+            'e' => Some(Self::WordEnd),
+            _ => None,
+        }
+    }
+
+    pub fn from_key_code(code: KeyCode) -> Option<Self> {
+        match code {
+            KeyCode::Left => Some(Self::Left),
+            KeyCode::Right => Some(Self::Right),
+            KeyCode::Up => Some(Self::Up),
+            KeyCode::Down => Some(Self::Down),
+            KeyCode::PageUp => Some(Self::PageUp),
+            KeyCode::PageDown => Some(Self::PageDown),
+            KeyCode::Home => Some(Self::Home),
+            KeyCode::End => Some(Self::End),
+            _ => None,
+        }
+    }
+
+    pub fn key_code(self) -> Option<KeyCode> {
+        match self {
+            Self::Left => Some(KeyCode::Left),
+            Self::Right => Some(KeyCode::Right),
+            Self::Up => Some(KeyCode::Up),
+            Self::Down => Some(KeyCode::Down),
+            Self::PageUp => Some(KeyCode::PageUp),
+            Self::PageDown => Some(KeyCode::PageDown),
+            Self::Home => Some(KeyCode::Home),
+            Self::End => Some(KeyCode::End),
+            _ => None,
+        }
+    }
+}
+
+impl Action {
+    pub fn is_edit(self) -> bool {
+        match self {
+            Self::Operate(operator, _, _) | Self::OperateSelection(operator) => {
+                operator != Operator::Yank
+            }
+            Self::ShiftSelection(_)
+            | Self::ToggleComment(_)
+            | Self::OpenLine { .. }
+            | Self::JoinLines(_)
+            | Self::Paste { .. }
+            | Self::InsertChar(_)
+            | Self::InsertNewline
+            | Self::InsertTab
+            | Self::DeleteForward
+            | Self::DeleteBackward
+            | Self::InsertMode
+            | Self::Undo
+            | Self::Redo => true,
+            _ => false,
+        }
+    }
+}
+
+impl Operator {
+    pub fn from_key(c: char) -> Option<Self> {
+        match c {
+            'd' => Some(Self::Delete),
+            'c' => Some(Self::Change),
+            'y' => Some(Self::Yank),
+            '>' => Some(Self::Shift(ShiftDirection::Right)),
+            '<' => Some(Self::Shift(ShiftDirection::Left)),
+            _ => None,
+        }
+    }
+
+    pub fn key(self) -> char {
+        match self {
+            Self::Delete => 'd',
+            Self::Change => 'c',
+            Self::Yank => 'y',
+            Self::Shift(ShiftDirection::Right) => '>',
+            Self::Shift(ShiftDirection::Left) => '<',
+        }
+    }
+}
@@ -0,0 +1,113 @@
+use crossterm::style::Color;
+use unicode_segmentation::UnicodeSegmentation;
+
+const ESCAPE: char = '\x1b';
+const BASIC: [Color; 8] = [
+    Color::Black,
+    Color::DarkRed,
+    Color::DarkGreen,
+    Color::DarkYellow,
+    Color::DarkBlue,
+    Color::DarkMagenta,
+    Color::DarkCyan,
+    Color::Grey,
+];
+const BRIGHT: [Color; 8] = [
+    Color::DarkGrey,
+    Color::Red,
+    Color::Green,
+    Color::Yellow,
+    Color::Blue,
+    Color::Magenta,
+    Color::Cyan,
+    Color::White,
+];
+
+pub type Span = (usize, usize, Color);
+
+pub fn contains_escapes(text: &str) -> bool {
+    text.contains(ESCAPE)
+}
+
+pub fn parse(line: &str) -> (String, Vec<Span>) {
+    let mut text = String::new();
+    let mut spans: Vec<Span> = Vec::new();
+    let mut colour: Option<Color> = None;
+    let mut len = 0;
+    let mut rest = line;
+    while let Some(start) = rest.find(ESCAPE) {
+        let segment = &rest[..start];
+        let graphemes = segment.graphemes(true).count();
+        if let (Some(colour), true) = (colour, graphemes > 0) {
+            spans.push((len, len + graphemes, colour));
+        }
+        text.push_str(segment);
+        len += graphemes;
+
+        let (sequence, after) = split_sequence(&rest[start + 1..]);
+        if let Some(params) = sequence
+            .strip_prefix('[')
+            .and_then(|sequence| sequence.strip_suffix('m'))
+        {
+            colour = apply_sgr(params, colour);
+        }
+        rest = after;
+    }
+    let graphemes = rest.graphemes(true).count();
+    if let (Some(colour), true) = (colour, graphemes > 0) {
+        spans.push((len, len + graphemes, colour));
+    }
+    text.push_str(rest);
+    (text, spans)
+}
+
+fn split_sequence(rest: &str) -> (&str, &str) {
+    let mut chars = rest.char_indices();
+    let end = match chars.next() {
+        Some((_, '[')) => chars
+            .find(|&(_, c)| ('\x40'..='\x7e').contains(&c))
+            .map(|(i, c)| i + c.len_utf8()),
+        Some((_, ']')) => {
+            rest.find(['\x07', ESCAPE])
+                .map(|i| match rest[i..].starts_with("\x1b\\") {
+                    true => i + 2,
+                    false => i + 1,
+                })
+        }
+        Some((_, c)) => Some(c.len_utf8()),
+        None => Some(0),
+    }
+    .unwrap_or(rest.len());
+    rest.split_at(end)
+}
+
+fn apply_sgr(params: &str, current: Option<Color>) -> Option<Color> {
+    let codes: Vec<u8> = params
+        .split(';')
+        .map(|code| code.parse().unwrap_or(0))
+        .collect();
+    let mut colour = current;
+    let mut codes = codes.into_iter();
+    while let Some(code) = codes.next() {
+        match code {
+            0 | 39 => colour = None,
+            30..=37 => colour = Some(BASIC[usize::from(code - 30)]),
+            90..=97 => colour = Some(BRIGHT[usize::from(code - 90)]),
+            38 | 48 => {
+                let extended = match codes.next() {
+                    Some(5) => codes.next().map(Color::AnsiValue),
+                    Some(2) => match (codes.next(), codes.next(), codes.next()) {
+                        (Some(r), Some(g), Some(b)) => Some(Color::Rgb { r, g, b }),
+                        _ => None,
+                    },
+                    _ => None,
+                };
+                if code == 38 {
+                    colour = extended.or(colour);
+                }
+            }
+            _ => (),
+        }
+    }
+    colour
+}
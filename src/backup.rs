@@ -0,0 +1,40 @@
+use crate::paths;
+use std::fs;
+use std::io::Error;
+use std::path::{Path, PathBuf};
+
+pub fn tilde(filename: &str) -> Result<Option<PathBuf>, Error> {
+    if !Path::new(filename).is_file() {
+        return Ok(None);
+    }
+    let backup = PathBuf::from(format!("{}~", filename));
+    fs::copy(filename, &backup)?;
+    Ok(Some(backup))
+}
+
+pub fn rotate(filename: &str, count: usize) -> Result<Option<PathBuf>, Error> {
+    if count == 0 || !Path::new(filename).is_file() {
+        return Ok(None);
+    }
+    let dir = dir().ok_or_else(|| Error::other("No backup directory"))?;
+    fs::create_dir_all(&dir)?;
+    let name = fs::canonicalize(filename)?
+        .to_string_lossy()
+        .replace(['/', '\\', ':'], "%");
+    let numbered = |n: usize| dir.join(format!("{}.{}", name, n));
+
+    fs::remove_file(numbered(count)).ok();
+    for n in (1..count).rev() {
+        let from = numbered(n);
+        if from.exists() {
+            fs::rename(from, numbered(n + 1))?;
+        }
+    }
+    let backup = numbered(1);
+    fs::copy(filename, &backup)?;
+    Ok(Some(backup))
+}
+
+pub fn dir() -> Option<PathBuf> {
+    Some(paths::state_dir()?.join("backups"))
+}
@@ -0,0 +1,89 @@
+use crate::ex;
+use crate::Document;
+use std::path::Path;
+
+pub fn run(script: &str, filename: Option<&str>) -> Result<(), String> {
+    let mut document = match filename {
+        Some(filename) if Path::new(filename).exists() => {
+            Document::open(filename).map_err(|err| format!("{}: {}", filename, err))?
+        }
+        _ => {
+            let mut document = Document::default();
+            document.filename = filename.map(str::to_string);
+            document
+        }
+    };
+
+    for command in split_commands(script).into_iter().map(str::trim) {
+        let (name, args) = command
+            .split_once(char::is_whitespace)
+            .unwrap_or((command, ""));
+        match name {
+            "" => (),
+            "w" | "write" => write(&mut document, args.trim())?,
+            "wq" | "x" => {
+                write(&mut document, args.trim())?;
+                return Ok(());
+            }
+            "q" | "q!" | "quit" => return Ok(()),
+            _ => match ex::execute(&mut document, command, 0) {
+                Some(result) => {
+                    result?;
+                }
+                None => return Err(format!("Not a batch command: {}", command)),
+            },
+        }
+    }
+    Ok(())
+}
+
+fn write(document: &mut Document, filename: &str) -> Result<(), String> {
+    if !filename.is_empty() {
+        document.filename = Some(filename.to_string());
+    }
+    if document.filename.is_none() {
+        return Err(String::from("No file name"));
+    }
+    document.save().map_err(|err| err.to_string())
+}
+
+fn split_commands(script: &str) -> Vec<&str> {
+    let mut commands = Vec::new();
+    let mut start = 0;
+    while start <= script.len() {
+        let end = command_end(&script[start..]).map_or(script.len(), |end| start + end);
+        commands.push(&script[start..end]);
+        start = end + 1;
+    }
+    commands
+}
+
+fn command_end(script: &str) -> Option<usize> {
+    let command = script.trim_start_matches(|c: char| c.is_ascii_digit() || "%,.$+-' ".contains(c));
+    let skip = substitute_len(command).map_or(0, |len| script.len() - command.len() + len);
+    script[skip..].find('|').map(|index| skip + index)
+}
+
+fn substitute_len(command: &str) -> Option<usize> {
+    let rest = command
+        .strip_prefix("substitute")
+        .or_else(|| command.strip_prefix('s'))?;
+    let delimiter = rest.chars().next().filter(|&delimiter| {
+        !delimiter.is_alphanumeric() && !delimiter.is_whitespace() && delimiter != '\\'
+    })?;
+    let mut seen = 0;
+    let mut escaped = false;
+    for (index, c) in rest.char_indices() {
+        if escaped {
+            escaped = false;
+        } else if c == '\\' {
+            escaped = true;
+        } else if c == delimiter {
+            seen += 1;
+            if seen == 3 {
+                return Some(command.len() - rest.len() + index + c.len_utf8());
+            }
+        }
+    }
+    Some(command.len())
+}
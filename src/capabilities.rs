@@ -0,0 +1,115 @@
+use crate::ColourSupport;
+use serde::Deserialize;
+use std::env;
+
+const MODERN_TERMINALS: [&str; 6] = [
+    "kitty",
+    "wezterm",
+    "alacritty",
+    "foot",
+    "ghostty",
+    "contour",
+];
+const MODERN_PROGRAMS: [&str; 5] = ["WezTerm", "iTerm.app", "ghostty", "vscode", "contour"];
+
+#[derive(Clone, Copy)]
+pub struct Capabilities {
+    pub colour: ColourSupport,
+    pub italics: bool,
+    pub undercurl: bool,
+    pub synchronized_output: bool,
+    pub mouse: bool,
+    pub bracketed_paste: bool,
+    pub osc52: bool,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct Overrides {
+    pub italics: Option<bool>,
+    pub undercurl: Option<bool>,
+    pub synchronized_output: Option<bool>,
+    pub mouse: Option<bool>,
+    pub bracketed_paste: Option<bool>,
+    pub osc52: Option<bool>,
+}
+
+impl Capabilities {
+    pub fn detect() -> Self {
+        let var = |name: &str| env::var(name).unwrap_or_default();
+        let term = var("TERM");
+        let program = var("TERM_PROGRAM");
+        let in_tmux = !var("TMUX").is_empty();
+        let is_vte = !var("VTE_VERSION").is_empty();
+        let is_konsole = !var("KONSOLE_VERSION").is_empty();
+
+        let dumb = term.is_empty() || term == "dumb";
+        let console = dumb || term == "linux" || term.starts_with("vt");
+        let modern = MODERN_TERMINALS.iter().any(|name| term.contains(name))
+            || MODERN_PROGRAMS.contains(&program.as_str());
+
+        Self {
+            colour: colour_support(&term, &var("COLORTERM")),
+            italics: !console && term != "screen",
+            undercurl: modern && !in_tmux,
+            synchronized_output: modern || (in_tmux && term.starts_with("tmux")),
+            mouse: !dumb,
+            bracketed_paste: !console,
+            osc52: !console
+                && program != "Apple_Terminal"
+                && (modern || in_tmux || !(is_vte || is_konsole)),
+        }
+    }
+
+    pub fn apply(&mut self, overrides: &Overrides, colour: Option<ColourSupport>) {
+        if let Some(colour) = colour {
+            self.colour = colour;
+        }
+        let settings = [
+            (&mut self.italics, overrides.italics),
+            (&mut self.undercurl, overrides.undercurl),
+            (&mut self.synchronized_output, overrides.synchronized_output),
+            (&mut self.mouse, overrides.mouse),
+            (&mut self.bracketed_paste, overrides.bracketed_paste),
+            (&mut self.osc52, overrides.osc52),
+        ];
+        for (capability, setting) in settings {
+            if let Some(setting) = setting {
+                *capability = setting;
+            }
+        }
+    }
+
+    pub fn summary(&self) -> String {
+        let colour = match self.colour {
+            ColourSupport::TrueColour => "truecolour",
+            ColourSupport::Ansi256 => "256",
+            ColourSupport::Ansi16 => "16",
+        };
+        let flags = [
+            ("italics", self.italics),
+            ("undercurl", self.undercurl),
+            ("synchronized_output", self.synchronized_output),
+            ("mouse", self.mouse),
+            ("bracketed_paste", self.bracketed_paste),
+            ("osc52", self.osc52),
+        ];
+        let mut parts = vec![format!("colour {}", colour)];
+        parts.extend(
+            flags
+                .iter()
+                .map(|(name, on)| format!("{}{}", if *on { "+" } else { "-" }, name)),
+        );
+        parts.join(" ")
+    }
+}
+
+fn colour_support(term: &str, colorterm: &str) -> ColourSupport {
+    if colorterm == "truecolor" || colorterm == "24bit" {
+        ColourSupport::TrueColour
+    } else if term.contains("256color") || term.contains("truecolor") || term.contains("direct") {
+        ColourSupport::Ansi256
+    } else {
+        ColourSupport::Ansi16
+    }
+}
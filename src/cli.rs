@@ -0,0 +1,58 @@
+use clap::Parser;
+use std::path::PathBuf;
+
+/// A small terminal text editor.
+#[derive(Parser)]
+#[command(version, about)]
+pub struct Args {
+    /// Files to edit; the first is opened and the rest are reached with :next.
+    /// A `+N`, `+` or `+/pattern` argument moves the cursor after opening.
+    #[arg(value_name = "FILE")]
+    pub files: Vec<String>,
+
+    /// Open files read-only without taking their lock
+    #[arg(long)]
+    pub readonly: bool,
+
+    /// Read options from this file instead of the usual config layers
+    #[arg(long, value_name = "PATH")]
+    pub config: Option<PathBuf>,
+
+    /// Start with built-in defaults, skipping config files and session recovery
+    #[arg(long)]
+    pub clean: bool,
+
+    /// Reload syntax and theme definitions when they change on disk
+    #[arg(long)]
+    pub dev: bool,
+
+    /// Apply `|`-separated ex commands to the first file without a terminal
+    #[arg(long, value_name = "COMMANDS")]
+    pub batch: Option<String>,
+
+    /// Play a key sequence on a headless screen and print the final frame
+    #[arg(long, value_name = "KEYS")]
+    pub snapshot: Option<String>,
+
+    /// Follow the files like `tail -f` in a read-only view
+    #[arg(long)]
+    pub tail: bool,
+
+    #[arg(skip)]
+    pub start_position: Option<String>,
+}
+
+impl Args {
+    pub fn parse_env() -> Self {
+        let mut args = Self::parse();
+        if let Some(index) = args.files.iter().position(|file| file.starts_with('+')) {
+            let position = args.files.remove(index);
+            args.start_position = Some(position[1..].to_string());
+        }
+        args
+    }
+
+    pub fn filename(&self) -> Option<&str> {
+        self.files.first().map(String::as_str)
+    }
+}
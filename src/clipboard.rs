@@ -0,0 +1,72 @@
+use crate::transform;
+use std::env;
+use std::io::{stdout, Write};
+use std::process::{Command, Stdio};
+
+const COMMANDS: [(&str, &[&str]); 4] = [
+    ("wl-copy", &[]),
+    ("xclip", &["-selection", "clipboard"]),
+    ("xsel", &["--clipboard", "--input"]),
+    ("pbcopy", &[]),
+];
+
+pub fn copy(text: &str, osc52: bool) -> Result<(), std::io::Error> {
+    if cfg!(windows) {
+        return copy_with_windows_api(&text.replace('\n', "\r\n"));
+    }
+    if !osc52 {
+        return copy_with_command(text);
+    }
+    let mut stdout = stdout();
+    write!(
+        stdout,
+        "\x1b]52;c;{}\x07",
+        transform::base64_encode(text.as_bytes())
+    )?;
+    stdout.flush()
+}
+
+#[cfg(windows)]
+fn copy_with_windows_api(text: &str) -> Result<(), std::io::Error> {
+    clipboard_win::set_clipboard_string(text).map_err(|err| std::io::Error::other(err.to_string()))
+}
+
+#[cfg(not(windows))]
+fn copy_with_windows_api(_text: &str) -> Result<(), std::io::Error> {
+    unreachable!("the Windows clipboard API is only used on Windows")
+}
+
+fn copy_with_command(text: &str) -> Result<(), std::io::Error> {
+    let wayland = env::var_os("WAYLAND_DISPLAY").is_some();
+    let copied = COMMANDS
+        .iter()
+        .filter(|(program, _)| wayland || *program != "wl-copy")
+        .any(|(program, args)| copy_with(program, args, text).is_ok());
+    if copied {
+        Ok(())
+    } else {
+        Err(std::io::Error::other(
+            "no OSC 52 support and no clipboard command (wl-copy, xclip, xsel, pbcopy) worked",
+        ))
+    }
+}
+
+fn copy_with(program: &str, args: &[&str], text: &str) -> Result<(), std::io::Error> {
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(text.as_bytes())?;
+    }
+    let status = child.wait()?;
+    if !status.success() {
+        return Err(std::io::Error::other(format!(
+            "{} exited with {}",
+            program, status
+        )));
+    }
+    Ok(())
+}
@@ -0,0 +1,76 @@
+use chrono::Local;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+pub fn minute() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs() / 60)
+}
+
+pub fn time(format: &str) -> String {
+    let of_day = local_seconds().rem_euclid(86_400);
+    let (hour, minute) = (of_day / 3_600, of_day % 3_600 / 60);
+    let hour12 = if hour % 12 == 0 { 12 } else { hour % 12 };
+    format
+        .replace("%H", &format!("{:02}", hour))
+        .replace("%I", &format!("{:02}", hour12))
+        .replace("%M", &format!("{:02}", minute))
+        .replace("%p", if hour < 12 { "AM" } else { "PM" })
+}
+
+pub fn today() -> (i64, u32, u32) {
+    civil_date(local_seconds().div_euclid(86_400))
+}
+
+// Civil-from-days, see http://howardhinnant.github.io/date_algorithms.html
+pub fn civil_date(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_index + 2) / 5 + 1) as u32;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    } as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+pub fn age(elapsed: Duration) -> String {
+    let minutes = elapsed.as_secs() / 60;
+    let days = minutes / 1_440;
+    match minutes {
+        0 => String::from("just now"),
+        1..=59 => format!("{} min ago", minutes),
+        60..=1_439 => format!("{} h {} min ago", minutes / 60, minutes % 60),
+        _ if days < 60 => format!("{} days ago", days),
+        _ if days < 730 => format!("{} months ago", days / 30),
+        _ => format!("{} years ago", days / 365),
+    }
+}
+
+pub fn local_seconds() -> i64 {
+    let now = Local::now();
+    now.timestamp() + i64::from(now.offset().local_minus_utc())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_days_since_the_epoch_to_dates() {
+        assert_eq!(civil_date(0), (1970, 1, 1));
+        assert_eq!(civil_date(-1), (1969, 12, 31));
+        assert_eq!(civil_date(59), (1970, 3, 1));
+        assert_eq!(civil_date(11_016), (2000, 2, 29));
+        assert_eq!(civil_date(11_017), (2000, 3, 1));
+        assert_eq!(civil_date(-25_508), (1900, 3, 1));
+        assert_eq!(civil_date(20_742), (2026, 10, 16));
+    }
+}
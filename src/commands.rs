@@ -0,0 +1,73 @@
+use serde::Deserialize;
+
+pub const MAX_DEPTH: usize = 16;
+
+#[derive(Deserialize, Clone)]
+#[serde(untagged)]
+pub enum Steps {
+    One(String),
+    Many(Vec<String>),
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(untagged)]
+pub enum UserCommand {
+    Run(Steps),
+    Full {
+        run: Steps,
+        complete: Option<String>,
+        hint: Option<String>,
+    },
+}
+
+impl UserCommand {
+    pub fn steps(&self) -> &[String] {
+        let steps = match self {
+            Self::Run(steps) | Self::Full { run: steps, .. } => steps,
+        };
+        match steps {
+            Steps::One(step) => std::slice::from_ref(step),
+            Steps::Many(steps) => steps,
+        }
+    }
+
+    pub fn completes_paths(&self) -> bool {
+        matches!(self, Self::Full { complete: Some(kind), .. } if kind == "paths")
+    }
+
+    pub fn hint(&self) -> Option<&str> {
+        match self {
+            Self::Full { hint, .. } => hint.as_deref(),
+            Self::Run(_) => None,
+        }
+    }
+}
+
+pub fn substitute(step: &str, args: &str) -> Result<String, String> {
+    let words: Vec<&str> = args.split_whitespace().collect();
+    let mut expanded = String::new();
+    let mut chars = step.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '$' {
+            expanded.push(c);
+            continue;
+        }
+        match chars.peek().copied() {
+            Some('$') => expanded.push('$'),
+            Some('*') => expanded.push_str(args),
+            Some(digit @ '1'..='9') => {
+                let index = digit as usize - '1' as usize;
+                let word = words
+                    .get(index)
+                    .ok_or_else(|| format!("Missing argument ${}", digit))?;
+                expanded.push_str(word);
+            }
+            _ => {
+                expanded.push('$');
+                continue;
+            }
+        }
+        chars.next();
+    }
+    Ok(expanded)
+}
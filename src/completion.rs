@@ -0,0 +1,106 @@
+use std::fs;
+use std::path::{self, Path, MAIN_SEPARATOR_STR};
+
+const PATH_COMMANDS: [&str; 5] = ["e", "e!", "edit", "edit!", "review-export"];
+
+pub type Source = Box<dyn Fn(&str) -> Vec<String>>;
+
+pub struct Completion {
+    candidates: Vec<String>,
+    index: usize,
+}
+
+impl Completion {
+    pub fn new(source: &Source, input: &str) -> Option<Self> {
+        let candidates = source(input);
+        (!candidates.is_empty()).then_some(Self {
+            candidates,
+            index: 0,
+        })
+    }
+
+    pub fn is_unique(&self) -> bool {
+        self.candidates.len() == 1
+    }
+
+    pub fn current(&self) -> &str {
+        &self.candidates[self.index]
+    }
+
+    pub fn next(&mut self) {
+        self.index = (self.index + 1) % self.candidates.len();
+    }
+
+    pub fn previous(&mut self) {
+        self.index = self
+            .index
+            .checked_sub(1)
+            .unwrap_or(self.candidates.len() - 1);
+    }
+
+    pub fn hint(&self) -> String {
+        let names: Vec<String> = self
+            .candidates
+            .iter()
+            .enumerate()
+            .map(|(i, candidate)| {
+                let name = file_name(candidate);
+                if i == self.index {
+                    format!("[{}]", name)
+                } else {
+                    name.to_string()
+                }
+            })
+            .collect();
+        format!("  {}", names.join(" "))
+    }
+}
+
+pub fn paths(input: &str) -> Vec<String> {
+    let (dir, prefix) = match input.rfind(path::is_separator) {
+        Some(slash) => input.split_at(slash + 1),
+        None => ("", input),
+    };
+    let search = if dir.is_empty() { "." } else { dir };
+    let Ok(entries) = fs::read_dir(Path::new(search)) else {
+        return Vec::new();
+    };
+    let mut candidates: Vec<String> = entries
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            if !name.starts_with(prefix) || (name.starts_with('.') && !prefix.starts_with('.')) {
+                return None;
+            }
+            let slash = if entry.path().is_dir() {
+                MAIN_SEPARATOR_STR
+            } else {
+                ""
+            };
+            Some(format!("{}{}{}", dir, name, slash))
+        })
+        .collect();
+    candidates.sort();
+    candidates
+}
+
+pub fn command_paths(input: &str, user_commands: &[String]) -> Vec<String> {
+    let Some((command, argument)) = input.split_once(' ') else {
+        return Vec::new();
+    };
+    if !PATH_COMMANDS.contains(&command) && !user_commands.iter().any(|name| name == command) {
+        return Vec::new();
+    }
+    paths(argument.trim_start())
+        .into_iter()
+        .map(|path| format!("{} {}", command, path))
+        .collect()
+}
+
+fn file_name(candidate: &str) -> &str {
+    let trimmed = candidate.trim_end_matches(path::is_separator);
+    let start = trimmed
+        .rfind(|c| path::is_separator(c) || c == ' ')
+        .map_or(0, |index| index + 1);
+    &candidate[start..]
+}
@@ -0,0 +1,398 @@
+use crate::capabilities;
+use crate::paths;
+use crate::transform;
+use crate::ColourSupport;
+use crate::UserCommand;
+use crossterm::style::Color;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use toml::{Table, Value};
+
+const OPTIONS: [&str; 43] = [
+    "tab_width",
+    "expandtab",
+    "auto_pairs",
+    "line_numbers",
+    "wrap",
+    "scrollbar",
+    "tree_width",
+    "git_gutter",
+    "scroll_margin",
+    "subword_motions",
+    "ignore_case",
+    "smart_case",
+    "wrap_scan",
+    "search_provider",
+    "trash",
+    "backup",
+    "backup_count",
+    "undo_limit",
+    "undo_spill",
+    "autosave_interval",
+    "autosave_target",
+    "swap_interval",
+    "idle_timeout_ms",
+    "idle_actions",
+    "paste_command",
+    "paste_limit",
+    "author",
+    "save_guard",
+    "linters",
+    "theme",
+    "transforms",
+    "commands",
+    "keymaps",
+    "statusline",
+    "tabline",
+    "tabline_segments",
+    "clock_format",
+    "status_commands",
+    "save_hooks",
+    "colour_support",
+    "capabilities",
+    "colours",
+    "colors",
+];
+
+const COLOUR_OPTIONS: [&str; 4] = ["status_fg", "status_bg", "gutter_fg", "selection_bg"];
+const CAPABILITY_OPTIONS: [&str; 6] = [
+    "italics",
+    "undercurl",
+    "synchronized_output",
+    "mouse",
+    "bracketed_paste",
+    "osc52",
+];
+
+#[derive(Deserialize, PartialEq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum LineNumbers {
+    Off,
+    Absolute,
+    Relative,
+}
+
+#[derive(Deserialize, PartialEq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum Backup {
+    Off,
+    Tilde,
+    Dir,
+}
+
+#[derive(Deserialize, PartialEq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum AutosaveTarget {
+    File,
+    Recovery,
+}
+
+#[derive(Deserialize, PartialEq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum SaveGuard {
+    Off,
+    Warn,
+    Block,
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(try_from = "String")]
+pub struct Colour(pub Color);
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct Colours {
+    pub status_fg: Option<Colour>,
+    pub status_bg: Option<Colour>,
+    pub gutter_fg: Option<Colour>,
+    pub selection_bg: Option<Colour>,
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub tab_width: usize,
+    pub expandtab: bool,
+    pub auto_pairs: bool,
+    pub line_numbers: LineNumbers,
+    pub wrap: bool,
+    pub scrollbar: bool,
+    pub tree_width: usize,
+    pub git_gutter: bool,
+    pub scroll_margin: usize,
+    pub subword_motions: bool,
+    pub ignore_case: bool,
+    pub smart_case: bool,
+    pub wrap_scan: bool,
+    pub search_provider: String,
+    pub trash: bool,
+    pub backup: Backup,
+    pub backup_count: usize,
+    pub undo_limit: usize,
+    pub undo_spill: bool,
+    pub autosave_interval: u64,
+    pub autosave_target: AutosaveTarget,
+    pub swap_interval: u64,
+    pub idle_timeout_ms: u64,
+    pub idle_actions: Vec<String>,
+    pub paste_command: Option<String>,
+    pub paste_limit: usize,
+    pub author: Option<String>,
+    pub save_guard: BTreeMap<String, SaveGuard>,
+    pub linters: BTreeMap<String, String>,
+    pub theme: String,
+    pub transforms: BTreeMap<String, String>,
+    pub commands: BTreeMap<String, UserCommand>,
+    pub keymaps: BTreeMap<String, BTreeMap<String, String>>,
+    pub statusline: Vec<String>,
+    pub tabline: bool,
+    pub tabline_segments: Vec<String>,
+    pub clock_format: String,
+    pub status_commands: BTreeMap<String, String>,
+    pub save_hooks: BTreeMap<String, Vec<String>>,
+    pub colour_support: Option<ColourSupport>,
+    pub capabilities: capabilities::Overrides,
+    #[serde(alias = "colors")]
+    pub colours: Colours,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            tab_width: 4,
+            expandtab: false,
+            auto_pairs: true,
+            line_numbers: LineNumbers::Absolute,
+            wrap: false,
+            scrollbar: false,
+            tree_width: 30,
+            git_gutter: true,
+            scroll_margin: 0,
+            subword_motions: false,
+            ignore_case: false,
+            smart_case: false,
+            wrap_scan: true,
+            search_provider: String::from("literal"),
+            trash: true,
+            backup: Backup::Off,
+            backup_count: 5,
+            undo_limit: 64 * 1024 * 1024,
+            undo_spill: false,
+            autosave_interval: 0,
+            autosave_target: AutosaveTarget::File,
+            swap_interval: 5,
+            idle_timeout_ms: 2000,
+            idle_actions: Vec::new(),
+            paste_command: None,
+            paste_limit: 1024 * 1024,
+            author: None,
+            save_guard: BTreeMap::new(),
+            linters: BTreeMap::new(),
+            theme: String::from("default"),
+            transforms: BTreeMap::new(),
+            commands: BTreeMap::new(),
+            keymaps: BTreeMap::new(),
+            statusline: Vec::new(),
+            tabline: false,
+            tabline_segments: Vec::new(),
+            clock_format: String::from("%H:%M"),
+            status_commands: BTreeMap::new(),
+            save_hooks: BTreeMap::new(),
+            colour_support: None,
+            capabilities: capabilities::Overrides::default(),
+            colours: Colours::default(),
+        }
+    }
+}
+
+impl Config {
+    pub fn path() -> Option<PathBuf> {
+        Some(paths::config_dir()?.join("texty.toml"))
+    }
+
+    pub fn layer_paths() -> Vec<PathBuf> {
+        let system_dirs = env::var("XDG_CONFIG_DIRS")
+            .ok()
+            .filter(|dirs| !dirs.is_empty())
+            .unwrap_or_else(|| String::from("/etc/xdg"));
+        let mut paths: Vec<PathBuf> = system_dirs
+            .split(':')
+            .rev()
+            .filter(|dir| !dir.is_empty())
+            .map(|dir| PathBuf::from(dir).join("texty").join("texty.toml"))
+            .collect();
+        paths.extend(Self::path());
+        paths
+    }
+
+    pub fn defaults_only() -> Self {
+        Self::default()
+    }
+
+    pub fn load() -> Result<Self, String> {
+        let mut merged = Table::new();
+        for path in Self::layer_paths() {
+            if let Some(layer) = read_layer(&path)? {
+                merge(&mut merged, layer);
+            }
+        }
+        Self::from_table(merged)
+    }
+
+    pub fn load_file(path: &PathBuf) -> Result<Self, String> {
+        match read_layer(path)? {
+            Some(layer) => Self::from_table(layer),
+            None => Err(format!("{}: no such file", path.display())),
+        }
+    }
+
+    pub fn check() -> Vec<String> {
+        let mut problems = Vec::new();
+        let mut merged = Table::new();
+        for path in Self::layer_paths() {
+            match read_layer(&path) {
+                Ok(Some(layer)) => {
+                    for key in unknown_options(&layer) {
+                        problems.push(format!(
+                            "{}: unknown option `{}`, remove it or check its spelling",
+                            path.display(),
+                            key
+                        ));
+                    }
+                    merge(&mut merged, layer);
+                }
+                Ok(None) => (),
+                Err(err) => problems.push(err),
+            }
+        }
+
+        match Self::from_table(merged) {
+            Ok(config) => {
+                if config.tab_width == 0 {
+                    problems.push(String::from("tab_width must be at least 1"));
+                }
+                let commands = config
+                    .linters
+                    .iter()
+                    .map(|(key, command)| ("linters", key, command))
+                    .chain(
+                        config
+                            .transforms
+                            .iter()
+                            .map(|(key, command)| ("transforms", key, command)),
+                    );
+                for (table, key, command) in commands {
+                    if let Some(program) = transform::missing_program(command) {
+                        problems.push(format!(
+                            "{}.{}: `{}` is not installed or not on PATH; install it or fix the command",
+                            table, key, program
+                        ));
+                    }
+                }
+            }
+            Err(err) => problems.push(err),
+        }
+        problems
+    }
+
+    fn from_table(table: Table) -> Result<Self, String> {
+        Value::Table(table)
+            .try_into()
+            .map_err(|err: toml::de::Error| err.message().to_string())
+    }
+}
+
+fn read_layer(path: &PathBuf) -> Result<Option<Table>, String> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(_) if !path.exists() => return Ok(None),
+        Err(err) => return Err(format!("{}: {}", path.display(), err)),
+    };
+    toml::from_str(&contents)
+        .map(Some)
+        .map_err(|err| format!("{}: {}", path.display(), err.message()))
+}
+
+fn unknown_options(layer: &Table) -> Vec<String> {
+    let mut unknown = Vec::new();
+    for (key, value) in layer {
+        if !OPTIONS.contains(&key.as_str()) {
+            unknown.push(key.clone());
+        } else if let (true, Value::Table(colours)) = (key == "colours" || key == "colors", value) {
+            unknown.extend(
+                colours
+                    .keys()
+                    .filter(|name| !COLOUR_OPTIONS.contains(&name.as_str()))
+                    .map(|name| format!("{}.{}", key, name)),
+            );
+        } else if let (true, Value::Table(capabilities)) = (key == "capabilities", value) {
+            unknown.extend(
+                capabilities
+                    .keys()
+                    .filter(|name| !CAPABILITY_OPTIONS.contains(&name.as_str()))
+                    .map(|name| format!("{}.{}", key, name)),
+            );
+        }
+    }
+    unknown
+}
+
+fn merge(base: &mut Table, layer: Table) {
+    for (key, value) in layer {
+        match (base.get_mut(&key), value) {
+            (Some(Value::Table(base)), Value::Table(layer)) => merge(base, layer),
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+impl TryFrom<String> for Colour {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        parse_colour(&value)
+            .map(Colour)
+            .ok_or_else(|| format!("invalid colour `{}`", value))
+    }
+}
+
+fn parse_colour(value: &str) -> Option<Color> {
+    if let Some(hex) = value.strip_prefix('#') {
+        if hex.len() != 6 {
+            return None;
+        }
+        let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+        return Some(Color::Rgb {
+            r: channel(0)?,
+            g: channel(2)?,
+            b: channel(4)?,
+        });
+    }
+
+    let colour = match value.to_ascii_lowercase().replace(['-', ' '], "_").as_str() {
+        "reset" => Color::Reset,
+        "black" => Color::Black,
+        "dark_grey" | "dark_gray" => Color::DarkGrey,
+        "red" => Color::Red,
+        "dark_red" => Color::DarkRed,
+        "green" => Color::Green,
+        "dark_green" => Color::DarkGreen,
+        "yellow" => Color::Yellow,
+        "dark_yellow" => Color::DarkYellow,
+        "blue" => Color::Blue,
+        "dark_blue" => Color::DarkBlue,
+        "magenta" => Color::Magenta,
+        "dark_magenta" => Color::DarkMagenta,
+        "cyan" => Color::Cyan,
+        "dark_cyan" => Color::DarkCyan,
+        "white" => Color::White,
+        "grey" | "gray" => Color::Grey,
+        _ => return None,
+    };
+    Some(colour)
+}
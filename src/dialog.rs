@@ -0,0 +1,67 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+pub const YES_NO: [&str; 2] = ["yes", "no"];
+
+pub enum Reply {
+    Chosen(char),
+    Cancelled,
+    Ignored,
+}
+
+pub struct Dialog<'a> {
+    question: &'a str,
+    options: &'a [&'a str],
+    default: usize,
+}
+
+impl<'a> Dialog<'a> {
+    pub fn new(question: &'a str, options: &'a [&'a str], default: usize) -> Self {
+        Self {
+            question,
+            options,
+            default: usize::min(default, options.len().saturating_sub(1)),
+        }
+    }
+
+    pub fn yes_no(question: &'a str, default_yes: bool) -> Self {
+        Self::new(question, &YES_NO, usize::from(!default_yes))
+    }
+
+    pub fn prompt(&self) -> String {
+        let options: Vec<String> = self
+            .options
+            .iter()
+            .enumerate()
+            .map(|(i, label)| {
+                let mut chars = label.chars();
+                let key = chars.next().unwrap_or_default();
+                let key = if i == self.default {
+                    key.to_ascii_uppercase()
+                } else {
+                    key
+                };
+                format!("[{}]{}", key, chars.as_str())
+            })
+            .collect();
+        format!("{} {} ", self.question, options.join(" / "))
+    }
+
+    pub fn answer(&self, key: KeyEvent) -> Reply {
+        match (key.modifiers, key.code) {
+            (_, KeyCode::Esc) | (KeyModifiers::CONTROL, KeyCode::Char('c')) => Reply::Cancelled,
+            (_, KeyCode::Enter) => self.key(self.default).map_or(Reply::Ignored, Reply::Chosen),
+            (_, KeyCode::Char(c)) => (0..self.options.len())
+                .filter_map(|i| self.key(i))
+                .find(|key| key.eq_ignore_ascii_case(&c))
+                .map_or(Reply::Ignored, Reply::Chosen),
+            _ => Reply::Ignored,
+        }
+    }
+
+    fn key(&self, index: usize) -> Option<char> {
+        self.options
+            .get(index)
+            .and_then(|label| label.chars().next())
+            .map(|key| key.to_ascii_lowercase())
+    }
+}
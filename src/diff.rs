@@ -0,0 +1,108 @@
+use std::collections::HashMap;
+
+const MAX_TABLE_SIZE: usize = 4_000_000;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum DiffMark {
+    Added,
+    Changed,
+    Removed,
+}
+
+pub struct Hunk {
+    pub old_start: usize,
+    pub removed: Vec<String>,
+    pub new_start: usize,
+    pub added: usize,
+}
+
+pub fn diff(old: &[&str], new: &[&str]) -> Vec<Hunk> {
+    let prefix = old
+        .iter()
+        .zip(new)
+        .take_while(|(old, new)| old == new)
+        .count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(old, new)| old == new)
+        .count();
+    let old_middle = &old[prefix..old.len() - suffix];
+    let new_middle = &new[prefix..new.len() - suffix];
+
+    if old_middle.is_empty() && new_middle.is_empty() {
+        return Vec::new();
+    }
+    if old_middle.len().saturating_mul(new_middle.len()) > MAX_TABLE_SIZE {
+        return vec![Hunk {
+            old_start: prefix,
+            removed: old_middle.iter().map(|line| line.to_string()).collect(),
+            new_start: prefix,
+            added: new_middle.len(),
+        }];
+    }
+
+    let (n, m) = (old_middle.len(), new_middle.len());
+    let mut lcs = vec![0u32; (n + 1) * (m + 1)];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lcs[i * (m + 1) + j] = if old_middle[i] == new_middle[j] {
+                lcs[(i + 1) * (m + 1) + j + 1] + 1
+            } else {
+                u32::max(lcs[(i + 1) * (m + 1) + j], lcs[i * (m + 1) + j + 1])
+            };
+        }
+    }
+
+    let mut hunks: Vec<Hunk> = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    let mut in_hunk = false;
+    while i < n || j < m {
+        if i < n && j < m && old_middle[i] == new_middle[j] {
+            i += 1;
+            j += 1;
+            in_hunk = false;
+            continue;
+        }
+        if !in_hunk {
+            hunks.push(Hunk {
+                old_start: prefix + i,
+                removed: Vec::new(),
+                new_start: prefix + j,
+                added: 0,
+            });
+            in_hunk = true;
+        }
+        let Some(hunk) = hunks.last_mut() else {
+            break;
+        };
+        if j < m && (i == n || lcs[i * (m + 1) + j + 1] >= lcs[(i + 1) * (m + 1) + j]) {
+            hunk.added += 1;
+            j += 1;
+        } else {
+            hunk.removed.push(old_middle[i].to_string());
+            i += 1;
+        }
+    }
+    hunks
+}
+
+pub fn marks(hunks: &[Hunk]) -> HashMap<usize, DiffMark> {
+    let mut marks = HashMap::new();
+    for hunk in hunks {
+        if hunk.added == 0 {
+            marks.insert(hunk.new_start, DiffMark::Removed);
+            continue;
+        }
+        let mark = if hunk.removed.is_empty() {
+            DiffMark::Added
+        } else {
+            DiffMark::Changed
+        };
+        for row in hunk.new_start..hunk.new_start + hunk.added {
+            marks.insert(row, mark);
+        }
+    }
+    marks
+}
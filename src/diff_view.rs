@@ -0,0 +1,282 @@
+use crate::diff::Hunk;
+use crate::intraline;
+use crate::Theme;
+use crossterm::event::{KeyCode, KeyEvent};
+use crossterm::style::{
+    Attribute, Color, Colors, ResetColor, SetAttribute, SetColors, SetForegroundColor,
+};
+use std::ops::Range;
+use unicode_segmentation::UnicodeSegmentation;
+
+const CONTEXT: usize = 3;
+
+struct Half {
+    text: String,
+    changed: Vec<Range<usize>>,
+}
+
+enum Row {
+    Header(String),
+    Lines(Option<Half>, Option<Half>),
+}
+
+enum Kind {
+    Context,
+    Removed,
+    Added,
+}
+
+enum Line<'a> {
+    Header(&'a str),
+    Split(&'a Option<Half>, &'a Option<Half>),
+    Single(char, &'a Half, Kind),
+}
+
+pub struct DiffView {
+    pub title: String,
+    rows: Vec<Row>,
+    split: bool,
+    top: usize,
+}
+
+impl DiffView {
+    pub fn new(title: &str, old: &[&str], new: &[&str], hunks: &[Hunk], split: bool) -> Self {
+        let mut groups: Vec<Vec<&Hunk>> = Vec::new();
+        for hunk in hunks {
+            match groups.last_mut() {
+                Some(group)
+                    if group.last().is_some_and(|last| {
+                        hunk.old_start <= last.old_start + last.removed.len() + 2 * CONTEXT
+                    }) =>
+                {
+                    group.push(hunk);
+                }
+                _ => groups.push(vec![hunk]),
+            }
+        }
+
+        let mut rows = Vec::new();
+        for group in groups {
+            let first = group[0];
+            let old_start = first.old_start.saturating_sub(CONTEXT);
+            let new_start = first.new_start - (first.old_start - old_start);
+            let header = rows.len();
+            rows.push(Row::Header(String::new()));
+            let (mut o, mut n) = (old_start, new_start);
+            for hunk in group {
+                while o < hunk.old_start {
+                    rows.push(context(old[o]));
+                    o += 1;
+                    n += 1;
+                }
+                let added = &new[hunk.new_start..hunk.new_start + hunk.added];
+                for i in 0..usize::max(hunk.removed.len(), added.len()) {
+                    let removed = hunk.removed.get(i).map(String::as_str);
+                    let (old_changed, new_changed) = match (removed, added.get(i)) {
+                        (Some(removed), Some(added)) => intraline::changes(removed, added),
+                        _ => (Vec::new(), Vec::new()),
+                    };
+                    rows.push(Row::Lines(
+                        removed.map(|text| half(text, old_changed)),
+                        added.get(i).map(|text| half(text, new_changed)),
+                    ));
+                }
+                o += hunk.removed.len();
+                n += hunk.added;
+            }
+            let mut trailing = 0;
+            while trailing < CONTEXT && o < old.len() && n < new.len() {
+                rows.push(context(old[o]));
+                o += 1;
+                n += 1;
+                trailing += 1;
+            }
+            rows[header] = Row::Header(format!(
+                "@@ -{},{} +{},{} @@",
+                old_start + 1,
+                o - old_start,
+                new_start + 1,
+                n - new_start
+            ));
+        }
+
+        Self {
+            title: title.to_string(),
+            rows,
+            split,
+            top: 0,
+        }
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent, height: usize) -> bool {
+        let page = height.saturating_sub(1).max(1);
+        let last = self.lines().len().saturating_sub(1);
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return false,
+            KeyCode::Char('j') | KeyCode::Down => self.top = self.top.saturating_add(1),
+            KeyCode::Char('k') | KeyCode::Up => self.top = self.top.saturating_sub(1),
+            KeyCode::Char(' ') | KeyCode::PageDown => self.top = self.top.saturating_add(page),
+            KeyCode::Char('b') | KeyCode::PageUp => self.top = self.top.saturating_sub(page),
+            KeyCode::Char('g') | KeyCode::Home => self.top = 0,
+            KeyCode::Char('G') | KeyCode::End => self.top = last.saturating_sub(page),
+            KeyCode::Char('n') => {
+                let headers = self.headers();
+                if let Some(&next) = headers.iter().find(|&&header| header > self.top) {
+                    self.top = next;
+                }
+            }
+            KeyCode::Char('N') => {
+                let headers = self.headers();
+                if let Some(&previous) = headers.iter().rev().find(|&&header| header < self.top) {
+                    self.top = previous;
+                }
+            }
+            KeyCode::Tab => {
+                self.split = !self.split;
+                self.top = 0;
+            }
+            _ => (),
+        }
+        self.top = usize::min(self.top, last);
+        true
+    }
+
+    pub fn draw(&self, lines: &mut [String], width: usize, theme: &Theme) {
+        let Some((header, rows)) = lines.split_first_mut() else {
+            return;
+        };
+        *header = format!(
+            "{}{}{}",
+            SetColors(Colors::new(theme.status_fg.0, theme.status_bg.0)),
+            pad(&self.title, width),
+            ResetColor
+        );
+        let display = self.lines();
+        for (index, line) in rows.iter_mut().enumerate() {
+            *line = display
+                .get(self.top.saturating_add(index))
+                .map_or_else(String::new, |line| line.render(width, theme));
+        }
+    }
+
+    fn headers(&self) -> Vec<usize> {
+        self.lines()
+            .iter()
+            .enumerate()
+            .filter(|(_, line)| matches!(line, Line::Header(_)))
+            .map(|(index, _)| index)
+            .collect()
+    }
+
+    fn lines(&self) -> Vec<Line<'_>> {
+        let mut lines = Vec::new();
+        for row in &self.rows {
+            match row {
+                Row::Header(text) => lines.push(Line::Header(text)),
+                Row::Lines(old, new) if self.split => lines.push(Line::Split(old, new)),
+                Row::Lines(Some(old), new) if is_context(Some(old), new.as_ref()) => {
+                    lines.push(Line::Single(' ', old, Kind::Context));
+                }
+                Row::Lines(old, new) => {
+                    if let Some(old) = old {
+                        lines.push(Line::Single('-', old, Kind::Removed));
+                    }
+                    if let Some(new) = new {
+                        lines.push(Line::Single('+', new, Kind::Added));
+                    }
+                }
+            }
+        }
+        lines
+    }
+}
+
+impl Line<'_> {
+    fn render(&self, width: usize, theme: &Theme) -> String {
+        match self {
+            Line::Header(text) => format!(
+                "{}{}{}",
+                SetForegroundColor(theme.gutter_fg.0),
+                pad(text, width),
+                ResetColor
+            ),
+            Line::Split(old, new) => {
+                let left = width.saturating_sub(1) / 2;
+                let right = width.saturating_sub(left + 1);
+                let is_context = is_context(old.as_ref(), new.as_ref());
+                format!(
+                    "{}{}│{}{}",
+                    side(old.as_ref(), '-', is_context, left, theme.diff_removed.0),
+                    SetForegroundColor(theme.gutter_fg.0),
+                    ResetColor,
+                    side(new.as_ref(), '+', is_context, right, theme.diff_added.0)
+                )
+            }
+            Line::Single(sign, half, kind) => {
+                let colour = match kind {
+                    Kind::Context => theme.none.0,
+                    Kind::Removed => theme.diff_removed.0,
+                    Kind::Added => theme.diff_added.0,
+                };
+                styled(*sign, &half.text, &half.changed, width, colour)
+            }
+        }
+    }
+}
+
+fn context(text: &str) -> Row {
+    Row::Lines(Some(half(text, Vec::new())), Some(half(text, Vec::new())))
+}
+
+fn half(text: &str, changed: Vec<Range<usize>>) -> Half {
+    Half {
+        text: text.replace('\t', " "),
+        changed,
+    }
+}
+
+fn is_context(old: Option<&Half>, new: Option<&Half>) -> bool {
+    matches!((old, new), (Some(old), Some(new)) if old.text == new.text)
+}
+
+fn side(half: Option<&Half>, sign: char, is_context: bool, width: usize, colour: Color) -> String {
+    match half {
+        Some(half) if is_context => styled(' ', &half.text, &[], width, Color::Reset),
+        Some(half) => styled(sign, &half.text, &half.changed, width, colour),
+        None => " ".repeat(width),
+    }
+}
+
+fn styled(sign: char, text: &str, changed: &[Range<usize>], width: usize, colour: Color) -> String {
+    let mut line = format!("{}{}", SetForegroundColor(colour), sign);
+    let mut reversed = false;
+    let mut used = 1;
+    for (index, grapheme) in text.graphemes(true).enumerate() {
+        if used >= width {
+            break;
+        }
+        let in_change = changed.iter().any(|range| range.contains(&index));
+        if in_change != reversed {
+            reversed = in_change;
+            let attribute = if reversed {
+                Attribute::Reverse
+            } else {
+                Attribute::NoReverse
+            };
+            line.push_str(&format!("{}", SetAttribute(attribute)));
+        }
+        line.push_str(grapheme);
+        used += 1;
+    }
+    if reversed {
+        line.push_str(&format!("{}", SetAttribute(Attribute::NoReverse)));
+    }
+    line.push_str(&" ".repeat(width.saturating_sub(used)));
+    line.push_str(&format!("{}", ResetColor));
+    line
+}
+
+fn pad(text: &str, width: usize) -> String {
+    let graphemes: Vec<&str> = text.graphemes(true).take(width).collect();
+    format!("{:<width$}", graphemes.concat(), width = width)
+}
@@ -1,113 +1,900 @@
+use crate::ansi;
+use crate::diff;
+use crate::templates;
 use crate::FileType;
+use crate::Matcher;
+use crate::Review;
 use crate::Position;
 use crate::Row;
+use crate::RowStore;
 use crate::SearchDirection;
+use crate::{Change, UndoHistory};
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
-use std::io::{Error, Write};
+use std::io::{self, BufWriter, Error, Read, Write};
+use std::ops::RangeInclusive;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::SystemTime;
+use unicode_segmentation::UnicodeSegmentation;
+
+const BRACKET_SCAN_ROWS: usize = 5000;
+
+static REVISIONS: AtomicU64 = AtomicU64::new(0);
+const VOID_ELEMENTS: [&str; 14] = [
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "param", "source",
+    "track", "wbr",
+];
+
+struct Token {
+    start: usize,
+    end: usize,
+    name: String,
+    opening: bool,
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum ShiftDirection {
+    Left,
+    Right,
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub struct DiskStamp {
+    modified: Option<SystemTime>,
+    len: u64,
+}
+
+impl DiskStamp {
+    fn of(filename: &str) -> Option<Self> {
+        let metadata = fs::metadata(filename).ok()?;
+        Some(Self {
+            modified: metadata.modified().ok(),
+            len: metadata.len(),
+        })
+    }
+}
 
 #[derive(Default)]
 pub struct Document {
-    rows: Vec<Row>,
+    rows: RowStore,
     pub filename: Option<String>,
     is_dirty: bool,
     file_type: FileType,
+    history: UndoHistory,
+    marks: HashMap<char, Position>,
+    review: Review,
+    tracked: Option<Vec<Change>>,
+    revision: u64,
+    crlf: bool,
+    disk_stamp: Option<DiskStamp>,
+    keymap: BTreeMap<String, String>,
 }
 
 impl Document {
     pub fn open(filename: &str) -> Result<Self, std::io::Error> {
         let contents = fs::read_to_string(filename)?;
-        let file_type = FileType::from(filename);
-        let mut rows = Vec::new();
-        for line in contents.lines() {
-            let mut row = Row::from(line);
-            row.highlight(file_type.highlighting_options(), None);
-            rows.push(row);
-        }
+        Ok(Self::from_contents(&contents, Some(filename)))
+    }
+
+    pub fn create(filename: &str) -> Self {
+        Self::from_contents("", Some(filename))
+    }
+
+    pub fn scratch(contents: &str) -> Self {
+        Self::from_contents(contents, None)
+    }
+
+    pub fn from_stdin() -> Result<Self, Error> {
+        let mut bytes = Vec::new();
+        io::stdin().lock().read_to_end(&mut bytes)?;
+        Ok(Self::from_contents(&String::from_utf8_lossy(&bytes), None))
+    }
+
+    pub fn recover(filename: Option<&str>, contents: &str) -> Self {
+        let mut document = Self::from_contents(contents, filename);
+        document.is_dirty = true;
+        document.disk_stamp = None;
+        document
+    }
 
-        Ok(Self {
-            rows,
-            filename: Some(filename.to_string()),
+    fn from_contents(contents: &str, filename: Option<&str>) -> Self {
+        let file_type = filename.map_or_else(FileType::default, FileType::from);
+        Self {
+            rows: RowStore::from_text(contents),
+            filename: filename.map(str::to_string),
             is_dirty: false,
             file_type,
-        })
+            history: UndoHistory::default(),
+            marks: HashMap::new(),
+            review: filename.map_or_else(Review::default, Review::load),
+            tracked: None,
+            revision: next_revision(),
+            crlf: contents.matches("\r\n").count() * 2 > contents.matches('\n').count(),
+            disk_stamp: filename.and_then(DiskStamp::of),
+            keymap: BTreeMap::new(),
+        }
     }
 
     pub fn save(&mut self) -> Result<(), Error> {
         if let Some(filename) = &self.filename {
-            let mut file = fs::File::create(filename)?;
-            self.file_type = FileType::from(filename);
-            for row in &mut self.rows {
-                file.write_all(row.as_bytes())?;
-                file.write_all(b"\n")?;
-                row.highlight(self.file_type.highlighting_options(), None);
+            let mut file = BufWriter::new(fs::File::create(filename)?);
+            let file_type = FileType::from(filename);
+            let file_type_changed = file_type.name() != self.file_type.name();
+            self.file_type = file_type;
+            let line_ending: &[u8] = if self.crlf { b"\r\n" } else { b"\n" };
+            for line in self.rows.lines() {
+                file.write_all(line.as_bytes())?;
+                file.write_all(line_ending)?;
+            }
+            if file_type_changed {
+                for row in self.rows.built_mut() {
+                    row.clear_highlighting();
+                }
+                self.revision = next_revision();
             }
+            file.flush()?;
+            drop(file);
+            self.disk_stamp = DiskStamp::of(filename);
+            self.review.save(filename)?;
             self.is_dirty = false;
         }
 
         Ok(())
     }
 
-    pub fn highlight(&mut self, word: Option<&str>) {
-        for row in &mut self.rows {
-            row.highlight(self.file_type.highlighting_options(), word);
+    pub fn mark_saved(&mut self) {
+        self.is_dirty = false;
+        self.disk_stamp = self.filename.as_deref().and_then(DiskStamp::of);
+    }
+
+    pub fn saved_at(&self) -> Option<SystemTime> {
+        self.disk_stamp.and_then(|stamp| stamp.modified)
+    }
+
+    pub fn changed_on_disk(&self) -> Option<DiskStamp> {
+        let current = DiskStamp::of(self.filename.as_deref()?)?;
+        self.disk_stamp.filter(|stamp| *stamp != current)?;
+        Some(current)
+    }
+
+    pub fn refresh_file_type(&mut self) {
+        self.file_type = self
+            .filename
+            .as_deref()
+            .map_or_else(FileType::default, FileType::from);
+        for row in self.rows.built_mut() {
+            row.clear_highlighting();
         }
+        self.revision = next_revision();
+    }
+
+    pub fn contents(&self) -> String {
+        let mut contents = String::new();
+        for line in self.rows.lines() {
+            contents.push_str(line);
+            contents.push('\n');
+        }
+        contents
+    }
+
+    pub fn highlight_rows(&mut self, start: usize, end: usize) {
+        let mut index = start;
+        while index < usize::min(end, self.len()) {
+            if self.rows.get(index).is_some_and(Row::is_highlighted) {
+                index += 1;
+            } else {
+                index = self.rehighlight(index, end);
+            }
+        }
+    }
+
+    fn rehighlight(&mut self, start: usize, end: usize) -> usize {
+        let opts = self.file_type.highlighting_options();
+        let start = if opts.block_comment().is_none() {
+            start
+        } else {
+            (0..start)
+                .rev()
+                .find(|&index| self.rows.get(index).is_some_and(Row::is_highlighted))
+                .map_or(0, |index| index + 1)
+        };
+        let mut in_comment = start
+            .checked_sub(1)
+            .and_then(|previous| self.rows.get(previous))
+            .is_some_and(Row::ends_in_comment);
+        let mut index = start;
+        while let Some(row) = self.rows.get_mut(index) {
+            if index > start {
+                let up_to_date = row.is_highlighted() && row.starts_in_comment() == in_comment;
+                if up_to_date || (!row.is_highlighted() && index >= end) {
+                    break;
+                }
+            }
+            in_comment = row.highlight(opts, in_comment);
+            index += 1;
+        }
+        index
     }
 
     pub fn insert(&mut self, at: &Position, c: char) {
-        self.is_dirty = true;
-        if c == '\n' {
-            self.insert_newline(at);
+        self.insert_str(at, &c.to_string());
+    }
+
+    pub fn delete(&mut self, at: &Position) {
+        let Some(row) = self.rows.get(at.y) else {
             return;
+        };
+        let end = if at.x < row.len() {
+            Position {
+                x: at.x.saturating_add(1),
+                y: at.y,
+            }
+        } else if at.y.saturating_add(1) < self.len() {
+            Position {
+                x: 0,
+                y: at.y.saturating_add(1),
+            }
+        } else {
+            return;
+        };
+        self.delete_range(at, &end);
+    }
+
+    pub fn insert_str(&mut self, at: &Position, text: &str) -> Position {
+        let len = self.len();
+        let (start, text) = match self.rows.get(len.saturating_sub(1)) {
+            Some(last) if at.y >= len => (
+                Position {
+                    x: last.len(),
+                    y: len.saturating_sub(1),
+                },
+                format!("\n{}", text),
+            ),
+            _ => (*at, text.to_string()),
+        };
+        if text.is_empty() {
+            return start;
+        }
+
+        self.is_dirty = true;
+        let end = self.raw_insert_str(&start, &text);
+        self.history.record(Change {
+            start,
+            removed: String::new(),
+            inserted: text,
+        });
+        end
+    }
+
+    pub fn delete_range(&mut self, start: &Position, end: &Position) -> String {
+        let removed = self.raw_delete_range(start, end);
+        if !removed.is_empty() {
+            self.is_dirty = true;
+            self.history.record(Change {
+                start: *start,
+                removed: removed.clone(),
+                inserted: String::new(),
+            });
         }
-        if at.y >= self.len() {
-            let mut row = Row::default();
-            row.insert(0, c);
-            row.highlight(self.file_type.highlighting_options(), None);
-            self.rows.push(row);
+        removed
+    }
+
+    pub fn replace_range(&mut self, start: &Position, end: &Position, text: &str) -> Position {
+        self.delete_range(start, end);
+        self.insert_str(start, text)
+    }
+
+    pub fn replace_text(&mut self, start: &Position, end: &Position, text: &str) {
+        let old_text = self.text_between(start, &self.clamp(end));
+        let old: Vec<&str> = old_text.split('\n').collect();
+        let new: Vec<&str> = text.split('\n').collect();
+        let line_start = |i: usize| Position {
+            x: if i == 0 { start.x } else { 0 },
+            y: start.y + i,
+        };
+        let line_end = |i: usize| Position {
+            x: line_start(i).x + Row::from(old[i]).len(),
+            y: start.y + i,
+        };
+
+        for hunk in diff::diff(&old, &new).iter().rev() {
+            let removed = hunk.removed.len();
+            let lines = &new[hunk.new_start..hunk.new_start + hunk.added];
+            let added = lines.join("\n");
+            let first = hunk.old_start;
+            if removed > 0 && hunk.added > 0 {
+                let paired = usize::min(removed, hunk.added);
+                if hunk.added > paired {
+                    let extra = lines[paired..].join("\n");
+                    self.insert_str(&line_end(first + removed - 1), &format!("\n{}", extra));
+                } else if removed > paired {
+                    self.delete_range(
+                        &line_end(first + paired - 1),
+                        &line_end(first + removed - 1),
+                    );
+                }
+                for offset in (0..paired).rev() {
+                    let y = first + offset;
+                    self.replace_within_line(&line_start(y), old[y], lines[offset]);
+                }
+            } else if removed == 0 {
+                if first < old.len() {
+                    self.insert_str(&line_start(first), &format!("{}\n", added));
+                } else {
+                    self.insert_str(&line_end(first - 1), &format!("\n{}", added));
+                }
+            } else if first + removed < old.len() {
+                self.delete_range(&line_start(first), &line_start(first + removed));
+            } else if first > 0 {
+                self.delete_range(&line_end(first - 1), &line_end(first + removed - 1));
+            } else {
+                self.delete_range(&line_start(0), &line_end(removed - 1));
+            }
+        }
+    }
+
+    fn replace_within_line(&mut self, at: &Position, old: &str, new: &str) {
+        let old: Vec<&str> = old.graphemes(true).collect();
+        let new: Vec<&str> = new.graphemes(true).collect();
+        let prefix = old.iter().zip(&new).take_while(|(a, b)| a == b).count();
+        let suffix = old[prefix..]
+            .iter()
+            .rev()
+            .zip(new[prefix..].iter().rev())
+            .take_while(|(a, b)| a == b)
+            .count();
+        let start = Position {
+            x: at.x + prefix,
+            y: at.y,
+        };
+        let end = Position {
+            x: at.x + old.len() - suffix,
+            y: at.y,
+        };
+        self.replace_range(&start, &end, &new[prefix..new.len() - suffix].concat());
+    }
+
+    pub fn replace_row(&mut self, index: usize, content: &str) {
+        if let Some(row) = self.rows.get(index) {
+            let end = Position {
+                x: row.len(),
+                y: index,
+            };
+            self.replace_text(&Position { x: 0, y: index }, &end, content);
+        }
+    }
+
+    pub fn has_escapes(&self) -> bool {
+        self.rows.lines().any(ansi::contains_escapes)
+    }
+
+    pub fn convert_escapes(&mut self, colour: bool, mark_dirty: bool) -> usize {
+        let was_dirty = self.is_dirty;
+        let mut converted = 0;
+        for index in 0..self.len() {
+            let Some(row) = self.rows.get(index) else {
+                continue;
+            };
+            if !ansi::contains_escapes(row.as_str()) {
+                continue;
+            }
+            let (text, spans) = ansi::parse(row.as_str());
+            self.replace_row(index, &text);
+            if let (true, Some(row)) = (colour, self.rows.get_mut(index)) {
+                row.set_colours(spans);
+            }
+            converted += 1;
+        }
+        if !mark_dirty {
+            self.is_dirty = was_dirty;
+        }
+        converted
+    }
+
+    pub fn insert_row(&mut self, at: usize, content: &str) {
+        if at < self.len() {
+            self.insert_str(&Position { x: 0, y: at }, &format!("{}\n", content));
+        } else if self.is_empty() {
+            self.insert_str(&Position::default(), content);
         } else {
-            let row = self.rows.get_mut(at.y).unwrap();
-            row.insert(at.x, c);
-            row.highlight(self.file_type.highlighting_options(), None);
+            self.insert_str(&Position { x: 0, y: at }, content);
         }
     }
 
-    fn insert_newline(&mut self, at: &Position) {
+    pub fn delete_rows(&mut self, first: usize, last: usize) -> String {
         let len = self.len();
-        if at.y > len {
-            return;
+        if first >= len {
+            return String::new();
+        }
+        let last = usize::min(last, len - 1);
+        let row_len = |y: usize| self.rows.get(y).map_or(0, Row::len);
+        let line_end = Position {
+            x: row_len(last),
+            y: last,
+        };
+        let mut removed = self.yank_range(&Position { x: 0, y: first }, &line_end);
+        removed.push('\n');
+
+        if last + 1 < len {
+            self.delete_range(
+                &Position { x: 0, y: first },
+                &Position { x: 0, y: last + 1 },
+            );
+        } else if first > 0 {
+            let previous = Position {
+                x: row_len(first - 1),
+                y: first - 1,
+            };
+            self.delete_range(&previous, &line_end);
+        } else {
+            self.delete_range(&Position { x: 0, y: 0 }, &line_end);
+        }
+        removed
+    }
+
+    pub fn shift_rows(
+        &mut self,
+        rows: RangeInclusive<usize>,
+        direction: ShiftDirection,
+        tab_width: usize,
+        expandtab: bool,
+    ) {
+        let tab_width = usize::max(tab_width, 1);
+        self.history.begin();
+        for y in rows {
+            let Some(row) = self.rows.get(y) else {
+                break;
+            };
+            if direction == ShiftDirection::Right {
+                if !row.is_empty() {
+                    let indent = if expandtab {
+                        " ".repeat(tab_width)
+                    } else {
+                        String::from("\t")
+                    };
+                    self.insert_str(&Position { x: 0, y }, &indent);
+                }
+                continue;
+            }
+            let line = row.as_str();
+            let width = if line.starts_with('\t') {
+                1
+            } else {
+                line.len() - line.trim_start_matches(' ').len()
+            };
+            let width = usize::min(width, tab_width);
+            if width > 0 {
+                self.delete_range(&Position { x: 0, y }, &Position { x: width, y });
+            }
+        }
+        self.history.end();
+    }
+
+    pub fn matching_bracket(&mut self, at: &Position) -> Option<Position> {
+        self.highlight_rows(at.y, at.y.saturating_add(1));
+        let (_, bracket) = self
+            .rows
+            .get(at.y)?
+            .brackets()
+            .into_iter()
+            .find(|&(x, _)| x == at.x)?;
+        let (partner, forward) = match bracket {
+            '(' => (')', true),
+            '[' => (']', true),
+            '{' => ('}', true),
+            ')' => ('(', false),
+            ']' => ('[', false),
+            _ => ('{', false),
+        };
+        let rows: Box<dyn Iterator<Item = usize>> = if forward {
+            let last = usize::min(at.y.saturating_add(BRACKET_SCAN_ROWS), self.len());
+            Box::new(at.y..last)
+        } else {
+            Box::new((at.y.saturating_sub(BRACKET_SCAN_ROWS)..=at.y).rev())
+        };
+
+        let mut depth: usize = 0;
+        for y in rows {
+            self.highlight_rows(y, y.saturating_add(1));
+            let mut brackets = self.rows.get(y).map(Row::brackets).unwrap_or_default();
+            if !forward {
+                brackets.reverse();
+            }
+            for (x, c) in brackets {
+                let ahead = if forward {
+                    y > at.y || x >= at.x
+                } else {
+                    y < at.y || x <= at.x
+                };
+                if !ahead {
+                    continue;
+                }
+                if c == bracket {
+                    depth += 1;
+                } else if c == partner {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Some(Position { x, y });
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    pub fn matching_pair(&mut self, at: &Position) -> Option<(Position, usize)> {
+        if let Some(position) = self.matching_bracket(at) {
+            return Some((position, 1));
+        }
+        if self.file_type.match_tags() {
+            let tags = |row: &Row| {
+                row.tags()
+                    .into_iter()
+                    .filter(|(_, _, name, _)| !VOID_ELEMENTS.contains(&name.as_str()))
+                    .map(|(start, end, name, closing)| Token {
+                        start,
+                        end,
+                        name,
+                        opening: !closing,
+                    })
+                    .collect()
+            };
+            if let Some(found) = self.matching_token(at, tags) {
+                return Some(found);
+            }
+        }
+        let pairs = self.file_type.match_pairs();
+        if pairs.is_empty() {
+            return None;
+        }
+        let keywords = |row: &Row| {
+            row.code_words()
+                .into_iter()
+                .filter_map(|(start, end, word)| {
+                    pairs.iter().find_map(|(open, close)| {
+                        let opening = word == open;
+                        (opening || word == close).then(|| Token {
+                            start,
+                            end,
+                            name: close.clone(),
+                            opening,
+                        })
+                    })
+                })
+                .collect()
+        };
+        self.matching_token(at, keywords)
+    }
+
+    fn matching_token<F>(&self, at: &Position, tokens: F) -> Option<(Position, usize)>
+    where
+        F: Fn(&Row) -> Vec<Token>,
+    {
+        let first = at.y.saturating_sub(BRACKET_SCAN_ROWS);
+        let last = usize::min(at.y.saturating_add(BRACKET_SCAN_ROWS), self.len());
+        let current = tokens(self.rows.get(at.y)?)
+            .into_iter()
+            .find(|token| (token.start..token.end).contains(&at.x))?;
+        let tokens_in = |y: usize| {
+            self.rows
+                .get(y)
+                .map(&tokens)
+                .unwrap_or_default()
+                .into_iter()
+                .filter(|token| token.name == current.name)
+                .map(move |token| (y, token))
+        };
+        let candidates: Box<dyn Iterator<Item = (usize, Token)>> = if current.opening {
+            Box::new(
+                (at.y..last)
+                    .flat_map(tokens_in)
+                    .filter(|(y, token)| *y > at.y || token.start > current.start),
+            )
+        } else {
+            Box::new(
+                (first..=at.y)
+                    .rev()
+                    .flat_map(|y| tokens_in(y).rev())
+                    .filter(|(y, token)| *y < at.y || token.start < current.start),
+            )
+        };
+
+        let mut depth: usize = 1;
+        for (y, token) in candidates {
+            if token.opening == current.opening {
+                depth += 1;
+            } else {
+                depth -= 1;
+                if depth == 0 {
+                    return Some((Position { x: token.start, y }, token.end - token.start));
+                }
+            }
+        }
+        None
+    }
+
+    pub fn toggle_comment(&mut self, rows: RangeInclusive<usize>) -> Option<bool> {
+        let leader = self
+            .file_type
+            .highlighting_options()
+            .line_comment()?
+            .to_string();
+        let indent = |line: &str| line.chars().take_while(|c| c.is_whitespace()).count();
+        let lines: Vec<(usize, usize, Option<usize>)> = rows
+            .filter_map(|y| {
+                let line = self.rows.get(y)?.as_str();
+                let trimmed = line.trim_start();
+                let commented = trimmed
+                    .strip_prefix(&leader)
+                    .map(|rest| usize::from(rest.starts_with(' ')));
+                (!trimmed.is_empty()).then(|| (y, indent(line), commented))
+            })
+            .collect();
+        if lines.is_empty() {
+            return Some(false);
+        }
+
+        let uncomment = lines.iter().all(|(_, _, commented)| commented.is_some());
+        let leader_len = Row::from(leader.as_str()).len();
+        let column = lines.iter().map(|&(_, x, _)| x).min().unwrap_or(0);
+        self.history.begin();
+        for &(y, x, commented) in &lines {
+            if uncomment {
+                let end = Position {
+                    x: x + leader_len + commented.unwrap_or(0),
+                    y,
+                };
+                self.delete_range(&Position { x, y }, &end);
+            } else {
+                self.insert_str(&Position { x: column, y }, &format!("{} ", leader));
+            }
+        }
+        self.history.end();
+        Some(!uncomment)
+    }
+
+    pub fn set_header(&mut self, text: &str) -> Option<bool> {
+        let leader = self
+            .file_type
+            .highlighting_options()
+            .line_comment()?
+            .to_string();
+        let (start, len) = templates::header_span(self.lines(), &leader);
+        let mut lines = templates::comment_lines(text, &leader);
+        if len == 0 {
+            lines.push(String::new());
+        }
+        self.history.begin();
+        if len > 0 {
+            self.delete_rows(start, start + len - 1);
+        }
+        for (offset, line) in lines.iter().enumerate() {
+            self.insert_row(start + offset, line);
         }
-        if at.y == len {
+        self.history.end();
+        Some(len > 0)
+    }
+
+    pub fn join_rows(&mut self, index: usize) -> Option<Position> {
+        let current = self.rows.get(index)?;
+        let next = self.rows.get(index.saturating_add(1))?;
+        let trimmed = next.as_str().trim_start();
+        let separator = if current.as_str().is_empty()
+            || current.as_str().ends_with(char::is_whitespace)
+            || trimmed.is_empty()
+            || trimmed.starts_with(')')
+        {
+            ""
+        } else {
+            " "
+        };
+        let start = Position {
+            x: current.len(),
+            y: index,
+        };
+        let end = Position {
+            x: next.len() - Row::from(trimmed).len(),
+            y: index + 1,
+        };
+        self.replace_range(&start, &end, separator);
+        Some(start)
+    }
+
+    pub fn undo_history(&self) -> &UndoHistory {
+        &self.history
+    }
+
+    pub fn set_undo_limit(&mut self, limit: usize, spill: bool) {
+        self.history.set_limit(limit, spill);
+    }
+
+    pub fn seal_undo(&mut self) {
+        self.history.seal();
+    }
+
+    pub fn begin_undo(&mut self) {
+        self.history.begin();
+    }
+
+    pub fn end_undo(&mut self) {
+        self.history.end();
+    }
+
+    pub fn undo(&mut self) -> Option<Position> {
+        let group = self.history.pop_undo()?;
+        for change in group.iter().rev() {
+            self.raw_delete_range(&change.start, &change.inserted_end());
+            self.raw_insert_str(&change.start, &change.removed);
+        }
+        let position = group.first().map(|change| change.start);
+        self.history.push_redo(group);
+        self.is_dirty = true;
+        position
+    }
+
+    pub fn redo(&mut self) -> Option<Position> {
+        let group = self.history.pop_redo()?;
+        for change in &group {
+            self.raw_delete_range(&change.start, &change.removed_end());
+            self.raw_insert_str(&change.start, &change.inserted);
+        }
+        let position = group.last().map(Change::inserted_end);
+        self.history.push_undo(group);
+        self.is_dirty = true;
+        position
+    }
+
+    fn raw_insert_str(&mut self, at: &Position, text: &str) -> Position {
+        while self.len() <= at.y {
             self.rows.push(Row::default());
-            return;
         }
-        let current_row = &mut self.rows[at.y];
-        let mut new_row = current_row.split(at.x);
-        current_row.highlight(self.file_type.highlighting_options(), None);
-        new_row.highlight(self.file_type.highlighting_options(), None);
-        self.rows.insert(at.y + 1, new_row);
+        let Some(row) = self.rows.get_mut(at.y) else {
+            return *at;
+        };
+        let tail = row.split(at.x);
+        let mut lines = text.split('\n');
+        row.append(&Row::from(lines.next().unwrap_or_default()));
+
+        let mut y = at.y;
+        for line in lines {
+            y += 1;
+            self.rows.insert(y, Row::from(line));
+        }
+        let row = self.rows.get_mut(y).unwrap();
+        let end = Position { x: row.len(), y };
+        row.append(&tail);
+        self.rehighlight(at.y, y + 1);
+
+        self.remap(Change {
+            start: *at,
+            removed: String::new(),
+            inserted: text.to_string(),
+        });
+        end
     }
 
-    pub fn delete(&mut self, at: &Position) {
+    pub fn yank_range(&self, start: &Position, end: &Position) -> String {
         let len = self.len();
-        if at.y >= len {
-            return;
+        if len == 0 || (end.y, end.x) <= (start.y, start.x) || start.y >= len {
+            return String::new();
         }
+        self.text_between(start, &self.clamp(end))
+    }
 
-        self.is_dirty = true;
+    pub fn clamp(&self, at: &Position) -> Position {
+        let last = self.len().saturating_sub(1);
+        if at.y > last {
+            return Position {
+                x: self.rows.get(last).map_or(0, Row::len),
+                y: last,
+            };
+        }
+        Position {
+            x: usize::min(at.x, self.rows.get(at.y).map_or(0, Row::len)),
+            y: at.y,
+        }
+    }
+
+    fn raw_delete_range(&mut self, start: &Position, end: &Position) -> String {
+        let removed = self.yank_range(start, end);
+        if removed.is_empty() {
+            return removed;
+        }
+        let end = self.clamp(end);
+
+        let tail = self.rows.get_mut(end.y).unwrap().split(end.x);
+        for _ in start.y..end.y {
+            self.rows.remove(start.y + 1);
+        }
+        let row = self.rows.get_mut(start.y).unwrap();
+        row.split(start.x);
+        row.append(&tail);
+        self.rehighlight(start.y, start.y + 1);
+
+        self.marks.retain(|_, mark| {
+            let inside =
+                (start.y, start.x) <= (mark.y, mark.x) && (mark.y, mark.x) < (end.y, end.x);
+            let whole_line = mark.y < end.y && (mark.y > start.y || start.x == 0);
+            !(inside && whole_line)
+        });
+        self.remap(Change {
+            start: *start,
+            removed: removed.clone(),
+            inserted: String::new(),
+        });
+        removed
+    }
 
-        if at.x == self.rows.get(at.y).unwrap().len() && at.y + 1 < len {
-            let next_row = self.rows.remove(at.y + 1);
-            let row = self.rows.get_mut(at.y).unwrap();
-            row.append(&next_row);
-            row.highlight(self.file_type.highlighting_options(), None);
+    fn remap(&mut self, change: Change) {
+        for mark in self.marks.values_mut() {
+            *mark = change.map(*mark);
+        }
+        self.review.remap(&change);
+        self.revision = next_revision();
+        if let Some(tracked) = &mut self.tracked {
+            tracked.push(change);
+        }
+    }
+
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    pub fn track_edits(&mut self) {
+        self.tracked = Some(Vec::new());
+    }
+
+    pub fn take_edits(&mut self) -> Vec<Change> {
+        self.tracked.take().unwrap_or_default()
+    }
+
+    pub fn set_mark(&mut self, name: char, at: Position) {
+        self.marks.insert(name, at);
+    }
+
+    pub fn mark(&self, name: char) -> Option<Position> {
+        let mark = self.marks.get(&name)?;
+        let len = self.rows.get(mark.y).map_or(0, Row::len);
+        Some(Position {
+            x: usize::min(mark.x, len),
+            y: mark.y,
+        })
+    }
+
+    pub fn keymap(&self) -> &BTreeMap<String, String> {
+        &self.keymap
+    }
+
+    pub fn map_key(&mut self, key: String, command: String) {
+        self.keymap.insert(key, command);
+    }
+
+    pub fn unmap_key(&mut self, key: &str) -> bool {
+        self.keymap.remove(key).is_some()
+    }
+
+    pub fn review(&self) -> &Review {
+        &self.review
+    }
+
+    pub fn annotate(&mut self, y: usize, comment: &str) -> Result<(), Error> {
+        if comment.is_empty() {
+            self.review.remove(y);
         } else {
-            let row = self.rows.get_mut(at.y).unwrap();
-            row.delete(at.x);
-            row.highlight(self.file_type.highlighting_options(), None);
+            self.review.set(y, comment);
+        }
+        match &self.filename {
+            Some(filename) if !self.is_dirty => self.review.save(filename),
+            _ => Ok(()),
         }
     }
 
-    pub fn find(&self, query: &str, at: &Position, direction: SearchDirection) -> Option<Position> {
+    pub fn find(
+        &self,
+        matcher: &dyn Matcher,
+        at: &Position,
+        direction: SearchDirection,
+    ) -> Option<Position> {
         if at.y >= self.rows.len() {
             return None;
         }
@@ -121,7 +908,7 @@ impl Document {
 
         for _ in start..end {
             if let Some(row) = self.rows.get(position.y) {
-                if let Some(x) = row.find(query, position.x, direction) {
+                if let Some(x) = row.find(matcher, position.x, direction) {
                     position.x = x;
                     return Some(position);
                 }
@@ -130,7 +917,7 @@ impl Document {
                     position.x = 0;
                 } else {
                     position.y = position.y.saturating_sub(1);
-                    position.x = self.rows[position.y].len();
+                    position.x = self.rows.get(position.y).map_or(0, Row::len);
                 }
             } else {
                 return None;
@@ -140,6 +927,102 @@ impl Document {
         None
     }
 
+    pub fn find_wrapping(
+        &self,
+        matcher: &dyn Matcher,
+        at: &Position,
+        direction: SearchDirection,
+    ) -> Option<(Position, bool)> {
+        if let Some(position) = self.find(matcher, at, direction) {
+            return Some((position, false));
+        }
+        let from = match direction {
+            SearchDirection::Forward => Position::default(),
+            SearchDirection::Backward => {
+                let y = self.len().saturating_sub(1);
+                Position {
+                    x: self.rows.get(y).map_or(0, Row::len),
+                    y,
+                }
+            }
+        };
+        self.find(matcher, &from, direction)
+            .map(|position| (position, true))
+    }
+
+    pub fn match_positions(&self, matcher: &dyn Matcher) -> Vec<(usize, usize)> {
+        self.rows
+            .iter()
+            .enumerate()
+            .flat_map(|(y, row)| row.matches(matcher).into_iter().map(move |(x, _)| (y, x)))
+            .collect()
+    }
+
+    pub fn byte_offset(&self, at: &Position) -> usize {
+        let y = usize::min(at.y, self.rows.len());
+        let preceding = self
+            .rows
+            .bytes_before(y)
+            .saturating_add(y.saturating_mul(self.newline_len()));
+        let within = self.rows.get(at.y).map_or(0, |row| row.byte_index(at.x));
+        preceding.saturating_add(within)
+    }
+
+    pub fn total_bytes(&self) -> usize {
+        self.rows
+            .total_bytes()
+            .saturating_add(self.rows.len().saturating_mul(self.newline_len()))
+    }
+
+    pub fn position_of_byte(&self, byte: usize) -> Position {
+        let y = self.rows.row_containing_byte(byte, self.newline_len());
+        let start = self.byte_offset(&Position { x: 0, y });
+        let Some(row) = self.rows.get(y) else {
+            return Position::default();
+        };
+        let within = byte.saturating_sub(start);
+        Position {
+            x: if within <= row.as_bytes().len() {
+                row.grapheme_index(within)
+            } else {
+                row.len()
+            },
+            y,
+        }
+    }
+
+    pub fn text_between(&self, start: &Position, end: &Position) -> String {
+        let mut text = String::new();
+        for y in start.y..=end.y {
+            let Some(row) = self.rows.get(y) else {
+                break;
+            };
+            let from = if y == start.y {
+                row.byte_index(start.x)
+            } else {
+                0
+            };
+            let to = if y == end.y {
+                row.byte_index(end.x)
+            } else {
+                row.as_bytes().len()
+            };
+            text.push_str(row.as_str().get(from..to).unwrap_or_default());
+            if y != end.y {
+                text.push('\n');
+            }
+        }
+        text
+    }
+
+    pub fn rows(&self) -> impl Iterator<Item = &Row> {
+        self.rows.iter()
+    }
+
+    pub fn lines(&self) -> impl Iterator<Item = &str> {
+        self.rows.lines()
+    }
+
     pub fn row(&self, index: usize) -> Option<&Row> {
         self.rows.get(index)
     }
@@ -148,6 +1031,14 @@ impl Document {
         self.file_type.name()
     }
 
+    pub fn auto_pairs(&self) -> &[(char, char)] {
+        self.file_type.auto_pairs()
+    }
+
+    pub fn outline(&self) -> &[String] {
+        self.file_type.outline()
+    }
+
     pub fn len(&self) -> usize {
         self.rows.len()
     }
@@ -156,7 +1047,36 @@ impl Document {
         self.rows.is_empty()
     }
 
+    pub fn is_crlf(&self) -> bool {
+        self.crlf
+    }
+
+    pub fn set_crlf(&mut self, crlf: bool) {
+        if self.crlf != crlf {
+            self.crlf = crlf;
+            self.is_dirty = true;
+        }
+    }
+
+    fn newline_len(&self) -> usize {
+        if self.crlf {
+            2
+        } else {
+            1
+        }
+    }
+
+    pub fn mark_dirty(&mut self) {
+        self.is_dirty = true;
+    }
+
     pub fn is_dirty(&self) -> bool {
         self.is_dirty
     }
 }
+
+// Revisions are unique across documents, so a cache keyed on one cannot mistake a newly
+// opened buffer for the one it replaced.
+fn next_revision() -> u64 {
+    REVISIONS.fetch_add(1, Ordering::Relaxed)
+}
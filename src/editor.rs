@@ -1,23 +1,134 @@
-use crate::{Document, Row, Terminal};
+use crate::backup;
+use crate::clipboard;
+use crate::clock;
+use crate::commands;
+use crate::completion::{self, Completion};
+use crate::dialog::Reply;
+use crate::diff::{self, DiffMark};
+use crate::events::Handler;
+use crate::ex;
+use crate::file_tree::TreeAction;
+use crate::filetype;
+use crate::finder;
+use crate::fuzzy;
+use crate::git;
+use crate::highlighting;
+use crate::hooks;
+use crate::input::{self, Expected};
+use crate::json;
+use crate::lint;
+use crate::macros;
+use crate::outline::{self, Symbol};
+use crate::paths;
+use crate::quickfix;
+use crate::refs;
+use crate::status_commands;
+use crate::table;
+use crate::templates;
+use crate::transform::{self, Transforms};
+use crate::{
+    Action, Args, AutosaveTarget, Backup, Capabilities, Config, Dialog, DiffView, DiskStamp,
+    Document, EditorEvent, EventBus, FileTree, FileType, History, HistoryKind, Layout, LineNumbers,
+    Listing, Lock, Matcher, Motion, Operator, Overlay, Quickfix, Register, Registers, Row,
+    SaveGuard, SearchProviders, Session, ShiftDirection, Swap, Templates, Terminal, Theme, Timings,
+    Trash, UserCommand, VisualLine, Watcher, Window,
+};
 use crossterm::{
-    event::{Event, KeyCode, KeyEvent, KeyModifiers},
-    style::{Color, Colors},
+    cursor::MoveToColumn,
+    event::{Event, KeyCode, KeyEvent, KeyModifiers, MouseButton, MouseEvent, MouseEventKind},
+    style::{Color, Colors, ResetColor, SetColors, SetForegroundColor},
 };
+use md5::Md5;
+use sha2::{Digest, Sha256};
+use signal_hook::consts::{SIGHUP, SIGTERM};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
 use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 use std::time::{Duration, Instant};
+use unicode_segmentation::UnicodeSegmentation;
 
-const STATUS_BG_COLOR: Color = Color::Rgb {
-    r: 153,
-    g: 217,
-    b: 140,
-};
-const STATUS_FG_COLOR: Color = Color::Rgb {
-    r: 43,
-    g: 45,
-    b: 66,
-};
+const MOUSE_SCROLL_LINES: usize = 3;
+const JUMP_LIST_SIZE: usize = 100;
+const PASTE_PREVIEW_BYTES: usize = 4096;
+const MESSAGE_LOG_SIZE: usize = 50;
+const OVERLAY_ITEMS: usize = 1000;
+const GREP_RESULTS: usize = 10_000;
+const EXPANSION_TOKENS: [&str; 3] = ["cword", "sel", "search"];
+
+type SearchMatches = (String, u64, Vec<(usize, usize)>);
 
-#[derive(Default, Clone, Copy)]
+const READ_ONLY_COMMANDS: [&str; 64] = [
+    "goto",
+    "view",
+    "normal",
+    "norm",
+    "w",
+    "write",
+    "w!",
+    "write!",
+    "e",
+    "edit",
+    "e!",
+    "edit!",
+    "n",
+    "next",
+    "n!",
+    "next!",
+    "N",
+    "prev",
+    "previous",
+    "N!",
+    "prev!",
+    "previous!",
+    "args",
+    "undo-info",
+    "timings",
+    "set",
+    "noh",
+    "nohlsearch",
+    "lines",
+    "symbols",
+    "macro",
+    "checkhealth",
+    "paths",
+    "capabilities",
+    "messages",
+    "mes",
+    "share",
+    "selection-stats",
+    "ansi",
+    "tree",
+    "map",
+    "unmap",
+    "theme",
+    "diff",
+    "registers",
+    "reg",
+    "grep",
+    "bgrep",
+    "copen",
+    "cw",
+    "cfilter",
+    "cfilter!",
+    "dups",
+    "hash",
+    "note",
+    "unnote",
+    "nnext",
+    "nprev",
+    "review-export",
+    "cnext",
+    "cn",
+    "cprev",
+    "cp",
+    "",
+];
+
+#[derive(Default, Clone, Copy, PartialEq)]
 pub struct Position {
     pub x: usize,
     pub y: usize,
@@ -28,7 +139,7 @@ struct StatusMessage {
     time: Instant,
 }
 
-#[derive(PartialEq, Clone, Copy)]
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
 pub enum SearchDirection {
     Forward,
     Backward,
@@ -43,343 +154,5517 @@ impl StatusMessage {
     }
 }
 
+#[derive(PartialEq, Clone, Copy)]
+enum WordMotion {
+    Forward,
+    Backward,
+    End,
+}
+
+#[derive(PartialEq, Clone, Copy)]
+enum MotionKind {
+    Exclusive,
+    Inclusive,
+    Linewise,
+}
+
 #[derive(PartialEq, Clone, Copy)]
 enum TerminalMode {
     Normal,
     Insert,
+    Visual,
 }
 
 pub struct Editor {
     should_quit: bool,
     terminal: Terminal,
     cursor_position: Position,
-    offset: Position,
+    window: Window,
     document: Document,
     status_message: StatusMessage,
     terminal_mode: TerminalMode,
+    count: Option<usize>,
+    pending: Option<char>,
+    operator: Option<(Operator, usize)>,
+    registers: Registers,
+    jumps: Vec<Position>,
+    jump_index: usize,
+    register: Option<char>,
+    terminated: Arc<AtomicBool>,
+    last_trashed: Option<String>,
+    prompt_hint: String,
+    prompt_history: Option<HistoryKind>,
+    prompt_completion: Option<completion::Source>,
+    history: History,
+    search_query: Option<String>,
+    search_matcher: Option<Box<dyn Matcher>>,
+    search_providers: SearchProviders,
+    last_search: Option<String>,
+    search_direction: SearchDirection,
+    selection_anchor: Position,
+    bracket_match: Option<(Position, usize)>,
+    bracket_match_at: Option<(Position, u64)>,
+    timings: Option<Timings>,
+    overlay: Option<Overlay>,
+    file_tree: Option<FileTree>,
+    listing: Option<Listing>,
+    diff_view: Option<DiffView>,
+    config: Config,
+    theme: Theme,
+    quickfix: Quickfix,
+    diff_marks: HashMap<usize, DiffMark>,
+    diff_base: Option<Vec<String>>,
+    diff_revision: u64,
+    search_rows: Option<(u64, Vec<usize>)>,
+    search_matches: Option<SearchMatches>,
+    word_count: Option<(u64, usize)>,
+    transforms: Transforms,
+    status_commands: status_commands::StatusCommands,
+    status_minute: u64,
+    blame: git::Blame,
+    pending_input: VecDeque<Event>,
+    replaying: bool,
+    recording: Option<(char, Vec<KeyEvent>)>,
+    macros: HashMap<char, Vec<KeyEvent>>,
+    last_macro: Option<char>,
+    events: EventBus,
+    last_input: Instant,
+    idle_fired: bool,
+    autosave_fired: bool,
+    autosave_failed: bool,
+    recovery_written: bool,
+    swap_written: Option<Instant>,
+    swap_disabled: bool,
+    backed_up: bool,
+    clean: bool,
+    lock: Option<Lock>,
+    read_only: bool,
+    arg_list: Vec<String>,
+    arg_index: usize,
+    dev_watcher: Option<Watcher>,
+    diagnostics_revision: Option<u64>,
+    disk_prompted: Option<DiskStamp>,
+    command_depth: usize,
+    message_log: Vec<String>,
+    hooks: hooks::Hooks,
 }
 
 impl Editor {
-    pub fn default() -> Self {
-        let args: Vec<String> = env::args().collect();
-        let mut initial_status = String::from("[USAGE] <C-q> = quit | <C-s> = save | <C-f> = find");
-        let document = if let Some(filename) = args.get(1) {
-            let doc = Document::open(filename);
-            if let Ok(doc) = doc {
-                doc
-            } else {
-                initial_status = format!("ERROR: Could not open file: {}", filename);
+    pub fn new(args: &Args) -> Self {
+        Self::with_terminal(args, |capabilities| {
+            Terminal::new(capabilities).expect("Cannot initialise terminal.")
+        })
+    }
+
+    pub fn headless(args: &Args, width: u16, height: u16) -> Self {
+        Self::with_terminal(args, |capabilities| {
+            Terminal::headless(width, height, capabilities)
+        })
+    }
+
+    fn with_terminal(args: &Args, terminal: impl FnOnce(Capabilities) -> Terminal) -> Self {
+        let clean = args.clean;
+        let mut initial_status = String::from(
+            "[USAGE] <C-q> = quit | <C-s> = save | <C-f> = find | <C-l> = lines | <C-n> = line numbers",
+        );
+        let directory = args
+            .filename()
+            .filter(|filename| Path::new(filename).is_dir());
+        let document = if args.filename() == Some("-") {
+            Document::from_stdin().unwrap_or_else(|err| {
+                initial_status = format!("ERROR: Could not read stdin: {}", err);
                 Document::default()
+            })
+        } else if directory.is_some() {
+            Document::default()
+        } else if let Some(filename) = args.filename() {
+            if Path::new(filename).exists() {
+                Document::open(filename).unwrap_or_else(|_| {
+                    initial_status = format!("ERROR: Could not open file: {}", filename);
+                    Document::default()
+                })
+            } else {
+                Document::create(filename)
             }
         } else {
             Document::default()
         };
 
-        Self {
+        if !clean {
+            if let Err(err) = paths::migrate() {
+                initial_status = format!("ERROR: Could not migrate state directory: {}", err);
+            }
+        }
+        let config = if clean {
+            Config::defaults_only()
+        } else {
+            match &args.config {
+                Some(path) => Config::load_file(path),
+                None => Config::load(),
+            }
+            .unwrap_or_else(|err| {
+                initial_status = format!("ERROR: Invalid config: {}", err);
+                Config::default()
+            })
+        };
+
+        let mut capabilities = Capabilities::detect();
+        capabilities.apply(&config.capabilities, config.colour_support);
+        let mut theme = Theme::load(&config.theme).unwrap_or_else(|err| {
+            initial_status = format!("ERROR: {}", err);
+            Theme::default()
+        });
+        theme.apply(&config.colours);
+        theme.adapt(&capabilities);
+
+        let transforms = Transforms::with_commands(&config.transforms);
+
+        let mut events = EventBus::default();
+        for handler in [
+            Editor::update_swap,
+            Editor::flush_registers,
+            Editor::check_disk,
+            Editor::clock_tick,
+            Editor::blame_tick,
+            Editor::diff_marks_tick,
+            Editor::hooks_tick,
+            Editor::status_commands_tick,
+        ] {
+            events.subscribe(EditorEvent::Tick, handler);
+        }
+        if config.autosave_interval > 0 {
+            events.subscribe(EditorEvent::Tick, Editor::autosave_tick);
+        }
+        for action in &config.idle_actions {
+            match idle_action(action) {
+                Some(handler) => events.subscribe(EditorEvent::Idle, handler),
+                None => initial_status = format!("ERROR: Unknown idle action: {}", action),
+            }
+        }
+        if !config.status_commands.is_empty() {
+            events.subscribe(EditorEvent::Idle, Editor::refresh_status_commands);
+        }
+
+        let terminated = Arc::new(AtomicBool::new(false));
+        for signal in [SIGTERM, SIGHUP] {
+            signal_hook::flag::register(signal, Arc::clone(&terminated)).ok();
+        }
+
+        let mut editor = Self {
             should_quit: false,
-            terminal: Terminal::default().expect("Cannot initialise terminal."),
+            terminal: terminal(capabilities),
             cursor_position: Position::default(),
-            offset: Position::default(),
+            window: Window::default(),
             document,
             status_message: StatusMessage::from(initial_status),
             terminal_mode: TerminalMode::Normal,
+            count: None,
+            pending: None,
+            operator: None,
+            registers: if clean {
+                Registers::default()
+            } else {
+                Registers::shared()
+            },
+            jumps: Vec::new(),
+            jump_index: 0,
+            register: None,
+            terminated,
+            last_trashed: None,
+            prompt_hint: String::new(),
+            prompt_history: None,
+            prompt_completion: None,
+            history: if clean {
+                History::default()
+            } else {
+                History::load()
+            },
+            search_query: None,
+            search_matcher: None,
+            search_providers: SearchProviders::default(),
+            last_search: None,
+            search_direction: SearchDirection::Forward,
+            selection_anchor: Position::default(),
+            bracket_match: None,
+            bracket_match_at: None,
+            timings: None,
+            overlay: None,
+            file_tree: None,
+            listing: None,
+            diff_view: None,
+            config,
+            theme,
+            quickfix: Quickfix::default(),
+            diff_marks: HashMap::new(),
+            diff_base: None,
+            diff_revision: 0,
+            search_rows: None,
+            search_matches: None,
+            word_count: None,
+            transforms,
+            status_commands: status_commands::StatusCommands::default(),
+            status_minute: 0,
+            blame: git::Blame::default(),
+            pending_input: VecDeque::new(),
+            replaying: false,
+            recording: None,
+            macros: HashMap::new(),
+            last_macro: None,
+            events,
+            last_input: Instant::now(),
+            idle_fired: false,
+            autosave_fired: false,
+            autosave_failed: false,
+            recovery_written: false,
+            swap_written: None,
+            swap_disabled: false,
+            backed_up: false,
+            clean,
+            lock: None,
+            read_only: args.readonly,
+            arg_list: args.files.clone(),
+            arg_index: 0,
+            dev_watcher: None,
+            diagnostics_revision: None,
+            disk_prompted: None,
+            command_depth: 0,
+            message_log: Vec::new(),
+            hooks: hooks::Hooks::default(),
+        };
+        editor.apply_undo_limit();
+        editor.terminal.set_top(u16::from(editor.config.tabline));
+        editor.refresh_git_marks();
+        if let Some(directory) = directory {
+            editor.browse(Path::new(directory));
+        }
+        if let Some(position) = &args.start_position {
+            editor.open_at(position);
+        }
+        if args.dev {
+            editor.dev_watcher = Some(Watcher::new(editor.definition_files()));
+            editor
+                .events
+                .subscribe(EditorEvent::Tick, Editor::reload_definitions);
+        }
+        editor
+    }
+
+    fn open_at(&mut self, position: &str) {
+        let target = if let Some(pattern) = position.strip_prefix('/') {
+            self.last_search = Some(pattern.to_string());
+            self.matcher(pattern).ok().and_then(|matcher| {
+                self.document.find(
+                    matcher.as_ref(),
+                    &Position::default(),
+                    SearchDirection::Forward,
+                )
+            })
+        } else if position.is_empty() {
+            Some(Position {
+                x: 0,
+                y: self.document.len().saturating_sub(1),
+            })
+        } else {
+            position.parse::<usize>().ok().map(|line| Position {
+                x: 0,
+                y: usize::min(line, self.document.len()).saturating_sub(1),
+            })
+        };
+
+        let Some(target) = target else {
+            self.status_message = StatusMessage::from(match position.strip_prefix('/') {
+                Some(pattern) => format!("Pattern not found: {}", pattern),
+                None => format!("Invalid position: +{}", position),
+            });
+            return;
+        };
+        self.cursor_position = target;
+        self.center_on(target.y);
+        self.scroll();
+    }
+
+    pub fn run(&mut self) {
+        Terminal::clear_screen();
+        self.offer_recovery();
+        if !self.read_only {
+            self.acquire_lock(false);
+        }
+        self.offer_swap();
+        self.offer_ansi();
+        self.offer_template();
+        self.refresh_status_commands();
+        loop {
+            if let Err(err) = self.refresh_screen() {
+                die(err);
+            }
+            if self.should_quit {
+                break;
+            }
+            if let Err(err) = self.process_keypress() {
+                die(err);
+            }
+        }
+        if self.recovery_written {
+            Session::clear();
+        }
+        self.discard_swap();
+        self.registers.flush().ok();
+    }
+
+    pub fn snapshot(&mut self, keys: Vec<KeyEvent>) -> Result<String, std::io::Error> {
+        self.pending_input.extend(keys.into_iter().map(Event::Key));
+        while !self.should_quit && !self.pending_input.is_empty() {
+            self.refresh_screen()?;
+            self.process_keypress()?;
+        }
+        self.refresh_screen()?;
+        Ok(self.terminal.snapshot())
+    }
+
+    fn acquire_lock(&mut self, force: bool) {
+        self.lock = None;
+        self.read_only = false;
+        let Some(filename) = self.document.filename.clone() else {
+            return;
+        };
+        let mut steal = force;
+        if let Some(holder) = Lock::holder(&filename).filter(|_| !force) {
+            let question = format!("{} is open in {}:", filename, holder);
+            let dialog = Dialog::new(&question, &["open read-only", "steal lock", "quit"], 0);
+            match self.ask(&dialog) {
+                Some('s') => steal = true,
+                Some('q') => {
+                    self.should_quit = true;
+                    return;
+                }
+                _ => {
+                    self.read_only = true;
+                    self.status_message =
+                        StatusMessage::from(format!("Opened {} read-only", filename));
+                    return;
+                }
+            }
+        }
+        match Lock::acquire(&filename, steal) {
+            Ok(lock) => self.lock = Some(lock),
+            Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists => {
+                self.read_only = true;
+                self.status_message = StatusMessage::from(format!(
+                    "{} was just locked by another texty; opened read-only",
+                    filename
+                ));
+            }
+            Err(err) => {
+                self.status_message =
+                    StatusMessage::from(format!("Could not create lock file: {}", err));
+            }
+        }
+    }
+
+    fn offer_recovery(&mut self) {
+        if self.clean {
+            return;
+        }
+        let Some(session) = Session::load() else {
+            return;
+        };
+        Session::clear();
+
+        if !self.confirm("Restore session from an unexpected exit?", false) {
+            return;
+        }
+
+        let filename = session.filename.as_deref();
+        self.document = if let Some(buffer) = &session.buffer {
+            Document::recover(filename, buffer)
+        } else if let Some(Ok(document)) = filename.map(Document::open) {
+            document
+        } else {
+            Document::default()
+        };
+        self.apply_undo_limit();
+
+        let y = usize::min(session.cursor_position.y, self.document.len());
+        let width = self.document.row(y).map_or(0, Row::len);
+        self.cursor_position = Position {
+            x: usize::min(session.cursor_position.x, width),
+            y,
+        };
+        self.scroll();
+        self.status_message = StatusMessage::from(String::from("Session restored"));
+    }
+
+    fn offer_swap(&mut self) {
+        self.swap_disabled = false;
+        self.swap_written = None;
+        let Some(filename) = self.document.filename.clone() else {
+            return;
+        };
+        if self.clean || self.read_only || self.should_quit {
+            return;
+        }
+        let swap = Swap::for_file(&filename);
+        let Some(recovered) = swap.read() else {
+            return;
+        };
+        if !recovered.is_stale() {
+            self.swap_disabled = true;
+            self.status_message = StatusMessage::from(format!(
+                "{} belongs to running texty pid {}; swap writing is off for this buffer",
+                swap.path().display(),
+                recovered.pid
+            ));
+            return;
+        }
+        if recovered.contents == self.document.contents() {
+            swap.remove();
+            return;
+        }
+
+        let question = format!("Found unsaved changes in {}:", swap.path().display());
+        match self.ask(&Dialog::new(&question, &["recover", "discard"], 0)) {
+            Some('r') => {
+                self.document = Document::recover(Some(&filename), &recovered.contents);
+                self.apply_undo_limit();
+                self.cursor_position = self.document.clamp(&self.cursor_position);
+                self.swap_written = Some(Instant::now());
+                self.status_message = StatusMessage::from(String::from(
+                    "Recovered unsaved changes; save to keep them",
+                ));
+            }
+            Some('d') => {
+                swap.remove();
+                self.status_message =
+                    StatusMessage::from(format!("Discarded {}", swap.path().display()));
+            }
+            _ => {
+                self.swap_disabled = true;
+                self.status_message = StatusMessage::from(format!(
+                    "Kept {}; swap writing is off for this buffer",
+                    swap.path().display()
+                ));
+            }
+        }
+    }
+
+    fn update_swap(&mut self) -> bool {
+        let Some(filename) = self.document.filename.as_deref() else {
+            return false;
+        };
+        if !self.document.is_dirty() {
+            if self.swap_written.take().is_some() {
+                Swap::for_file(filename).remove();
+            }
+            return false;
+        }
+        let interval = Duration::from_secs(self.config.swap_interval);
+        if interval.is_zero() || self.swap_disabled || self.read_only {
+            return false;
+        }
+        if self
+            .swap_written
+            .is_some_and(|at| at.elapsed() < interval || self.last_input < at)
+        {
+            return false;
+        }
+        if Swap::for_file(filename)
+            .write(&self.document.contents())
+            .is_ok()
+        {
+            self.swap_written = Some(Instant::now());
+        }
+        false
+    }
+
+    fn discard_swap(&mut self) {
+        if let (Some(filename), Some(_)) = (&self.document.filename, self.swap_written.take()) {
+            Swap::for_file(filename).remove();
+        }
+    }
+
+    fn session(&self) -> Session {
+        Session {
+            filename: self.document.filename.clone(),
+            cursor_position: self.cursor_position,
+            buffer: self.document.is_dirty().then(|| self.document.contents()),
+        }
+    }
+
+    fn emergency_exit(&self) -> ! {
+        if self.document.is_dirty() {
+            self.session().save().ok();
+        } else if self.recovery_written {
+            Session::clear();
+        }
+        if let Some(filename) = &self.document.filename {
+            if self.document.is_dirty() && !self.swap_disabled {
+                Swap::for_file(filename)
+                    .write(&self.document.contents())
+                    .ok();
+            }
+        }
+        if let Some(lock) = &self.lock {
+            lock.release();
+        }
+        Terminal::restore();
+        process::exit(1);
+    }
+
+    fn next_event(&mut self) -> Result<Event, std::io::Error> {
+        loop {
+            if let Some(event) = self.read_event()? {
+                return Ok(event);
+            }
+            if self.emit(EditorEvent::Tick) {
+                self.refresh_screen()?;
+            }
+            if !self.idle_fired
+                && self.last_input.elapsed() >= Duration::from_millis(self.config.idle_timeout_ms)
+            {
+                self.idle_fired = true;
+                if self.emit(EditorEvent::Idle) {
+                    self.refresh_screen()?;
+                }
+            }
+        }
+    }
+
+    fn dialog_event(&mut self) -> Result<Event, std::io::Error> {
+        loop {
+            if let Some(event) = self.read_event()? {
+                return Ok(event);
+            }
+        }
+    }
+
+    fn read_event(&mut self) -> Result<Option<Event>, std::io::Error> {
+        if let Some(event) = self.pending_input.pop_front() {
+            return Ok(Some(event));
+        }
+        if self.terminated.load(Ordering::Relaxed) {
+            self.emergency_exit();
+        }
+        if !Terminal::poll_key(Duration::from_millis(250))? {
+            return Ok(None);
+        }
+        let Some(event) = Terminal::read_key()? else {
+            return Ok(None);
+        };
+        self.last_input = Instant::now();
+        self.idle_fired = false;
+        self.autosave_fired = false;
+        if let (Event::Key(key), Some((_, keys))) = (&event, &mut self.recording) {
+            keys.push(*key);
+        }
+        Ok(Some(event))
+    }
+
+    fn check_disk(&mut self) -> bool {
+        let Some(stamp) = self.document.changed_on_disk() else {
+            return false;
+        };
+        if self.disk_prompted == Some(stamp) {
+            return false;
+        }
+        self.disk_prompted = Some(stamp);
+        let filename = self.document.filename.clone().unwrap_or_default();
+        let dirty = self.document.is_dirty();
+        let question = if dirty {
+            format!(
+                "{} changed on disk; reload and discard your changes?",
+                filename
+            )
+        } else {
+            format!("{} changed on disk; reload?", filename)
+        };
+        if self.confirm(&question, !dirty) {
+            self.reload_file();
+        } else {
+            self.status_message = StatusMessage::from(format!(
+                "Kept buffer; saving will ask before overwriting {}",
+                filename
+            ));
+        }
+        true
+    }
+
+    fn reload_file(&mut self) {
+        let Some(filename) = self.document.filename.clone() else {
+            return;
+        };
+        match Document::open(&filename) {
+            Ok(document) => {
+                self.document = document;
+                self.apply_undo_limit();
+                self.cursor_position = self.document.clamp(&self.cursor_position);
+                self.refresh_git_marks();
+                self.status_message = StatusMessage::from(format!("Reloaded {}", filename));
+            }
+            Err(err) => {
+                self.status_message =
+                    StatusMessage::from(format!("Could not reload {}: {}", filename, err));
+            }
+        }
+    }
+
+    fn definition_files(&self) -> Vec<PathBuf> {
+        let mut files = filetype::syntax_files();
+        files.extend(Theme::user_path(&self.config.theme));
+        files
+    }
+
+    fn reload_definitions(&mut self) -> bool {
+        let files = self.definition_files();
+        if !self
+            .dev_watcher
+            .as_mut()
+            .is_some_and(|watcher| watcher.changed(files))
+        {
+            return false;
+        }
+
+        let mut problems = FileType::check_syntaxes();
+        FileType::reload_syntaxes();
+        self.document.refresh_file_type();
+        match Theme::load(&self.config.theme) {
+            Ok(mut theme) => {
+                theme.apply(&self.config.colours);
+                theme.adapt(self.terminal.capabilities());
+                self.theme = theme;
+            }
+            Err(err) => problems.push(err),
+        }
+        self.terminal.invalidate();
+
+        let message = if problems.is_empty() {
+            String::from("Reloaded syntax and theme definitions")
+        } else {
+            format!(
+                "{} problem(s) reloading definitions: {}",
+                problems.len(),
+                problems.join("; ")
+            )
+        };
+        if problems.is_empty() {
+            self.log_message(&message);
+        }
+        for problem in &problems {
+            self.log_message(problem);
+        }
+        self.status_message = StatusMessage::from(message);
+        true
+    }
+
+    fn log_message(&mut self, message: &str) {
+        self.message_log.push(message.to_string());
+        if self.message_log.len() > MESSAGE_LOG_SIZE {
+            self.message_log.remove(0);
+        }
+    }
+
+    fn show_messages(&mut self) {
+        self.status_message = StatusMessage::from(if self.message_log.is_empty() {
+            String::from("No messages")
+        } else {
+            self.message_log.join(" | ")
+        });
+    }
+
+    fn emit(&mut self, event: EditorEvent) -> bool {
+        let mut redraw = false;
+        for handler in self.events.handlers(event) {
+            redraw |= handler(self);
+        }
+        redraw
+    }
+
+    fn refresh_status_commands(&mut self) -> bool {
+        let commands = self
+            .config
+            .statusline
+            .iter()
+            .chain(&self.config.tabline_segments)
+            .filter_map(|name| {
+                let command = self.config.status_commands.get(name)?;
+                Some((name.clone(), command.clone()))
+            })
+            .collect();
+        let env = vec![
+            (
+                "TEXTY_FILE",
+                self.document.filename.clone().unwrap_or_default(),
+            ),
+            ("TEXTY_FILETYPE", self.document.file_type()),
+            (
+                "TEXTY_LINE",
+                self.cursor_position.y.saturating_add(1).to_string(),
+            ),
+            (
+                "TEXTY_COLUMN",
+                self.cursor_position.x.saturating_add(1).to_string(),
+            ),
+            ("TEXTY_LINES", self.document.len().to_string()),
+            ("TEXTY_MODE", current_mode(self.terminal_mode)),
+            (
+                "TEXTY_MODIFIED",
+                u8::from(self.document.is_dirty()).to_string(),
+            ),
+        ];
+        self.status_commands.request(commands, env);
+        false
+    }
+
+    fn status_commands_tick(&mut self) -> bool {
+        self.status_commands.poll()
+    }
+
+    fn segments(&self, names: &[String]) -> Vec<String> {
+        names
+            .iter()
+            .filter_map(|name| match status_segment(name) {
+                Some(segment) => Some(segment(self)),
+                None => self.status_commands.output(name).cloned(),
+            })
+            .filter(|segment| !segment.is_empty())
+            .collect()
+    }
+
+    fn draw_tabline(&self) -> String {
+        let width = self.terminal.size().width as usize;
+        let current = self.document.filename.as_deref().unwrap_or("[unnamed]");
+        let tabs: Vec<String> = if self.arg_list.is_empty() {
+            vec![format!("[{}]", current)]
+        } else {
+            self.arg_list
+                .iter()
+                .enumerate()
+                .map(|(index, filename)| {
+                    if index == self.arg_index {
+                        format!("[{}]", filename)
+                    } else {
+                        format!(" {} ", filename)
+                    }
+                })
+                .collect()
+        };
+        let right = self.segments(&self.config.tabline_segments).join(" | ");
+        let mut left: String = tabs.concat().chars().take(width).collect();
+        let used = left.chars().count() + right.chars().count();
+        if used < width {
+            left.push_str(&" ".repeat(width - used));
+            left.push_str(&right);
+        }
+        format!(
+            "{}{}{}",
+            SetColors(Colors::new(self.theme.status_fg.0, self.theme.status_bg.0)),
+            left,
+            ResetColor
+        )
+    }
+
+    fn word_count(&self) -> String {
+        let words = self.word_count.map_or(0, |(_, words)| words);
+        format!("{} words", words)
+    }
+
+    fn update_word_count(&mut self) {
+        let revision = self.document.revision();
+        if matches!(self.word_count, Some((cached, _)) if cached == revision) {
+            return;
+        }
+        let words = self
+            .document
+            .lines()
+            .map(|line| line.split_whitespace().count())
+            .sum();
+        self.word_count = Some((revision, words));
+    }
+
+    fn clock(&self) -> String {
+        clock::time(&self.config.clock_format)
+    }
+
+    fn saved_age(&self) -> String {
+        self.document
+            .saved_at()
+            .and_then(|saved| saved.elapsed().ok())
+            .map_or_else(String::new, |elapsed| {
+                format!("saved {}", clock::age(elapsed))
+            })
+    }
+
+    fn blame(&self) -> String {
+        let Some(filename) = &self.document.filename else {
+            return String::new();
+        };
+        let y = self.cursor_position.y;
+        let current = self.document.row(y).map_or("", Row::as_str);
+        self.blame.text(filename, y, current).to_string()
+    }
+
+    fn run_save_hooks(&mut self) {
+        let Some(filename) = self.document.filename.as_deref() else {
+            return;
+        };
+        let Ok(path) = fs::canonicalize(filename) else {
+            return;
+        };
+        if let Some((dir, commands)) = hooks::matching(self.config.save_hooks.iter(), &path) {
+            self.hooks.run(&commands, &dir, &path);
+        }
+    }
+
+    fn hooks_tick(&mut self) -> bool {
+        let results = self.hooks.poll();
+        for result in &results {
+            self.log_message(result);
+        }
+        let Some(last) = results.last() else {
+            return false;
+        };
+        self.status_message = StatusMessage::from(last.clone());
+        true
+    }
+
+    fn blame_tick(&mut self) -> bool {
+        if !self.config.statusline.iter().any(|name| name == "blame") {
+            return false;
+        }
+        if let Some(filename) = &self.document.filename {
+            let y = self.cursor_position.y;
+            let current = self.document.row(y).map_or("", Row::as_str);
+            self.blame
+                .request(filename, y, current, || self.document.contents());
+        }
+        self.blame.poll()
+    }
+
+    fn clock_tick(&mut self) -> bool {
+        let timed = self
+            .config
+            .statusline
+            .iter()
+            .any(|name| name == "clock" || name == "saved");
+        let minute = clock::minute();
+        if !timed || minute == self.status_minute {
+            return false;
+        }
+        self.status_minute = minute;
+        true
+    }
+
+    fn byte_position(&self) -> String {
+        format!("byte {}", self.document.byte_offset(&self.cursor_position))
+    }
+
+    fn percentage(&self) -> String {
+        let total = self.document.total_bytes();
+        let offset = self.document.byte_offset(&self.cursor_position);
+        format!(
+            "{}%",
+            offset
+                .saturating_mul(100)
+                .checked_div(total)
+                .map_or(0, |percent| usize::min(percent, 100))
+        )
+    }
+
+    fn autosave_tick(&mut self) -> bool {
+        let interval = Duration::from_secs(self.config.autosave_interval);
+        if self.autosave_fired || !self.document.is_dirty() || self.last_input.elapsed() < interval
+        {
+            return false;
+        }
+        self.autosave_fired = true;
+        match self.config.autosave_target {
+            AutosaveTarget::File => self.autosave(),
+            AutosaveTarget::Recovery => self.save_recovery(),
+        }
+        true
+    }
+
+    fn autosave_pending(&self) -> bool {
+        self.config.autosave_interval > 0
+            && self.document.is_dirty()
+            && (self.config.autosave_target == AutosaveTarget::Recovery
+                || (self.document.filename.is_some() && !self.read_only))
+    }
+
+    fn save_recovery(&mut self) {
+        match self.session().save() {
+            Ok(()) => {
+                self.recovery_written = true;
+                self.autosave_failed = false;
+                self.status_message = StatusMessage::from(String::from("Saved recovery copy"));
+            }
+            Err(err) => {
+                self.autosave_failed = true;
+                self.status_message = StatusMessage::from(format!("Autosave failed: {}", err));
+            }
+        }
+    }
+
+    fn autosave(&mut self) {
+        if self.document.filename.is_none() || !self.document.is_dirty() || self.read_only {
+            return;
+        }
+        if self.document.changed_on_disk().is_some() {
+            self.autosave_failed = true;
+            self.status_message =
+                StatusMessage::from(String::from("Autosave skipped: file changed on disk"));
+            return;
+        }
+        let diagnostic = self.save_diagnostic();
+        if let Some((SaveGuard::Block, diagnostic)) = &diagnostic {
+            self.autosave_failed = true;
+            self.status_message = StatusMessage::from(format!(
+                "Autosave skipped: {}; :w! to save anyway",
+                diagnostic
+            ));
+            return;
+        }
+        if !self.backed_up {
+            if let Err(err) = self.back_up() {
+                self.autosave_failed = true;
+                self.status_message = StatusMessage::from(err);
+                return;
+            }
+        }
+        self.status_message = match self.document.save() {
+            Ok(()) => {
+                self.autosave_failed = false;
+                self.run_save_hooks();
+                match diagnostic {
+                    Some((_, diagnostic)) => {
+                        StatusMessage::from(format!("Autosaved with errors: {}", diagnostic))
+                    }
+                    None => StatusMessage::from(String::from("Autosaved")),
+                }
+            }
+            Err(err) => {
+                self.autosave_failed = true;
+                StatusMessage::from(format!("Autosave failed: {}", err))
+            }
+        };
+        self.refresh_git_marks();
+    }
+
+    fn back_up(&mut self) -> Result<(), String> {
+        let Some(filename) = &self.document.filename else {
+            return Ok(());
+        };
+        let result = match self.config.backup {
+            Backup::Off => return Ok(()),
+            Backup::Tilde => backup::tilde(filename),
+            Backup::Dir => backup::rotate(filename, self.config.backup_count),
+        };
+        result
+            .map(|_| self.backed_up = true)
+            .map_err(|err| format!("Backup failed: {}", err))
+    }
+
+    fn refresh_screen(&mut self) -> Result<(), std::io::Error> {
+        self.terminal.begin_frame();
+        Terminal::hide_cursor();
+        if self.should_quit {
+            Terminal::quit();
+        } else {
+            let started = Instant::now();
+            let height = self.terminal.size().height as usize;
+            self.document.highlight_rows(
+                self.window.offset.y,
+                self.window.offset.y.saturating_add(height),
+            );
+            let bracket_match_at = Some((self.cursor_position, self.document.revision()));
+            if self.bracket_match_at != bracket_match_at {
+                self.bracket_match = self.document.matching_pair(&self.cursor_position);
+                self.bracket_match_at = bracket_match_at;
+            }
+            let highlighted = Instant::now();
+
+            if self.config.statusline.iter().any(|name| name == "words") {
+                self.update_word_count();
+            }
+            let mut lines = self.draw_rows();
+            if self.config.scrollbar {
+                self.update_search_rows();
+                self.draw_scrollbar(&mut lines);
+            }
+            self.draw_file_tree(&mut lines);
+            if let Some(view) = &self.diff_view {
+                view.draw(&mut lines, self.terminal.size().width as usize, &self.theme);
+            }
+            if let Some(overlay) = &self.overlay {
+                overlay.draw(&mut lines, self.terminal.size().width as usize, &self.theme);
+            }
+            if self.timings.is_some() {
+                self.draw_timings(&mut lines);
+            }
+            if self.config.tabline {
+                lines.insert(0, self.draw_tabline());
+            }
+            lines.push(self.draw_status_bar());
+            lines.push(self.draw_message_bar());
+            for (y, line) in lines.iter().enumerate() {
+                self.terminal.draw_line(y, line);
+            }
+            let cursor = match &self.file_tree {
+                _ if self.diff_view.is_some() => Position { x: 0, y: 1 },
+                Some(tree) if tree.focused => Position {
+                    x: 0,
+                    y: tree.cursor_row(),
+                },
+                _ => self.cursor_screen_position(),
+            };
+            Terminal::position_cursor(&Position {
+                x: cursor.x,
+                y: cursor.y.saturating_add(usize::from(self.config.tabline)),
+            });
+            if let Some(timings) = &mut self.timings {
+                timings.highlight = highlighted - started;
+                timings.render = highlighted.elapsed();
+                timings.frames = timings.frames.saturating_add(1);
+            }
+        }
+        Terminal::show_cursor();
+        self.terminal.end_frame();
+        let flushing = Instant::now();
+        let result = Terminal::flush();
+        if let Some(timings) = &mut self.timings {
+            timings.flush = flushing.elapsed();
+        }
+        result
+    }
+
+    fn draw_file_tree(&mut self, lines: &mut [String]) {
+        let width = self.tree_width();
+        let Some(tree) = &mut self.file_tree else {
+            return;
+        };
+        tree.scroll(lines.len());
+        let cwd = env::current_dir().unwrap_or_default();
+        let open = self
+            .document
+            .filename
+            .as_deref()
+            .map(|filename| refs::normalize(&cwd.join(filename)));
+        for (y, line) in lines.iter_mut().enumerate() {
+            line.insert_str(0, &tree.draw_line(y, width, open.as_deref(), &self.theme));
+        }
+    }
+
+    fn draw_timings(&self, lines: &mut [String]) {
+        let Some(timings) = &self.timings else {
+            return;
+        };
+        let width = self.terminal.size().width as usize;
+        let overlay = timings.overlay();
+        let overlay_width = overlay.iter().map(String::len).max().unwrap_or(0) + 2;
+        let column = MoveToColumn(
+            width
+                .saturating_sub(overlay_width)
+                .saturating_sub(usize::from(self.config.scrollbar)) as u16,
+        );
+        for (line, text) in lines.iter_mut().zip(overlay) {
+            line.push_str(&format!(
+                "{}{} {:<width$} {}",
+                column,
+                SetColors(Colors::new(self.theme.status_fg.0, self.theme.status_bg.0)),
+                text,
+                ResetColor,
+                width = overlay_width - 2,
+            ));
+        }
+    }
+
+    fn cursor_screen_position(&self) -> Position {
+        let Position { x, y } = self.cursor_position;
+        let layout = self.layout();
+        let column = layout.column_at(&self.document, x, y);
+        if self.config.wrap {
+            let width = layout.row_width(&self.document, y);
+            return Position {
+                x: layout
+                    .column_of(column, width)
+                    .saturating_add(self.text_offset()),
+                y: layout.distance(
+                    &self.document,
+                    self.top_line(),
+                    layout.line_at(&self.document, x, y),
+                ),
+            };
+        }
+
+        Position {
+            x: column
+                .saturating_sub(self.window.offset.x)
+                .saturating_add(self.text_offset()),
+            y: y.saturating_sub(self.window.offset.y),
+        }
+    }
+
+    fn layout(&self) -> Layout {
+        Layout::new(self.text_width(), self.config.tab_width)
+    }
+
+    fn top_line(&self) -> VisualLine {
+        VisualLine {
+            row: self.window.offset.y,
+            segment: self.window.top_segment,
+        }
+    }
+
+    fn has_signs(&self) -> bool {
+        !self.diff_marks.is_empty() || !self.document.review().is_empty()
+    }
+
+    fn gutter_width(&self) -> usize {
+        let signs = usize::from(self.has_signs());
+        if self.config.line_numbers == LineNumbers::Off {
+            return signs;
+        }
+        let mut digits = 1;
+        let mut len = self.document.len() / 10;
+        while len > 0 {
+            digits += 1;
+            len /= 10;
+        }
+        digits + 1 + signs
+    }
+
+    fn tree_width(&self) -> usize {
+        self.file_tree.as_ref().map_or(0, |_| {
+            usize::min(
+                self.config.tree_width,
+                self.terminal.size().width as usize / 2,
+            )
+        })
+    }
+
+    fn text_offset(&self) -> usize {
+        self.tree_width().saturating_add(self.gutter_width())
+    }
+
+    fn text_width(&self) -> usize {
+        (self.terminal.size().width as usize)
+            .saturating_sub(self.text_offset())
+            .saturating_sub(usize::from(self.config.scrollbar))
+    }
+
+    fn draw_gutter(&self, index: usize, continuation: bool) -> String {
+        let mut width = self.gutter_width();
+        if width == 0 {
+            return String::new();
+        }
+        if continuation {
+            return " ".repeat(width);
+        }
+
+        let mut sign = String::new();
+        if self.has_signs() {
+            width -= 1;
+            sign = match self.diff_marks.get(&index) {
+                Some(DiffMark::Added) => {
+                    format!("{}+", SetForegroundColor(self.theme.diff_added.0))
+                }
+                Some(DiffMark::Changed) => {
+                    format!("{}~", SetForegroundColor(self.theme.diff_changed.0))
+                }
+                Some(DiffMark::Removed) => {
+                    format!("{}-", SetForegroundColor(self.theme.diff_removed.0))
+                }
+                None if self.document.review().get(index).is_some() => {
+                    format!("{}*", SetForegroundColor(self.theme.gutter_fg.0))
+                }
+                None => String::from(" "),
+            };
+            if width == 0 {
+                return format!("{}{}", sign, ResetColor);
+            }
+        }
+        let number = if self.config.line_numbers == LineNumbers::Relative
+            && index != self.cursor_position.y
+        {
+            index.abs_diff(self.cursor_position.y)
+        } else {
+            index.saturating_add(1)
+        };
+
+        format!(
+            "{}{}{:>4$} {}",
+            sign,
+            SetForegroundColor(self.theme.gutter_fg.0),
+            number,
+            ResetColor,
+            width.saturating_sub(1)
+        )
+    }
+
+    fn draw_row(&self, index: usize, row: &Row) -> String {
+        let mut start = self.window.offset.x;
+        let mut end = start.saturating_add(self.text_width());
+        let width = row.display_width(self.config.tab_width);
+        let indicator = |symbol: char| {
+            format!(
+                "{}{}{}",
+                SetForegroundColor(self.theme.gutter_fg.0),
+                symbol,
+                ResetColor
+            )
+        };
+
+        let mut left = String::new();
+        if start > 0 && width > 0 && end > start {
+            left = indicator('<');
+            start = start.saturating_add(1);
+        }
+        let mut right = String::new();
+        if width > end && end > start {
+            right = indicator('>');
+            end = end.saturating_sub(1);
+        }
+
+        let rendered = row.render(
+            start,
+            end,
+            self.selection_in_row(index),
+            &self.matches_in_row(index, row),
+            self.config.tab_width,
+            &self.theme,
+        );
+        format!("{}{}{}", left, rendered, right)
+    }
+
+    fn matches_in_row(&self, index: usize, row: &Row) -> Vec<(usize, usize, highlighting::Type)> {
+        let mut matches: Vec<_> = self
+            .search_matcher
+            .as_deref()
+            .map_or_else(Vec::new, |matcher| {
+                row.matches(matcher)
+                    .into_iter()
+                    .map(|(start, end)| (start, end, highlighting::Type::Match))
+                    .collect()
+            });
+        if let Some((Position { x, y }, len)) = self.bracket_match {
+            if y == index {
+                matches.push((x, x.saturating_add(len), highlighting::Type::Bracket));
+            }
+        }
+        matches.extend(
+            row.colours()
+                .iter()
+                .map(|&(start, end, colour)| (start, end, highlighting::Type::Ansi(colour))),
+        );
+        matches
+    }
+
+    fn draw_tilde() -> String {
+        format!("{}~{}", SetForegroundColor(Color::DarkCyan), ResetColor)
+    }
+
+    fn selection(&self) -> Option<(Position, Position)> {
+        if self.terminal_mode != TerminalMode::Visual {
+            return None;
+        }
+        let anchor = self.selection_anchor;
+        let cursor = self.cursor_position;
+        if (anchor.y, anchor.x) <= (cursor.y, cursor.x) {
+            Some((anchor, cursor))
+        } else {
+            Some((cursor, anchor))
+        }
+    }
+
+    fn selection_in_row(&self, index: usize) -> Option<(usize, usize)> {
+        let (start, end) = self.selection()?;
+        if index < start.y || index > end.y {
+            return None;
+        }
+        let from = if index == start.y { start.x } else { 0 };
+        let to = if index == end.y {
+            end.x.saturating_add(1)
+        } else {
+            self.document.row(index).map_or(0, Row::len)
+        };
+        Some((from, to))
+    }
+
+    fn draw_wrapped_rows(&self) -> Vec<String> {
+        let height = self.terminal.size().height;
+        let layout = self.layout();
+        let mut line = Some(self.top_line());
+        let mut lines = Vec::new();
+        for _ in 0..height {
+            if let Some((current, row)) =
+                line.and_then(|line| self.document.row(line.row).map(|row| (line, row)))
+            {
+                let (start, end) = layout.bounds(current);
+                lines.push(format!(
+                    "{}{}",
+                    self.draw_gutter(current.row, current.segment > 0),
+                    row.render(
+                        start,
+                        end,
+                        self.selection_in_row(current.row),
+                        &self.matches_in_row(current.row, row),
+                        self.config.tab_width,
+                        &self.theme
+                    )
+                ));
+                line = layout.next(&self.document, current);
+            } else {
+                line = None;
+                lines.push(Self::draw_tilde());
+            }
+        }
+        lines
+    }
+
+    fn draw_rows(&self) -> Vec<String> {
+        if self.config.wrap {
+            return self.draw_wrapped_rows();
+        }
+        let height = self.terminal.size().height;
+        let mut lines = Vec::new();
+        for terminal_row in 0..height {
+            let index = self.window.offset.y.saturating_add(terminal_row as usize);
+            if let Some(row) = self.document.row(index) {
+                lines.push(format!(
+                    "{}{}",
+                    self.draw_gutter(index, false),
+                    self.draw_row(index, row)
+                ));
+            } else {
+                lines.push(Self::draw_tilde());
+            }
+        }
+        lines
+    }
+
+    fn update_search_rows(&mut self) {
+        let Some(matcher) = self.search_matcher.as_deref() else {
+            return;
+        };
+        let revision = self.document.revision();
+        if matches!(self.search_rows, Some((cached, _)) if cached == revision) {
+            return;
+        }
+        let rows = self
+            .document
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !matcher.find_all(line).is_empty())
+            .map(|(y, _)| y)
+            .collect();
+        self.search_rows = Some((revision, rows));
+    }
+
+    fn draw_scrollbar(&self, lines: &mut [String]) {
+        let height = lines.len();
+        let len = usize::max(self.document.len(), 1);
+        let bar_row =
+            |y: usize| usize::min(y.saturating_mul(height) / len, height.saturating_sub(1));
+        let thumb_start = bar_row(self.window.offset.y);
+        let thumb_len = usize::max(height.saturating_mul(height) / len, 1);
+
+        let mut marks: HashMap<usize, (char, Color)> = HashMap::new();
+        if let Some((_, rows)) = &self.search_rows {
+            for &y in rows {
+                marks.insert(bar_row(y), ('-', self.theme.search_match.0));
+            }
+        }
+        for entry in self.quickfix.entries() {
+            if entry.filename.is_none() || entry.filename == self.document.filename {
+                marks.insert(bar_row(entry.position.y), ('!', self.theme.diff_removed.0));
+            }
+        }
+
+        let column = MoveToColumn(self.terminal.size().width.saturating_sub(1));
+        for (i, line) in lines.iter_mut().enumerate() {
+            let in_thumb = i >= thumb_start && i < thumb_start.saturating_add(thumb_len);
+            let (symbol, colour) = marks.get(&i).copied().unwrap_or(if in_thumb {
+                ('█', self.theme.gutter_fg.0)
+            } else {
+                ('│', self.theme.gutter_fg.0)
+            });
+            line.push_str(&format!(
+                "{}{}{}{}",
+                column,
+                SetForegroundColor(colour),
+                symbol,
+                ResetColor
+            ));
+        }
+    }
+
+    fn draw_status_bar(&self) -> String {
+        let mut status: String;
+        let width = self.terminal.size().width as usize;
+        let mut filename = String::from("[unnamed]");
+
+        let mut modified_state = String::from(if self.document.is_dirty() {
+            " [modified]"
+        } else {
+            ""
+        });
+        if self.read_only {
+            modified_state.push_str(" [RO]");
+        }
+        if self.autosave_failed {
+            modified_state.push_str(" [autosave failed]");
+        } else if self.autosave_pending() {
+            modified_state.push_str(" [autosave pending]");
+        }
+        if let Some((register, _)) = &self.recording {
+            modified_state.push_str(&format!(" [recording @{}]", register));
+        }
+        
+        let current_mode = current_mode(self.terminal_mode);
+        
+        if let Some(name) = &self.document.filename {
+            filename = name.clone();
+            filename.truncate(20);
+        }
+        status = format!(
+            "{}:{}:{}{}",
+            filename,
+            self.cursor_position.y.saturating_add(1),
+            self.cursor_position.x.saturating_add(1),
+            modified_state
+        );
+
+        let scrolled = if self.window.offset.x > 0 && !self.config.wrap {
+            format!("+{} cols | ", self.window.offset.x)
+        } else {
+            String::new()
+        };
+        let mut segments = self.segments(&self.config.statusline);
+        if let Some([lines, words, chars, bytes]) = self.selection_stats() {
+            segments.push(format!("{}L {}W {}C {}B", lines, words, chars, bytes));
+        }
+        segments.insert(
+            0,
+            String::from(if self.document.is_crlf() {
+                "CRLF"
+            } else {
+                "LF"
+            }),
+        );
+        let mut segments = segments.join(" | ");
+        if !segments.is_empty() {
+            segments.push_str(" | ");
+        }
+        let file_indicator = format!(
+            "{}{}{} | {}",
+            segments,
+            scrolled,
+            self.document.file_type(),
+            current_mode
+        );
+
+        let len = status.len() + file_indicator.len();
+        status.push_str(&" ".repeat(width.saturating_sub(len)));
+        status = format!("{}{}", status, file_indicator);
+        status.truncate(width);
+
+        format!(
+            "{}{}{}",
+            SetColors(Colors::new(self.theme.status_fg.0, self.theme.status_bg.0,)),
+            status,
+            ResetColor
+        )
+    }
+
+    fn draw_message_bar(&self) -> String {
+        let message = &self.status_message;
+        if Instant::now() - message.time < Duration::new(5, 0) {
+            let mut text = message.message.clone();
+            text.truncate(self.terminal.size().width as usize);
+            text
+        } else {
+            String::new()
+        }
+    }
+
+    fn save_file(&mut self, force: bool) {
+        if self.document.filename.is_none() {
+            let new_name = self.prompt_for(
+                "Save as: ",
+                &Expected::Path { must_exist: false },
+                Some(HistoryKind::SaveAs),
+            );
+            if new_name.is_none() {
+                self.status_message = StatusMessage::from(String::from("Aborted save"));
+                return;
+            }
+            self.document.filename = new_name;
+            self.backed_up = false;
+            self.acquire_lock(false);
+        }
+        if self.read_only {
+            self.status_message = StatusMessage::from(String::from(
+                "File is open read-only; :set noreadonly to take the lock",
+            ));
+            return;
+        }
+        if !force && self.document.changed_on_disk().is_some() {
+            let question = format!(
+                "{} changed on disk:",
+                self.document.filename.as_deref().unwrap_or_default()
+            );
+            let dialog = Dialog::new(&question, &["reload", "overwrite", "cancel"], 2);
+            match self.ask(&dialog) {
+                Some('r') => {
+                    self.reload_file();
+                    return;
+                }
+                Some('o') => (),
+                _ => {
+                    self.status_message = StatusMessage::from(String::from("Aborted save"));
+                    return;
+                }
+            }
+        }
+        let diagnostic = if force { None } else { self.save_diagnostic() };
+        if let Some((SaveGuard::Block, diagnostic)) = &diagnostic {
+            self.status_message =
+                StatusMessage::from(format!("Not saved: {}; :w! to save anyway", diagnostic));
+            return;
+        }
+        if let Err(err) = self.back_up() {
+            if !force {
+                self.status_message =
+                    StatusMessage::from(format!("{}; :w! to save without one", err));
+                return;
+            }
+        }
+
+        self.status_message = match (self.document.save(), diagnostic) {
+            (Ok(()), Some((_, diagnostic))) => {
+                StatusMessage::from(format!("Saved with errors: {}", diagnostic))
+            }
+            (Ok(()), None) => StatusMessage::from(String::from("Successfully saved file")),
+            (Err(_), _) => StatusMessage::from(String::from("Failed to save file")),
+        };
+        if !self.document.is_dirty() {
+            self.run_save_hooks();
+        }
+        self.refresh_git_marks();
+        self.autosave_failed &= self.document.is_dirty();
+    }
+
+    fn matches_file_type(&self, key: &str) -> bool {
+        key.eq_ignore_ascii_case(&self.document.file_type())
+            || self
+                .document
+                .filename
+                .as_deref()
+                .and_then(|filename| Path::new(filename).extension())
+                .is_some_and(|extension| extension == key)
+    }
+
+    fn save_diagnostic(&self) -> Option<(SaveGuard, String)> {
+        let guard = self
+            .config
+            .save_guard
+            .iter()
+            .find(|(key, _)| self.matches_file_type(key))
+            .map_or(SaveGuard::Off, |(_, guard)| *guard);
+        if guard == SaveGuard::Off {
+            return None;
+        }
+        self.diagnostic().map(|err| (guard, err))
+    }
+
+    fn diagnostic(&self) -> Option<String> {
+        let extension = self
+            .document
+            .filename
+            .as_deref()
+            .and_then(|filename| Path::new(filename).extension())
+            .and_then(|extension| extension.to_str());
+        let linter = self
+            .config
+            .linters
+            .iter()
+            .find(|(key, _)| self.matches_file_type(key))
+            .map(|(_, command)| command.as_str());
+        lint::check(&self.document.contents(), extension, linter)
+    }
+
+    fn run_diagnostics(&mut self) -> bool {
+        let revision = self.document.revision();
+        if self.diagnostics_revision == Some(revision) {
+            return false;
+        }
+        self.diagnostics_revision = Some(revision);
+        let Some(diagnostic) = self.diagnostic() else {
+            return false;
+        };
+        self.status_message = StatusMessage::from(format!("Diagnostics: {}", diagnostic));
+        true
+    }
+
+    fn view(&mut self) {
+        self.lock = None;
+        self.read_only = true;
+        self.terminal_mode = TerminalMode::Normal;
+        self.status_message = StatusMessage::from(String::from(
+            "Viewing read-only: space/b to page, q to quit",
+        ));
+    }
+
+    fn report_read_only(&mut self) {
+        self.status_message = StatusMessage::from(String::from(
+            "File is open read-only; :set noreadonly to edit",
+        ));
+    }
+
+    fn quit(&mut self) {
+        if !self.document.is_dirty() || self.confirm("Quit without saving?", false) {
+            self.should_quit = true;
+        } else {
+            self.status_message = StatusMessage::from(String::from("Aborted quit"));
+        }
+    }
+
+    fn toggle_line_numbers(&mut self) {
+        let (line_numbers, name) = match self.config.line_numbers {
+            LineNumbers::Off => (LineNumbers::Absolute, "absolute"),
+            LineNumbers::Absolute => (LineNumbers::Relative, "relative"),
+            LineNumbers::Relative => (LineNumbers::Off, "off"),
+        };
+        self.config.line_numbers = line_numbers;
+        self.status_message = StatusMessage::from(format!("Line numbers: {}", name));
+    }
+
+    fn ask(&mut self, dialog: &Dialog) -> Option<char> {
+        let answer = loop {
+            self.status_message = StatusMessage::from(dialog.prompt());
+            if self.refresh_screen().is_err() {
+                break None;
+            }
+            match self.dialog_event() {
+                Ok(Event::Key(key)) => match dialog.answer(key) {
+                    Reply::Chosen(key) => break Some(key),
+                    Reply::Cancelled => break None,
+                    Reply::Ignored => (),
+                },
+                Ok(Event::Resize(width, height)) => self.resize(width, height),
+                Ok(_) => (),
+                Err(_) => break None,
+            }
+        };
+        self.status_message = StatusMessage::from(String::new());
+        answer
+    }
+
+    fn confirm(&mut self, question: &str, default_yes: bool) -> bool {
+        self.ask(&Dialog::yes_no(question, default_yes)) == Some('y')
+    }
+
+    fn delete_file(&mut self) {
+        let Some(filename) = self.document.filename.clone() else {
+            self.status_message = StatusMessage::from(String::from("No file to delete"));
+            return;
+        };
+
+        let question = if self.config.trash {
+            format!("Move {} to trash?", filename)
+        } else {
+            format!("Permanently delete {}?", filename)
+        };
+        if !self.confirm(&question, false) {
+            self.status_message = StatusMessage::from(String::from("Aborted delete"));
+            return;
+        }
+
+        let result = if self.config.trash {
+            Trash::home()
+                .ok_or_else(|| std::io::Error::other("No trash directory"))
+                .and_then(|trash| trash.put(Path::new(&filename)))
+                .map(|name| {
+                    self.last_trashed = Some(name);
+                    format!("Moved {} to trash (:restore-file to undo)", filename)
+                })
+        } else {
+            fs::remove_file(&filename).map(|_| format!("Deleted {}", filename))
+        };
+
+        self.status_message = match result {
+            Ok(message) => {
+                self.document.mark_dirty();
+                StatusMessage::from(message)
+            }
+            Err(err) => StatusMessage::from(format!("Failed to delete {}: {}", filename, err)),
+        };
+    }
+
+    fn rename_file(&mut self, new: &str, force: bool) {
+        if self.read_only {
+            self.report_read_only();
+            return;
+        }
+        let Some(old) = self.document.filename.clone() else {
+            self.status_message = StatusMessage::from(String::from("No file to rename"));
+            return;
+        };
+        if new.is_empty() {
+            self.status_message = StatusMessage::from(String::from("Usage: :rename <new path>"));
+            return;
+        }
+        if Path::new(new).exists() && !force {
+            self.status_message =
+                StatusMessage::from(format!("{} exists (add ! to overwrite)", new));
+            return;
+        }
+        if let Err(err) = fs::rename(&old, new) {
+            self.status_message =
+                StatusMessage::from(format!("Failed to rename {} to {}: {}", old, new, err));
+            return;
+        }
+        self.discard_swap();
+        self.document.filename = Some(new.to_string());
+        self.document.refresh_file_type();
+        self.backed_up = false;
+        self.acquire_lock(false);
+        if !self.update_references(&old, new) {
+            self.status_message = StatusMessage::from(format!(
+                "Renamed {} to {}; no references to update",
+                old, new
+            ));
+        }
+    }
+
+    fn update_references(&mut self, old: &str, new: &str) -> bool {
+        let cwd = env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
+        let root = finder::project_root(&cwd);
+        let old_path = refs::normalize(&cwd.join(old));
+        let new_path = refs::normalize(&cwd.join(new));
+        let open = self
+            .document
+            .filename
+            .as_deref()
+            .map(|filename| refs::normalize(&cwd.join(filename)));
+
+        let mut found = Vec::new();
+        for file in finder::files(&root) {
+            let path = root.join(&file);
+            let is_open = open.as_ref() == Some(&path);
+            let text = if is_open {
+                self.document.contents()
+            } else {
+                match fs::read(&path) {
+                    Ok(bytes) if !bytes.contains(&0) => {
+                        String::from_utf8_lossy(&bytes).into_owned()
+                    }
+                    _ => continue,
+                }
+            };
+            let dir = path.parent().unwrap_or(&root);
+            let edits = refs::find(&text, &refs::replacements(&root, dir, &old_path, &new_path));
+            if !edits.is_empty() {
+                let filename = path
+                    .strip_prefix(&cwd)
+                    .unwrap_or(&path)
+                    .display()
+                    .to_string();
+                found.push((filename, path, is_open, text, edits));
+            }
+        }
+        let count: usize = found.iter().map(|(.., edits)| edits.len()).sum();
+        if count == 0 {
+            return false;
+        }
+
+        let mut overlay = Overlay::new(&format!("References to {} → {}", old, new));
+        overlay.height = Some(self.terminal.size().height as usize / 2);
+        overlay.items = found
+            .iter()
+            .flat_map(|(filename, .., edits)| {
+                edits.iter().map(move |edit| {
+                    format!(
+                        "{}:{}: {} → {}",
+                        filename,
+                        edit.line + 1,
+                        edit.old,
+                        edit.new
+                    )
+                })
+            })
+            .collect();
+        self.overlay = Some(overlay);
+        let question = format!("Update {} reference(s) in {} file(s)?", count, found.len());
+        let answer = self.ask(&Dialog::new(&question, &["apply", "list", "skip"], 0));
+        self.overlay = None;
+
+        match answer {
+            Some('a') => {
+                let mut failed = Vec::new();
+                for (filename, path, is_open, text, edits) in &found {
+                    let updated = refs::apply(text, edits);
+                    if *is_open {
+                        let y = self.document.len().saturating_sub(1);
+                        let x = self.document.row(y).map_or(0, Row::len);
+                        self.document.begin_undo();
+                        self.preserving_view(|document| {
+                            document.replace_text(
+                                &Position::default(),
+                                &Position { x, y },
+                                &updated,
+                            );
+                        });
+                        self.document.end_undo();
+                    } else if let Err(err) = fs::write(path, updated) {
+                        failed.push(format!("{}: {}", filename, err));
+                    }
+                }
+                self.status_message = StatusMessage::from(if failed.is_empty() {
+                    format!("Updated {} reference(s) in {} file(s)", count, found.len())
+                } else {
+                    format!("Could not update {}", failed.join(", "))
+                });
+            }
+            Some('l') => {
+                let entries = found
+                    .iter()
+                    .flat_map(|(filename, _, _, text, edits)| {
+                        let lines: Vec<&str> = text.lines().collect();
+                        edits.iter().map(move |edit| {
+                            let line = lines.get(edit.line).copied().unwrap_or_default();
+                            quickfix::Entry {
+                                filename: Some(filename.clone()),
+                                position: Position {
+                                    x: line[..edit.start].graphemes(true).count(),
+                                    y: edit.line,
+                                },
+                                message: format!("{}: {} → {}", edit.line + 1, edit.old, edit.new),
+                            }
+                        })
+                    })
+                    .collect();
+                self.quickfix.set(entries);
+                self.status_message = StatusMessage::from(format!(
+                    "Listed {} reference(s); :update-refs {} {} applies them",
+                    count, old, new
+                ));
+            }
+            _ => {
+                self.status_message =
+                    StatusMessage::from(format!("Left {} reference(s) to {}", count, old));
+            }
+        }
+        true
+    }
+
+    fn edit_file(&mut self, filename: &str, force: bool) {
+        if self.document.is_dirty() && !force {
+            self.status_message = StatusMessage::from(String::from(
+                "No write since last change (add ! to override)",
+            ));
+            return;
+        }
+        let filename = if filename.is_empty() {
+            self.document.filename.clone()
+        } else {
+            Some(filename.to_string())
+        };
+        let Some(filename) = filename else {
+            self.status_message = StatusMessage::from(String::from("No file name"));
+            return;
+        };
+        if Path::new(&filename).is_dir() {
+            self.browse(Path::new(&filename));
+            return;
+        }
+        self.discard_swap();
+        self.backed_up = false;
+        self.document = if Path::new(&filename).exists() {
+            match Document::open(&filename) {
+                Ok(document) => document,
+                Err(err) => {
+                    self.status_message =
+                        StatusMessage::from(format!("Could not open {}: {}", filename, err));
+                    return;
+                }
+            }
+        } else {
+            Document::create(&filename)
+        };
+        self.listing = None;
+        self.apply_undo_limit();
+        self.cursor_position = Position::default();
+        self.window = Window::default();
+        self.jumps.clear();
+        self.jump_index = 0;
+        self.refresh_git_marks();
+        self.status_message = StatusMessage::from(format!("Editing {}", filename));
+        self.acquire_lock(false);
+        self.offer_swap();
+        self.offer_ansi();
+        self.offer_template();
+    }
+
+    fn browse(&mut self, dir: &Path) {
+        let previous = self.listing.as_ref().map(|listing| listing.dir.clone());
+        let (listing, contents) = match Listing::new(dir)
+            .and_then(|listing| listing.contents().map(|contents| (listing, contents)))
+        {
+            Ok(listed) => listed,
+            Err(err) => {
+                self.status_message =
+                    StatusMessage::from(format!("Could not list {}: {}", dir.display(), err));
+                return;
+            }
+        };
+        self.discard_swap();
+        self.backed_up = false;
+        self.lock = None;
+        self.read_only = true;
+        self.document = Document::scratch(&contents);
+        let first = usize::min(2, self.document.len().saturating_sub(1));
+        self.cursor_position = Position {
+            x: 0,
+            y: previous
+                .and_then(|previous| listing.row_of(&contents, &previous))
+                .unwrap_or(first),
+        };
+        self.window = Window::default();
+        self.jumps.clear();
+        self.jump_index = 0;
+        self.diff_marks.clear();
+        self.diff_base = None;
+        self.status_message = StatusMessage::from(format!(
+            "{} | <CR> = open | - = up | % = new file",
+            listing.dir.display()
+        ));
+        self.listing = Some(listing);
+    }
+
+    fn listing_key(&mut self, key: KeyEvent) -> bool {
+        if self.listing.is_none()
+            || self.terminal_mode != TerminalMode::Normal
+            || self.pending.is_some()
+            || self.count.is_some()
+            || !key.modifiers.difference(KeyModifiers::SHIFT).is_empty()
+        {
+            return false;
+        }
+        match key.code {
+            KeyCode::Enter => self.open_listed(),
+            KeyCode::Char('-') => {
+                if let Some(parent) = self.listing.as_ref().and_then(Listing::parent) {
+                    self.browse(&parent);
+                }
+            }
+            KeyCode::Char('%') => self.create_listed(),
+            _ => return false,
+        }
+        true
+    }
+
+    fn open_listed(&mut self) {
+        let y = self.cursor_position.y;
+        let Some(path) = self.listing.as_ref().and_then(|listing| {
+            self.document
+                .row(y)
+                .and_then(|row| listing.entry(row.as_str(), y))
+        }) else {
+            return;
+        };
+        if path.is_dir() {
+            self.browse(&path);
+        } else {
+            self.edit_file(&relative_to_cwd(&path), false);
+        }
+    }
+
+    fn create_listed(&mut self) {
+        let Some(dir) = self.listing.as_ref().map(|listing| listing.dir.clone()) else {
+            return;
+        };
+        let Some(name) = self
+            .prompt("New file: ", |_, _, _| {})
+            .unwrap_or(None)
+            .map(|name| name.trim().to_string())
+            .filter(|name| !name.is_empty())
+        else {
+            return;
+        };
+        let path = dir.join(name);
+        match fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)
+        {
+            Ok(_) => self.edit_file(&relative_to_cwd(&path), false),
+            Err(err) => {
+                self.status_message =
+                    StatusMessage::from(format!("Could not create {}: {}", path.display(), err));
+            }
+        }
+    }
+
+    fn offer_ansi(&mut self) {
+        if self.should_quit || !self.document.has_escapes() {
+            return;
+        }
+        let question = "Text contains ANSI escape codes:";
+        match self.ask(&Dialog::new(question, &["colour", "strip", "keep"], 0)) {
+            Some('c') => self.convert_escapes("colour"),
+            Some('s') => self.convert_escapes("strip"),
+            _ => (),
+        }
+    }
+
+    fn convert_escapes(&mut self, mode: &str) {
+        let colour = match mode {
+            "" | "colour" | "color" => true,
+            "strip" => false,
+            _ => {
+                self.status_message =
+                    StatusMessage::from(String::from("Usage: :ansi [colour|strip]"));
+                return;
+            }
+        };
+        let mark_dirty = !self.read_only && self.document.filename.is_some();
+        self.document.begin_undo();
+        let converted = self.document.convert_escapes(colour, mark_dirty);
+        self.document.end_undo();
+        self.cursor_position = self.document.clamp(&self.cursor_position);
+        self.status_message = StatusMessage::from(match (converted, colour) {
+            (0, _) => String::from("No ANSI escape codes found"),
+            (_, true) => format!("Coloured {} lines from ANSI escape codes", converted),
+            (_, false) => format!("Stripped ANSI escape codes from {} lines", converted),
+        });
+    }
+
+    fn offer_template(&mut self) {
+        let Some(filename) = self.document.filename.clone() else {
+            return;
+        };
+        if self.should_quit || Path::new(&filename).exists() || !self.document.is_empty() {
+            return;
+        }
+        let names = Templates::discover(&filename).names_for(&filename);
+        if names.is_empty() {
+            return;
+        }
+        let prompt = format!(
+            "New file {}, template ({}; ESC = none): ",
+            filename,
+            names.join(", ")
+        );
+        if let Some(name) = self.prompt_for(&prompt, &Expected::Choice(names), None) {
+            self.insert_template(&name);
+        }
+    }
+
+    fn insert_template(&mut self, name: &str) {
+        let filename = self.document.filename.clone().unwrap_or_default();
+        let templates = Templates::discover(&filename);
+        if name.is_empty() {
+            let names = templates.names_for(&filename);
+            self.status_message = StatusMessage::from(if names.is_empty() {
+                String::from("No templates for this file")
+            } else {
+                format!("Templates: {}", names.join(", "))
+            });
+            return;
+        }
+        let Some(template) = templates.load(name, &filename) else {
+            self.status_message = StatusMessage::from(format!("No template named {}", name));
+            return;
+        };
+        let mut text = templates::render(&template, &filename, &self.author());
+        if self.document.is_empty() && text.ends_with('\n') {
+            text.pop();
+        }
+        self.document.seal_undo();
+        self.cursor_position = self.document.insert_str(&self.cursor_position, &text);
+        self.document.seal_undo();
+        self.scroll();
+        self.status_message = StatusMessage::from(format!("Inserted template {}", name));
+    }
+
+    fn license_header(&mut self, files: &str) {
+        let current = self.document.filename.clone();
+        let anchor = files
+            .split_whitespace()
+            .next()
+            .map(str::to_string)
+            .or_else(|| current.clone())
+            .unwrap_or_default();
+        let Some(license) = Templates::discover(&anchor).license() else {
+            self.status_message = StatusMessage::from(String::from(
+                "No license template; add .texty/templates/license.txt",
+            ));
+            return;
+        };
+        let author = self.author();
+        if files.trim().is_empty() {
+            let filename = current.unwrap_or_default();
+            let text = templates::render(&license, &filename, &author);
+            self.status_message = StatusMessage::from(match self.document.set_header(&text) {
+                Some(true) => String::from("License header refreshed"),
+                Some(false) => String::from("License header inserted"),
+                None => String::from("No line comment syntax for this file type"),
+            });
+            return;
+        }
+
+        let mut updated = 0;
+        let mut failed = Vec::new();
+        for filename in files.split_whitespace() {
+            let text = templates::render(&license, filename, &author);
+            if current.as_deref() == Some(filename) {
+                if self.document.set_header(&text).is_some() {
+                    updated += 1;
+                } else {
+                    failed.push(filename);
+                }
+                continue;
+            }
+            let result = Document::open(filename).ok().and_then(|mut document| {
+                document.set_header(&text)?;
+                document.save().ok()
+            });
+            if result.is_some() {
+                updated += 1;
+            } else {
+                failed.push(filename);
+            }
+        }
+        let mut message = format!("License header written to {} file(s)", updated);
+        if !failed.is_empty() {
+            message.push_str(&format!(", failed: {}", failed.join(" ")));
+        }
+        self.status_message = StatusMessage::from(message);
+    }
+
+    fn author(&self) -> String {
+        self.config
+            .author
+            .clone()
+            .unwrap_or_else(templates::default_author)
+    }
+
+    fn apply_undo_limit(&mut self) {
+        self.document
+            .set_undo_limit(self.config.undo_limit, self.config.undo_spill);
+    }
+
+    fn show_undo_info(&mut self) {
+        let history = self.document.undo_history();
+        let limit = history
+            .limit()
+            .map_or_else(|| String::from("unlimited"), format_size);
+        let mut info = format!(
+            "undo: {} steps | redo: {} steps | memory: {} of {}",
+            history.undo_len(),
+            history.redo_len(),
+            format_size(history.memory()),
+            limit
+        );
+        let (spilled, spilled_bytes) = history.spilled();
+        if spilled > 0 {
+            info.push_str(&format!(
+                " | on disk: {} steps ({})",
+                spilled,
+                format_size(spilled_bytes as usize)
+            ));
+        }
+        if history.evicted() > 0 {
+            info.push_str(&format!(" | dropped: {} steps", history.evicted()));
+        }
+        self.status_message = StatusMessage::from(info);
+    }
+
+    fn edit_arg(&mut self, forward: bool, force: bool) {
+        let index = if forward {
+            self.arg_index.checked_add(1)
+        } else {
+            self.arg_index.checked_sub(1)
+        };
+        let Some((index, filename)) =
+            index.and_then(|index| Some((index, self.arg_list.get(index)?.clone())))
+        else {
+            self.status_message = StatusMessage::from(String::from(if forward {
+                "No next file in the argument list"
+            } else {
+                "Already at the first file in the argument list"
+            }));
+            return;
+        };
+        self.edit_file(&filename, force);
+        if self.document.filename.as_deref() == Some(filename.as_str()) {
+            self.arg_index = index;
+            self.status_message = StatusMessage::from(format!(
+                "Editing {} ({} of {})",
+                filename,
+                index.saturating_add(1),
+                self.arg_list.len()
+            ));
+        }
+    }
+
+    fn show_args(&mut self) {
+        let args: Vec<String> = self
+            .arg_list
+            .iter()
+            .enumerate()
+            .map(|(index, filename)| {
+                if index == self.arg_index {
+                    format!("[{}]", filename)
+                } else {
+                    filename.clone()
+                }
+            })
+            .collect();
+        self.status_message = StatusMessage::from(if args.is_empty() {
+            String::from("Argument list is empty")
+        } else {
+            args.join(" ")
+        });
+    }
+
+    fn restore_file(&mut self, name: &str) {
+        let name = if name.is_empty() {
+            self.last_trashed.clone()
+        } else {
+            Some(name.to_string())
+        };
+        let Some(name) = name else {
+            self.status_message = StatusMessage::from(String::from("Nothing to restore"));
+            return;
+        };
+
+        let result = Trash::home()
+            .ok_or_else(|| std::io::Error::other("No trash directory"))
+            .and_then(|trash| trash.restore(&name));
+        self.status_message = match result {
+            Ok(path) => {
+                if self.last_trashed.as_deref() == Some(name.as_str()) {
+                    self.last_trashed = None;
+                }
+                StatusMessage::from(format!("Restored {}", path.display()))
+            }
+            Err(err) => StatusMessage::from(format!("Failed to restore {}: {}", name, err)),
+        };
+    }
+
+    fn pick<T>(
+        &mut self,
+        prompt: &str,
+        overlay: Overlay,
+        mut filter: impl FnMut(&mut Self, &str) -> T,
+    ) -> Option<T> {
+        self.overlay = Some(overlay);
+        let mut picked = filter(self, "");
+        let answer = self.prompt(prompt, |editor, key, query| {
+            if let Some(overlay) = &mut editor.overlay {
+                match key.code {
+                    KeyCode::Up => overlay.selected = overlay.selected.saturating_sub(1),
+                    KeyCode::Down => overlay.selected = overlay.selected.saturating_add(1),
+                    _ => overlay.selected = 0,
+                }
+            }
+            picked = filter(editor, query);
+        });
+        self.overlay = None;
+        answer.ok().flatten().map(|_| picked)
+    }
+
+    fn line_jump(&mut self) {
+        let original_position = self.cursor_position;
+        let original_window = self.window;
+        let mut overlay = Overlay::new("");
+        overlay.height = Some(self.terminal.size().height as usize / 2);
+        overlay.selected = self.cursor_position.y;
+        let found = self.pick(
+            "Lines (ESC = cancel, Up | Down = select): ",
+            overlay,
+            Self::filter_lines,
+        );
+
+        if found != Some(true) {
+            self.cursor_position = original_position;
+            self.window = original_window;
+        } else {
+            self.record_jump(original_position);
+        }
+    }
+
+    fn filter_lines(&mut self, query: &str) -> bool {
+        let Some(overlay) = &mut self.overlay else {
+            return false;
+        };
+        let matches = fuzzy::rank(query, self.document.lines());
+        let width = self.document.len().to_string().len();
+        overlay.title = format!("Lines {}/{}", matches.len(), self.document.len());
+        overlay.items = matches[overlay.window(matches.len(), OVERLAY_ITEMS)]
+            .iter()
+            .filter_map(|&(y, _)| {
+                let row = self.document.row(y)?;
+                let text = row.as_str().replace('\t', " ");
+                Some(format!("{:>width$} {}", y + 1, text, width = width))
+            })
+            .collect();
+        let Some(&(y, _)) = matches.get(overlay.selected) else {
+            self.prompt_hint = String::from(" [no match]");
+            return false;
+        };
+        self.preview_row(y);
+        true
+    }
+
+    fn preview_row(&mut self, y: usize) {
+        let covered = self
+            .overlay
+            .as_ref()
+            .and_then(|overlay| overlay.height)
+            .unwrap_or(0);
+        let visible = (self.terminal.size().height as usize).saturating_sub(covered);
+        self.prompt_hint.clear();
+        self.cursor_position = Position { x: 0, y };
+        self.window.offset.y = y.saturating_sub(visible / 2);
+        self.window.top_segment = 0;
+    }
+
+    fn symbol_jump(&mut self) {
+        let patterns = match outline::compile(self.document.outline()) {
+            Ok(patterns) => patterns,
+            Err(err) => {
+                self.status_message = StatusMessage::from(err);
+                return;
+            }
+        };
+        let symbols = outline::symbols(&patterns, self.document.lines());
+        if symbols.is_empty() {
+            self.status_message =
+                StatusMessage::from(format!("No symbols found ({})", self.document.file_type()));
+            return;
+        }
+
+        let original_position = self.cursor_position;
+        let original_window = self.window;
+        let mut overlay = Overlay::new("");
+        overlay.height = Some(self.terminal.size().height as usize / 2);
+        overlay.selected = symbols
+            .iter()
+            .take_while(|symbol| symbol.line <= self.cursor_position.y)
+            .count()
+            .saturating_sub(1);
+        let found = self.pick(
+            "Symbols (ESC = cancel, Up | Down = select): ",
+            overlay,
+            |editor, query| editor.filter_symbols(&symbols, query),
+        );
+
+        if found != Some(true) {
+            self.cursor_position = original_position;
+            self.window = original_window;
+        } else {
+            self.record_jump(original_position);
+        }
+    }
+
+    fn filter_symbols(&mut self, symbols: &[Symbol], query: &str) -> bool {
+        let Some(overlay) = &mut self.overlay else {
+            return false;
+        };
+        let matches = fuzzy::rank(query, symbols.iter().map(|symbol| symbol.name.as_str()));
+        let width = self.document.len().to_string().len();
+        overlay.title = format!("Symbols {}/{}", matches.len(), symbols.len());
+        overlay.items = matches[overlay.window(matches.len(), OVERLAY_ITEMS)]
+            .iter()
+            .map(|&(index, _)| {
+                let symbol = &symbols[index];
+                format!(
+                    "{:>width$} {}",
+                    symbol.line + 1,
+                    symbol.label,
+                    width = width
+                )
+            })
+            .collect();
+        let Some(&(index, _)) = matches.get(overlay.selected) else {
+            self.prompt_hint = String::from(" [no match]");
+            return false;
+        };
+        self.preview_row(symbols[index].line);
+        true
+    }
+
+    fn toggle_file_tree(&mut self) {
+        match &mut self.file_tree {
+            Some(tree) if tree.focused => self.file_tree = None,
+            Some(tree) => tree.focused = true,
+            None => {
+                let cwd = env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
+                let mut tree = FileTree::new(&cwd);
+                if let Some(filename) = &self.document.filename {
+                    tree.select(&refs::normalize(&cwd.join(filename)));
+                }
+                self.file_tree = Some(tree);
+            }
+        }
+    }
+
+    fn tree_action(&mut self, action: TreeAction) {
+        let cwd = env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
+        let relative = relative_to_cwd;
+        match action {
+            TreeAction::None => (),
+            TreeAction::Open(path) => {
+                let filename = relative(&path);
+                self.edit_file(&filename, false);
+                if self.is_open(&filename) {
+                    if let Some(tree) = &mut self.file_tree {
+                        tree.focused = false;
+                    }
+                }
+            }
+            TreeAction::Create(dir) => {
+                let default = match relative(&dir) {
+                    dir if dir.is_empty() => dir,
+                    dir => format!("{}{}", dir, std::path::MAIN_SEPARATOR),
+                };
+                let Some(name) = self
+                    .prompt_with_default(
+                        &format!(
+                            "New file (end with {} for a directory): ",
+                            std::path::MAIN_SEPARATOR
+                        ),
+                        &default,
+                        |_, _, _| {},
+                    )
+                    .unwrap_or(None)
+                    .filter(|name| !name.trim().is_empty() && name.trim() != default)
+                else {
+                    return;
+                };
+                let name = name.trim();
+                let created = if name.ends_with(std::path::is_separator) {
+                    fs::create_dir_all(name)
+                } else {
+                    Path::new(name)
+                        .parent()
+                        .filter(|parent| !parent.as_os_str().is_empty())
+                        .map_or(Ok(()), fs::create_dir_all)
+                        .and_then(|()| {
+                            fs::OpenOptions::new()
+                                .write(true)
+                                .create_new(true)
+                                .open(name)
+                        })
+                        .map(|_| ())
+                };
+                if let Err(err) = created {
+                    self.status_message =
+                        StatusMessage::from(format!("Could not create {}: {}", name, err));
+                    return;
+                }
+                if let Some(tree) = &mut self.file_tree {
+                    tree.select(&refs::normalize(&cwd.join(name)));
+                }
+                self.status_message = StatusMessage::from(format!("Created {}", name));
+            }
+            TreeAction::Rename(path) => {
+                let old = relative(&path);
+                let Some(new) = self
+                    .prompt_with_default("Rename to: ", &old, |_, _, _| {})
+                    .unwrap_or(None)
+                    .map(|new| new.trim().to_string())
+                    .filter(|new| !new.is_empty() && *new != old)
+                else {
+                    return;
+                };
+                if self.is_open(&old) {
+                    self.rename_file(&new, false);
+                } else if Path::new(&new).exists() {
+                    self.status_message = StatusMessage::from(format!("{} exists", new));
+                } else if let Err(err) = fs::rename(&old, &new) {
+                    self.status_message = StatusMessage::from(format!(
+                        "Failed to rename {} to {}: {}",
+                        old, new, err
+                    ));
+                } else if !self.update_references(&old, &new) {
+                    self.status_message =
+                        StatusMessage::from(format!("Renamed {} to {}", old, new));
+                }
+                if let Some(tree) = &mut self.file_tree {
+                    tree.select(&refs::normalize(&cwd.join(&new)));
+                }
+            }
+            TreeAction::Delete(path) => {
+                let name = relative(&path);
+                if self.is_open(&name) {
+                    self.delete_file();
+                } else {
+                    self.delete_path(&name);
+                }
+                if let Some(tree) = &mut self.file_tree {
+                    tree.refresh();
+                }
+            }
+            TreeAction::Unfocus => {
+                if let Some(tree) = &mut self.file_tree {
+                    tree.focused = false;
+                }
+            }
+            TreeAction::Close => self.file_tree = None,
+        }
+    }
+
+    fn delete_path(&mut self, name: &str) {
+        let question = if self.config.trash {
+            format!("Move {} to trash?", name)
+        } else {
+            format!("Permanently delete {}?", name)
+        };
+        if !self.confirm(&question, false) {
+            self.status_message = StatusMessage::from(String::from("Aborted delete"));
+            return;
+        }
+        let path = Path::new(name);
+        let result = if self.config.trash {
+            Trash::home()
+                .ok_or_else(|| std::io::Error::other("No trash directory"))
+                .and_then(|trash| trash.put(path))
+                .map(|trashed| {
+                    self.last_trashed = Some(trashed);
+                    format!("Moved {} to trash (:restore-file to undo)", name)
+                })
+        } else if path.is_dir() {
+            fs::remove_dir_all(path).map(|()| format!("Deleted {}", name))
+        } else {
+            fs::remove_file(path).map(|()| format!("Deleted {}", name))
+        };
+        self.status_message = StatusMessage::from(match result {
+            Ok(message) => message,
+            Err(err) => format!("Failed to delete {}: {}", name, err),
+        });
+    }
+
+    fn find_file(&mut self) {
+        let root = env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
+        let files = finder::files(&root);
+        let choice = self.pick(
+            "Find file (ESC = cancel, Up | Down = select): ",
+            Overlay::new(""),
+            |editor, query| editor.filter_files(&files, query),
+        );
+
+        match choice {
+            None => (),
+            Some(Some(filename)) => self.edit_file(&filename, false),
+            Some(None) => {
+                self.status_message = StatusMessage::from(String::from("No matching file"));
+            }
+        }
+    }
+
+    fn filter_files(&mut self, files: &[String], query: &str) -> Option<String> {
+        let overlay = self.overlay.as_mut()?;
+        let matches = fuzzy::rank(query, files.iter().map(String::as_str));
+        overlay.title = format!("Files {}/{}", matches.len(), files.len());
+        overlay.items = matches[overlay.window(matches.len(), OVERLAY_ITEMS)]
+            .iter()
+            .map(|&(index, _)| files[index].clone())
+            .collect();
+        matches
+            .get(overlay.selected)
+            .map(|&(index, _)| files[index].clone())
+    }
+
+    fn center_on(&mut self, y: usize) {
+        let height = self.terminal.size().height as usize;
+        self.window.offset.y = y.saturating_sub(height / 2);
+        self.window.top_segment = 0;
+    }
+
+    fn search(&mut self) {
+        let current_position = self.cursor_position;
+        let mut direction = SearchDirection::Forward;
+
+        let query = self
+            .prompt_with_history(
+                HistoryKind::Search,
+                "Search (ESC = cancel, Left | Right = nav, Up | Down = history): ",
+                |editor, key, query| {
+                    let mut moved = false;
+                    match key.code {
+                        KeyCode::Right => {
+                            direction = SearchDirection::Forward;
+                            moved = true;
+                            editor.move_cursor(KeyCode::Right);
+                        }
+                        KeyCode::Left => {
+                            direction = SearchDirection::Backward;
+                        }
+                        _ => direction = SearchDirection::Forward,
+                    }
+                    if let Err(err) = editor.set_search_query(Some(query.clone())) {
+                        editor.prompt_hint = format!(" [{}]", err);
+                        return;
+                    }
+                    let mut wrapped = false;
+                    if let Some((position, wrap)) = editor.find(&editor.cursor_position, direction)
+                    {
+                        editor.cursor_position = position;
+                        wrapped = wrap;
+                        editor.scroll();
+                    } else if moved {
+                        editor.move_cursor(KeyCode::Left);
+                    }
+
+                    editor.prompt_hint = match editor.match_counter() {
+                        Some(counter) if wrapped => format!(" [search wrapped, {}]", counter),
+                        Some(counter) => format!(" [{}]", counter),
+                        None => String::new(),
+                    };
+                },
+            )
+            .unwrap_or(None);
+
+        match query.filter(|query| !query.is_empty()) {
+            None => {
+                self.cursor_position = current_position;
+                self.set_search_query(None).ok();
+                self.scroll();
+            }
+            Some(query) => {
+                if self.cursor_position != current_position {
+                    self.record_jump(current_position);
+                }
+                self.status_message = StatusMessage::from(
+                    self.match_counter()
+                        .unwrap_or_else(|| format!("Pattern not found: {}", query)),
+                );
+                self.last_search = Some(query);
+                self.search_direction = SearchDirection::Forward;
+            }
+        }
+    }
+
+    fn search_word(&mut self, direction: SearchDirection) {
+        let Some((start, word)) = self.word_under_cursor() else {
+            self.status_message = StatusMessage::from(String::from("No word under cursor"));
+            return;
+        };
+        self.cursor_position = Position {
+            x: start,
+            y: self.cursor_position.y,
+        };
+        self.last_search = Some(word);
+        self.search_direction = direction;
+        self.search_next(direction);
+    }
+
+    fn word_under_cursor(&self) -> Option<(usize, String)> {
+        let Position { x, y } = self.cursor_position;
+        let row = self.document.row(y)?;
+        let (start, end) = row.word_at(x)?;
+        Some((
+            start,
+            row.as_str()[row.byte_index(start)..row.byte_index(end)].to_string(),
+        ))
+    }
+
+    fn search_next(&mut self, direction: SearchDirection) {
+        let Some(query) = self.last_search.clone() else {
+            self.status_message = StatusMessage::from(String::from("No previous search"));
+            return;
+        };
+        let Position { x, y } = self.cursor_position;
+        let from = match direction {
+            SearchDirection::Forward => Position {
+                x: x.saturating_add(1),
+                y,
+            },
+            SearchDirection::Backward => self.cursor_position,
+        };
+        if let Err(err) = self.set_search_query(Some(query.clone())) {
+            self.status_message = StatusMessage::from(err);
+            return;
+        }
+        match self.find(&from, direction) {
+            Some((position, wrapped)) => {
+                self.jump_to(position);
+                let counter = self.match_counter().unwrap_or_default();
+                self.status_message = StatusMessage::from(if wrapped {
+                    format!("search wrapped: {}", counter)
+                } else {
+                    counter
+                });
+            }
+            None => {
+                self.status_message = StatusMessage::from(format!("Pattern not found: {}", query));
+            }
+        }
+    }
+
+    fn find(&self, from: &Position, direction: SearchDirection) -> Option<(Position, bool)> {
+        let matcher = self.search_matcher.as_deref()?;
+        if self.config.wrap_scan {
+            self.document.find_wrapping(matcher, from, direction)
+        } else {
+            self.document
+                .find(matcher, from, direction)
+                .map(|position| (position, false))
+        }
+    }
+
+    fn match_counter(&mut self) -> Option<String> {
+        let matcher = self.search_matcher.as_deref()?;
+        let query = self.search_query.clone()?;
+        let revision = self.document.revision();
+        let fresh = self
+            .search_matches
+            .as_ref()
+            .is_some_and(|(cached, at, _)| *cached == query && *at == revision);
+        if !fresh {
+            self.search_matches = Some((query, revision, self.document.match_positions(matcher)));
+        }
+        let (_, _, positions) = self.search_matches.as_ref()?;
+        let Position { x, y } = self.cursor_position;
+        let index = positions.partition_point(|&position| position <= (y, x));
+        (!positions.is_empty()).then(|| format!("match {} of {}", index, positions.len()))
+    }
+
+    fn matcher(&self, query: &str) -> Result<Box<dyn Matcher>, String> {
+        self.search_providers
+            .compile(&self.config.search_provider, query, self.ignore_case(query))
+    }
+
+    fn set_search_query(&mut self, query: Option<String>) -> Result<(), String> {
+        self.search_matcher = None;
+        self.search_rows = None;
+        self.search_query = None;
+        if let Some(query) = query {
+            self.search_matcher = Some(self.matcher(&query)?);
+            self.search_query = Some(query);
+        }
+        Ok(())
+    }
+
+    fn ignore_case(&self, query: &str) -> bool {
+        self.config.ignore_case
+            && !(self.config.smart_case && query.chars().any(char::is_uppercase))
+    }
+
+    fn process_keypress(&mut self) -> Result<(), std::io::Error> {
+        let event = self.next_event()?;
+        let started = Instant::now();
+
+        if let Event::Key(key) = event {
+            let control = key.modifiers.contains(KeyModifiers::CONTROL);
+            let tree_key = !control && self.file_tree.as_ref().is_some_and(|tree| tree.focused);
+            let height = self.terminal.size().height as usize;
+            if let Some(view) = self.diff_view.as_mut().filter(|_| !control) {
+                if !view.handle_key(key, height) {
+                    self.diff_view = None;
+                }
+            } else if let Some(tree) = self.file_tree.as_mut().filter(|_| tree_key) {
+                let action = tree.handle_key(key);
+                self.tree_action(action);
+            } else if self.listing_key(key) {
+            } else if let Some(command) = self.local_binding(key) {
+                self.run_binding(&command);
+            } else if let Some(action) = self.key_action(key) {
+                self.execute(action);
+            }
+        } else if let Event::Mouse(mouse) = event {
+            self.process_mouse(mouse);
+        } else if let Event::Resize(width, height) = event {
+            self.resize(width, height);
+        } else if let Event::Paste(text) = event {
+            self.paste_text(&text);
+        }
+        if self.replaying && self.pending_input.is_empty() {
+            self.replaying = false;
+            self.document.end_undo();
+        }
+        if self.terminal_mode != TerminalMode::Insert {
+            self.document.seal_undo();
+        }
+        self.scroll();
+        if let Some(timings) = &mut self.timings {
+            timings.event = started.elapsed();
+            timings.events = timings.events.saturating_add(1);
+        }
+
+        Ok(())
+    }
+
+    fn local_binding(&self, key: KeyEvent) -> Option<String> {
+        if self.terminal_mode == TerminalMode::Insert
+            || self.pending.is_some()
+            || self.count.is_some()
+        {
+            return None;
+        }
+        let notation = macros::to_notation(&[key]);
+        if let Some(command) = self.document.keymap().get(&notation) {
+            return Some(command.clone());
+        }
+        self.config
+            .keymaps
+            .iter()
+            .filter(|(file_type, _)| self.matches_file_type(file_type))
+            .flat_map(|(_, keymap)| keymap.iter())
+            .find(|(key, _)| macros::single_key(key).is_ok_and(|key| key == notation))
+            .map(|(_, command)| command.clone())
+    }
+
+    fn run_binding(&mut self, command: &str) {
+        if self.command_depth >= commands::MAX_DEPTH {
+            self.status_message =
+                StatusMessage::from(String::from("Key mappings nested too deeply"));
+            return;
+        }
+        self.command_depth = self.command_depth.saturating_add(1);
+        match self.expand_tokens(command) {
+            Ok(command) => self.execute_command(command.trim().trim_start_matches(':')),
+            Err(err) => self.status_message = StatusMessage::from(err),
+        }
+        self.command_depth = self.command_depth.saturating_sub(1);
+    }
+
+    fn map_key(&mut self, args: &str) {
+        let (key, command) = args.split_once(' ').unwrap_or((args, ""));
+        if key.is_empty() {
+            let maps: Vec<String> = self
+                .document
+                .keymap()
+                .iter()
+                .map(|(key, command)| format!("{} :{}", key, command))
+                .collect();
+            self.status_message = StatusMessage::from(if maps.is_empty() {
+                String::from("No buffer mappings")
+            } else {
+                maps.join(" | ")
+            });
+            return;
+        }
+        let key = match macros::single_key(key) {
+            Ok(key) => key,
+            Err(err) => {
+                self.status_message = StatusMessage::from(err);
+                return;
+            }
+        };
+        let command = command.trim();
+        if command.is_empty() {
+            self.status_message = StatusMessage::from(match self.document.keymap().get(&key) {
+                Some(command) => format!("{} :{}", key, command),
+                None => format!("{} is not mapped in this buffer", key),
+            });
+            return;
+        }
+        self.document.map_key(key.clone(), command.to_string());
+        self.status_message = StatusMessage::from(format!("Mapped {} to :{}", key, command));
+    }
+
+    fn unmap_key(&mut self, key: &str) {
+        let message = match macros::single_key(key) {
+            Ok(key) if self.document.unmap_key(&key) => format!("Unmapped {}", key),
+            Ok(key) => format!("{} is not mapped in this buffer", key),
+            Err(err) => err,
+        };
+        self.status_message = StatusMessage::from(message);
+    }
+
+    fn insert_char(&mut self, c: char) {
+        let (previous, next) = self.chars_around_cursor();
+        let pair = self
+            .document
+            .auto_pairs()
+            .iter()
+            .find(|&&(open, close)| open == c || close == c)
+            .copied();
+        if let Some((open, close)) = pair.filter(|_| self.config.auto_pairs) {
+            if c == close && next == Some(close) {
+                self.move_cursor(KeyCode::Right);
+                return;
+            }
+            let symmetric = open == close;
+            let after_word = previous.is_some_and(char::is_alphanumeric);
+            let before_space = next.is_none_or(|next| {
+                next.is_whitespace()
+                    || self
+                        .document
+                        .auto_pairs()
+                        .iter()
+                        .any(|&(_, close)| close == next)
+            });
+            if c == open && before_space && !(symmetric && after_word) {
+                self.document
+                    .insert_str(&self.cursor_position, &format!("{}{}", open, close));
+                self.move_cursor(KeyCode::Right);
+                return;
+            }
+        }
+        self.document.insert(&self.cursor_position, c);
+        self.move_cursor(KeyCode::Right);
+    }
+
+    fn chars_around_cursor(&self) -> (Option<char>, Option<char>) {
+        let Position { x, y } = self.cursor_position;
+        self.document.row(y).map_or((None, None), |row| {
+            let (before, after) = row.as_str().split_at(row.byte_index(x));
+            (before.chars().next_back(), after.chars().next())
+        })
+    }
+
+    fn insert_tab(&mut self) {
+        if !self.config.expandtab {
+            self.document.insert(&self.cursor_position, '\t');
+            self.move_cursor(KeyCode::Right);
+            return;
+        }
+        let Position { x, y } = self.cursor_position;
+        let tab_width = usize::max(self.config.tab_width, 1);
+        let column = self
+            .document
+            .row(y)
+            .map_or(0, |row| row.display_column(x, tab_width));
+        let spaces = tab_width.saturating_sub(column % tab_width);
+        self.cursor_position = self
+            .document
+            .insert_str(&self.cursor_position, &" ".repeat(spaces));
+    }
+
+    fn undo(&mut self) {
+        match self.document.undo() {
+            Some(position) => self.cursor_position = position,
+            None => {
+                self.status_message = StatusMessage::from(String::from("Already at oldest change"));
+            }
+        }
+    }
+
+    fn redo(&mut self) {
+        match self.document.redo() {
+            Some(position) => self.cursor_position = position,
+            None => {
+                self.status_message = StatusMessage::from(String::from("Already at newest change"));
+            }
+        }
+    }
+
+    fn toggle_visual_mode(&mut self) {
+        if self.terminal_mode == TerminalMode::Visual {
+            self.terminal_mode = TerminalMode::Normal;
+        } else {
+            self.selection_anchor = self.cursor_position;
+            self.terminal_mode = TerminalMode::Visual;
+        }
+    }
+
+    fn process_mouse(&mut self, mouse: MouseEvent) {
+        match mouse.kind {
+            MouseEventKind::Down(MouseButton::Left) => {
+                if let Some(position) = self.screen_to_position(mouse.column, mouse.row) {
+                    if self.terminal_mode == TerminalMode::Visual {
+                        self.terminal_mode = TerminalMode::Normal;
+                    }
+                    self.cursor_position = position;
+                }
+            }
+            MouseEventKind::Drag(MouseButton::Left) => {
+                if let Some(position) = self.screen_to_position(mouse.column, mouse.row) {
+                    if self.terminal_mode != TerminalMode::Visual {
+                        self.selection_anchor = self.cursor_position;
+                        self.terminal_mode = TerminalMode::Visual;
+                    }
+                    self.cursor_position = position;
+                }
+            }
+            MouseEventKind::ScrollDown => self.scroll_viewport(KeyCode::Down, MOUSE_SCROLL_LINES),
+            MouseEventKind::ScrollUp => self.scroll_viewport(KeyCode::Up, MOUSE_SCROLL_LINES),
+            _ => (),
+        }
+    }
+
+    fn screen_to_position(&self, column: u16, row: u16) -> Option<Position> {
+        let row = row.checked_sub(u16::from(self.config.tabline))?;
+        if row >= self.terminal.size().height {
+            return None;
+        }
+        let column = (column as usize).saturating_sub(self.text_offset());
+        let last_row = self.document.len().saturating_sub(1);
+        let layout = self.layout();
+
+        let (column, y) = if self.config.wrap {
+            let mut line = self.top_line();
+            for _ in 0..row {
+                match layout.next(&self.document, line) {
+                    Some(next) => line = next,
+                    None => break,
+                }
+            }
+            let (start, _) = layout.bounds(line);
+            (start.saturating_add(column), line.row)
+        } else {
+            (
+                self.window.offset.x.saturating_add(column),
+                usize::min(self.window.offset.y.saturating_add(row as usize), last_row),
+            )
+        };
+
+        Some(Position {
+            x: layout.grapheme_at(&self.document, column, y),
+            y,
+        })
+    }
+
+    fn scroll_viewport(&mut self, direction: KeyCode, lines: usize) {
+        let height = self.terminal.size().height as usize;
+        if self.config.wrap {
+            let layout = self.layout();
+            let mut top = self.top_line();
+            for _ in 0..lines {
+                let next = if direction == KeyCode::Down {
+                    layout.next(&self.document, top)
+                } else {
+                    layout.previous(&self.document, top)
+                };
+                match next {
+                    Some(next) => top = next,
+                    None => break,
+                }
+            }
+            self.window.offset.y = top.row;
+            self.window.top_segment = top.segment;
+
+            let Position { x, y } = self.cursor_position;
+            let cursor_line = layout.line_at(&self.document, x, y);
+            if (cursor_line.row, cursor_line.segment) < (top.row, top.segment)
+                || layout.distance(&self.document, top, cursor_line) >= height
+            {
+                let target = if direction == KeyCode::Down {
+                    top
+                } else {
+                    let mut bottom = top;
+                    for _ in 1..height {
+                        match layout.next(&self.document, bottom) {
+                            Some(next) => bottom = next,
+                            None => break,
+                        }
+                    }
+                    bottom
+                };
+                let (start, _) = layout.bounds(target);
+                self.cursor_position = Position {
+                    x: layout.grapheme_at(&self.document, start, target.row),
+                    y: target.row,
+                };
+            }
+            return;
+        }
+
+        let last_row = self.document.len().saturating_sub(1);
+        self.window.offset.y = if direction == KeyCode::Down {
+            usize::min(self.window.offset.y.saturating_add(lines), last_row)
+        } else {
+            self.window.offset.y.saturating_sub(lines)
+        };
+        let bottom = self
+            .window
+            .offset
+            .y
+            .saturating_add(height.saturating_sub(1));
+        let y = self.cursor_position.y.clamp(self.window.offset.y, bottom);
+        let width = self.document.row(y).map_or(0, Row::len);
+        self.cursor_position = Position {
+            x: usize::min(self.cursor_position.x, width),
+            y,
+        };
+    }
+
+    fn normal_action(&mut self, c: char) -> Option<Action> {
+        if let Some(pending) = self.pending.take() {
+            let count = self.count.take();
+            return match (pending, c) {
+                ('g', 'j') => Some(Action::MoveVisualLine(Motion::Down)),
+                ('g', 'k') => Some(Action::MoveVisualLine(Motion::Up)),
+                ('g', 'g') => Some(Action::GotoLine(count.unwrap_or(1))),
+                ('g', 'c') => Some(Action::ToggleComment(count.unwrap_or(1))),
+                ('i', 'v') => Some(Action::SelectSubword { around: false }),
+                ('a', 'v') => Some(Action::SelectSubword { around: true }),
+                ('q', register) if register.is_ascii_alphanumeric() => {
+                    Some(Action::RecordMacro(register))
+                }
+                ('@', register) => Some(Action::ReplayMacro(register, count.unwrap_or(1))),
+                ('m', mark) if mark.is_ascii_alphabetic() => Some(Action::SetMark(mark)),
+                ('\'', mark) => Some(Action::JumpToMark(mark)),
+                ('"', register) if Registers::is_valid(register) => {
+                    self.count = count;
+                    Some(Action::SelectRegister(register))
+                }
+                _ => None,
+            };
+        }
+
+        if let Some(digit) = c.to_digit(10) {
+            if digit > 0 || self.count.is_some() {
+                let count = self.count.unwrap_or(0);
+                self.count = Some(count.saturating_mul(10).saturating_add(digit as usize));
+                return None;
+            }
+        }
+
+        if matches!(c, '@' | '"' | 'm' | '\'') {
+            self.pending = Some(c);
+            return None;
+        }
+
+        let count = self.count.take();
+        if let Some((operator, operator_count)) = self.operator.take() {
+            let count = operator_count.saturating_mul(count.unwrap_or(1));
+            let motion = if c == operator.key() {
+                Motion::Line
+            } else {
+                Motion::from_key(c)?
+            };
+            return Some(Action::Operate(operator, motion, count));
+        }
+
+        let normal = self.terminal_mode == TerminalMode::Normal;
+        let visual = self.terminal_mode == TerminalMode::Visual;
+        let action = match c {
+            '>' | '<' if visual => match Operator::from_key(c)? {
+                Operator::Shift(direction) => Action::ShiftSelection(direction),
+                _ => return None,
+            },
+            'd' | 'x' if visual => Action::OperateSelection(Operator::Delete),
+            'c' | 'y' if visual => Action::OperateSelection(Operator::from_key(c)?),
+            'd' | 'c' | 'y' | '>' | '<' if normal => {
+                self.operator = Some((Operator::from_key(c)?, count.unwrap_or(1)));
+                return None;
+            }
+            'x' if normal => Action::Operate(Operator::Delete, Motion::Right, count.unwrap_or(1)),
+            'D' if normal => Action::Operate(Operator::Delete, Motion::LineEnd, count.unwrap_or(1)),
+            'C' if normal => Action::Operate(Operator::Change, Motion::LineEnd, count.unwrap_or(1)),
+            'o' | 'O' if normal => Action::OpenLine { below: c == 'o' },
+            'J' if normal => Action::JoinLines(count.unwrap_or(2)),
+            'p' | 'P' if normal => Action::Paste {
+                after: c == 'p',
+                count: count.unwrap_or(1),
+            },
+            'i' | 'a' if visual => {
+                self.pending = Some(c);
+                return None;
+            }
+            'i' => Action::InsertMode,
+            'v' => Action::ToggleVisual,
+            'u' => Action::Undo,
+            ':' => Action::CommandLine,
+            '/' => Action::Search,
+            'n' => Action::SearchNext { reverse: false },
+            'N' => Action::SearchNext { reverse: true },
+            '*' => Action::SearchWord(SearchDirection::Forward),
+            '#' => Action::SearchWord(SearchDirection::Backward),
+            'g' => {
+                self.pending = Some(c);
+                self.count = count;
+                return None;
+            }
+            'G' => Action::GotoLine(count.unwrap_or(self.document.len())),
+            'q' if self.recording.is_some() => Action::StopRecording,
+            'q' if self.read_only && normal => Action::Quit,
+            ' ' if self.read_only && normal => Action::Move(Motion::PageDown, count.unwrap_or(1)),
+            'b' if self.read_only && normal => Action::Move(Motion::PageUp, count.unwrap_or(1)),
+            'q' => {
+                self.pending = Some(c);
+                return None;
+            }
+            '%' => count.map_or(Action::MatchBracket, Action::GotoPercent),
+            _ => Action::Move(Motion::from_key(c)?, count.unwrap_or(1)),
+        };
+        Some(action)
+    }
+
+    fn key_action(&mut self, key: KeyEvent) -> Option<Action> {
+        let insert = self.terminal_mode == TerminalMode::Insert;
+        if let (KeyModifiers::CONTROL, KeyCode::Char(c)) = (key.modifiers, key.code) {
+            if let Some(action) = control_action(c, insert) {
+                return Some(action);
+            }
+        }
+        let action = match (key.modifiers, key.code) {
+            (_, KeyCode::Tab) if !insert => Action::JumpForward,
+            (_, KeyCode::Char(c)) if !insert => return self.normal_action(c),
+            (_, KeyCode::Char(c)) => Action::InsertChar(c),
+            (_, KeyCode::Enter) if insert => Action::InsertNewline,
+            (_, KeyCode::Tab) if insert => Action::InsertTab,
+            (_, KeyCode::Delete) if insert => Action::DeleteForward,
+            (_, KeyCode::Backspace) if insert => Action::DeleteBackward,
+            (_, KeyCode::Esc) => Action::NormalMode,
+            (_, code) => Action::Move(Motion::from_key_code(code)?, 1),
+        };
+        Some(action)
+    }
+
+    pub fn execute(&mut self, action: Action) {
+        if self.read_only && action.is_edit() {
+            self.report_read_only();
+            return;
+        }
+        match action {
+            Action::Move(motion, count) => self.apply_motion(motion, count),
+            Action::MoveVisualLine(motion) => {
+                if let Some(key) = motion.key_code() {
+                    self.move_visual_line(key);
+                }
+            }
+            Action::Operate(operator, motion, count) => {
+                self.apply_operator(operator, motion, count);
+            }
+            Action::OperateSelection(operator) => self.operate_selection(operator),
+            Action::ShiftSelection(direction) => {
+                let (first, last) = self.selected_rows();
+                self.shift_rows(first, last, direction);
+                self.terminal_mode = TerminalMode::Normal;
+            }
+            Action::ToggleComment(count) => self.toggle_comment(count),
+            Action::OpenLine { below } => self.open_line(below),
+            Action::JoinLines(count) => self.join_lines(count),
+            Action::Paste { after, count } => self.paste(after, count),
+            Action::InsertChar(c) => self.insert_char(c),
+            Action::InsertNewline => {
+                self.document.insert(&self.cursor_position, '\n');
+                self.move_cursor(KeyCode::Right);
+            }
+            Action::InsertTab => self.insert_tab(),
+            Action::DeleteForward => self.document.delete(&self.cursor_position),
+            Action::DeleteBackward => {
+                if self.cursor_position.x > 0 || self.cursor_position.y > 0 {
+                    let (previous, next) = self.chars_around_cursor();
+                    self.move_cursor(KeyCode::Left);
+                    self.document.delete(&self.cursor_position);
+                    if self.config.auto_pairs
+                        && previous
+                            .zip(next)
+                            .is_some_and(|pair| self.document.auto_pairs().contains(&pair))
+                    {
+                        self.document.delete(&self.cursor_position);
+                    }
+                }
+            }
+            Action::InsertMode => self.terminal_mode = TerminalMode::Insert,
+            Action::NormalMode => {
+                self.terminal_mode = TerminalMode::Normal;
+                self.operator = None;
+                self.count = None;
+                self.register = None;
+            }
+            Action::ToggleVisual => self.toggle_visual_mode(),
+            Action::SelectSubword { around } => self.select_subword(around),
+            Action::SelectRegister(register) => self.register = Some(register),
+            Action::Undo => self.undo(),
+            Action::Redo => self.redo(),
+            Action::GotoLine(line) => self.goto_line(line),
+            Action::GotoLinePrompt => self.goto_line_prompt(),
+            Action::GotoPercent(percent) => self.goto_percent(percent),
+            Action::MatchBracket => self.jump_to_bracket(),
+            Action::SetMark(mark) => self.document.set_mark(mark, self.cursor_position),
+            Action::JumpToMark(mark) => self.jump_to_mark(mark),
+            Action::JumpBack => self.jump_back(),
+            Action::JumpForward => self.jump_forward(),
+            Action::RecordMacro(register) => self.recording = Some((register, Vec::new())),
+            Action::StopRecording => {
+                if let Some((register, mut keys)) = self.recording.take() {
+                    keys.pop();
+                    self.macros.insert(register, keys);
+                }
+            }
+            Action::ReplayMacro(register, count) => self.replay_macro(register, count),
+            Action::CommandLine => self.command_line(),
+            Action::Search => self.search(),
+            Action::SearchNext { reverse } => {
+                let direction = match (self.search_direction, reverse) {
+                    (direction, false) => direction,
+                    (SearchDirection::Forward, true) => SearchDirection::Backward,
+                    (SearchDirection::Backward, true) => SearchDirection::Forward,
+                };
+                self.search_next(direction);
+            }
+            Action::SearchWord(direction) => self.search_word(direction),
+            Action::LineJump => self.line_jump(),
+            Action::SymbolJump => self.symbol_jump(),
+            Action::FindFile => self.find_file(),
+            Action::ToggleFileTree => self.toggle_file_tree(),
+            Action::ToggleLineNumbers => self.toggle_line_numbers(),
+            Action::Save => self.save_file(false),
+            Action::Quit => self.quit(),
+        }
+    }
+
+    fn apply_motion(&mut self, motion: Motion, count: usize) {
+        match motion {
+            Motion::WordForward => self.move_word(WordMotion::Forward, count),
+            Motion::WordBackward => self.move_word(WordMotion::Backward, count),
+            Motion::WordEnd => self.move_word(WordMotion::End, count),
+            Motion::LineStart | Motion::LineEnd | Motion::Line => {
+                if let Some((position, _)) = self.motion_target(motion, count) {
+                    self.cursor_position = position;
+                }
+            }
+            _ => {
+                if let Some(key) = motion.key_code() {
+                    for _ in 0..count {
+                        self.move_cursor(key);
+                    }
+                }
+            }
+        }
+    }
+
+    fn motion_target(&self, motion: Motion, count: usize) -> Option<(Position, MotionKind)> {
+        let Position { x, y } = self.cursor_position;
+        let row_len = |y: usize| self.document.row(y).map_or(0, Row::len);
+        let last_row = self.document.len().saturating_sub(1);
+
+        let target = match motion {
+            Motion::Left => (
+                Position {
+                    x: x.saturating_sub(count),
+                    y,
+                },
+                MotionKind::Exclusive,
+            ),
+            Motion::Right => (
+                Position {
+                    x: usize::min(x.saturating_add(count), row_len(y)),
+                    y,
+                },
+                MotionKind::Exclusive,
+            ),
+            Motion::Down => (
+                Position {
+                    x,
+                    y: usize::min(y.saturating_add(count), last_row),
+                },
+                MotionKind::Linewise,
+            ),
+            Motion::Line => (
+                Position {
+                    x,
+                    y: usize::min(y.saturating_add(count.saturating_sub(1)), last_row),
+                },
+                MotionKind::Linewise,
+            ),
+            Motion::Up => (
+                Position {
+                    x,
+                    y: y.saturating_sub(count),
+                },
+                MotionKind::Linewise,
+            ),
+            Motion::LineStart => (Position { x: 0, y }, MotionKind::Exclusive),
+            Motion::LineEnd => {
+                let y = usize::min(y.saturating_add(count.saturating_sub(1)), last_row);
+                (
+                    Position {
+                        x: row_len(y).saturating_sub(1),
+                        y,
+                    },
+                    MotionKind::Inclusive,
+                )
+            }
+            Motion::WordForward | Motion::WordBackward | Motion::WordEnd => {
+                let (word_motion, kind) = match motion {
+                    Motion::WordForward => (WordMotion::Forward, MotionKind::Exclusive),
+                    Motion::WordBackward => (WordMotion::Backward, MotionKind::Exclusive),
+                    _ => (WordMotion::End, MotionKind::Inclusive),
+                };
+                let mut position = self.cursor_position;
+                for _ in 0..count {
+                    match self.word_target(position, word_motion) {
+                        Some(next) => position = next,
+                        None if word_motion == WordMotion::Forward => {
+                            position = Position {
+                                x: row_len(last_row),
+                                y: last_row,
+                            };
+                            break;
+                        }
+                        None => break,
+                    }
+                }
+                (position, kind)
+            }
+            _ => return None,
+        };
+        Some(target)
+    }
+
+    fn apply_operator(&mut self, operator: Operator, motion: Motion, count: usize) {
+        let from = self.cursor_position;
+        let on_word = self.document.row(from.y).is_some_and(|row| {
+            row.words(self.config.subword_motions)
+                .any(|(start, end)| (start..end).contains(&from.x))
+        });
+        let motion = if operator == Operator::Change && motion == Motion::WordForward && on_word {
+            Motion::WordEnd
+        } else {
+            motion
+        };
+        let Some((mut target, kind)) = self.motion_target(motion, count) else {
+            return;
+        };
+        if motion == Motion::WordForward && target.y > from.y {
+            let y = target.y.saturating_sub(1);
+            target = Position {
+                x: self.document.row(y).map_or(0, Row::len),
+                y,
+            };
+        }
+
+        let (start, end) = if (target.y, target.x) < (from.y, from.x) {
+            (target, from)
+        } else {
+            (from, target)
+        };
+        if let Operator::Shift(direction) = operator {
+            self.shift_rows(start.y, end.y, direction);
+            return;
+        }
+        if kind == MotionKind::Linewise {
+            self.apply_linewise_operator(operator, start.y, end.y);
+            return;
+        }
+
+        let end = if kind == MotionKind::Inclusive {
+            Position {
+                x: end.x.saturating_add(1),
+                y: end.y,
+            }
+        } else {
+            end
+        };
+        self.operate_range(operator, start, end);
+    }
+
+    fn operate_selection(&mut self, operator: Operator) {
+        let Some((start, end)) = self.selection() else {
+            return;
+        };
+        self.terminal_mode = TerminalMode::Normal;
+        let end = Position {
+            x: end.x.saturating_add(1),
+            y: end.y,
+        };
+        self.operate_range(operator, start, end);
+    }
+
+    fn operate_range(&mut self, operator: Operator, start: Position, end: Position) {
+        let text = if operator == Operator::Yank {
+            self.document.yank_range(&start, &end)
+        } else {
+            self.document.delete_range(&start, &end)
+        };
+        self.store_register(operator, Register::new(text, false));
+        self.cursor_position = start;
+        match operator {
+            Operator::Change => self.terminal_mode = TerminalMode::Insert,
+            Operator::Delete => {
+                let len = self.document.row(start.y).map_or(0, Row::len);
+                if start.x >= len {
+                    self.cursor_position.x = len.saturating_sub(1);
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn paste(&mut self, after: bool, count: usize) {
+        let name = self.register.take();
+        self.registers.sync();
+        let Some(register) = self.registers.get(name) else {
+            self.status_message =
+                StatusMessage::from(format!("Register \"{} is empty", name.unwrap_or('"')));
+            return;
+        };
+        let text = register.text.repeat(count);
+        let Position { x, y } = self.cursor_position;
+
+        if register.linewise {
+            if after && y < self.document.len() {
+                let end = Position {
+                    x: self.document.row(y).map_or(0, Row::len),
+                    y,
+                };
+                let text = format!("\n{}", text.strip_suffix('\n').unwrap_or(&text));
+                self.document.insert_str(&end, &text);
+                self.cursor_position = Position { x: 0, y: y + 1 };
+            } else {
+                self.document.insert_str(&Position { x: 0, y }, &text);
+                self.cursor_position = Position { x: 0, y };
+            }
+            return;
+        }
+
+        let len = self.document.row(y).map_or(0, Row::len);
+        let at = Position {
+            x: if after {
+                usize::min(x.saturating_add(1), len)
+            } else {
+                x
+            },
+            y,
+        };
+        let end = self.document.insert_str(&at, &text);
+        self.cursor_position = Position {
+            x: end.x.saturating_sub(1),
+            y: end.y,
+        };
+    }
+
+    fn paste_text(&mut self, text: &str) {
+        if self.read_only {
+            self.report_read_only();
+            return;
+        }
+        let text = text.replace("\r\n", "\n");
+        let binary = is_binary(&text);
+        if !binary && text.len() <= self.config.paste_limit {
+            self.insert_pasted(&text);
+            return;
+        }
+
+        let question = format!(
+            "Paste is {}{}:",
+            format_size(text.len()),
+            if binary { " of binary data" } else { "" }
+        );
+        let dialog = Dialog::new(&question, &["truncate", "write to file", "cancel"], 2);
+        match self.ask(&dialog) {
+            Some('t') => {
+                let mut end = usize::min(PASTE_PREVIEW_BYTES, text.len());
+                while !text.is_char_boundary(end) {
+                    end -= 1;
+                }
+                let preview: String = text[..end]
+                    .chars()
+                    .map(|c| {
+                        if c.is_control() && c != '\n' && c != '\t' {
+                            char::REPLACEMENT_CHARACTER
+                        } else {
+                            c
+                        }
+                    })
+                    .collect();
+                self.insert_pasted(&preview);
+                self.status_message = StatusMessage::from(format!(
+                    "Inserted {} of {}",
+                    format_size(end),
+                    format_size(text.len())
+                ));
+            }
+            Some('w') => {
+                let expected = Expected::Path { must_exist: false };
+                let Some(filename) = self.prompt_for("Write paste to: ", &expected, None) else {
+                    return;
+                };
+                self.status_message = match fs::write(&filename, &text) {
+                    Ok(()) => StatusMessage::from(format!(
+                        "Wrote {} to {}",
+                        format_size(text.len()),
+                        filename
+                    )),
+                    Err(err) => {
+                        StatusMessage::from(format!("Could not write {}: {}", filename, err))
+                    }
+                };
+            }
+            _ => self.status_message = StatusMessage::from(String::from("Paste cancelled")),
+        }
+    }
+
+    fn insert_pasted(&mut self, text: &str) {
+        self.cursor_position = self.document.insert_str(&self.cursor_position, text);
+    }
+
+    fn store_register(&mut self, operator: Operator, value: Register) {
+        let register = self.register.take();
+        if operator == Operator::Yank {
+            self.registers.yank(register, value);
+        } else {
+            self.registers.delete(register, value);
+        }
+    }
+
+    fn flush_registers(&mut self) -> bool {
+        let Err(err) = self.registers.flush() else {
+            return false;
+        };
+        self.status_message = StatusMessage::from(format!("Could not share registers: {}", err));
+        true
+    }
+
+    fn list_registers(&mut self) {
+        self.registers.sync();
+        let registers: Vec<String> = self
+            .registers
+            .list()
+            .into_iter()
+            .map(|(name, register)| {
+                let text: String = register.text.replace('\n', "^J").chars().take(20).collect();
+                format!("\"{} {}", name, text)
+            })
+            .collect();
+        self.status_message = StatusMessage::from(if registers.is_empty() {
+            String::from("No registers set")
+        } else {
+            registers.join(" | ")
+        });
+    }
+
+    fn open_line(&mut self, below: bool) {
+        let y = if below && !self.document.is_empty() {
+            self.cursor_position.y.saturating_add(1)
+        } else {
+            self.cursor_position.y
+        };
+        self.document.insert_row(y, "");
+        self.cursor_position = Position { x: 0, y };
+        self.terminal_mode = TerminalMode::Insert;
+    }
+
+    fn shift_rows(&mut self, first: usize, last: usize, direction: ShiftDirection) {
+        self.document.shift_rows(
+            first..=last,
+            direction,
+            self.config.tab_width,
+            self.config.expandtab,
+        );
+        let indent = self.document.row(first).map_or(0, |row| {
+            row.as_str().len() - row.as_str().trim_start().len()
+        });
+        self.cursor_position = Position {
+            x: indent,
+            y: first,
+        };
+    }
+
+    fn toggle_comment(&mut self, count: usize) {
+        let (first, last) = if self.terminal_mode == TerminalMode::Visual {
+            let rows = self.selected_rows();
+            self.terminal_mode = TerminalMode::Normal;
+            rows
+        } else {
+            let y = self.cursor_position.y;
+            (y, y.saturating_add(count.saturating_sub(1)))
+        };
+        let toggled = self.preserving_view(|document| document.toggle_comment(first..=last));
+        if toggled.is_none() {
+            self.status_message =
+                StatusMessage::from(format!("No line comment for {}", self.document.file_type()));
+        }
+    }
+
+    fn join_lines(&mut self, count: usize) {
+        let y = self.cursor_position.y;
+        self.document.begin_undo();
+        for _ in 0..usize::max(count.saturating_sub(1), 1) {
+            match self.document.join_rows(y) {
+                Some(position) => self.cursor_position = position,
+                None => break,
+            }
+        }
+        self.document.end_undo();
+    }
+
+    fn apply_linewise_operator(&mut self, operator: Operator, first: usize, last: usize) {
+        let line_start = Position { x: 0, y: first };
+        let line_end = Position {
+            x: self.document.row(last).map_or(0, Row::len),
+            y: last,
+        };
+        let mut text = self.document.yank_range(&line_start, &line_end);
+        text.push('\n');
+        self.store_register(operator, Register::new(text, true));
+
+        match operator {
+            Operator::Yank => {
+                self.cursor_position = Position {
+                    x: usize::min(
+                        self.cursor_position.x,
+                        self.document.row(first).map_or(0, Row::len),
+                    ),
+                    y: first,
+                };
+            }
+            Operator::Change => {
+                self.document.delete_range(&line_start, &line_end);
+                self.cursor_position = line_start;
+                self.terminal_mode = TerminalMode::Insert;
+            }
+            _ => {
+                self.document.delete_rows(first, last);
+                self.cursor_position = Position {
+                    x: 0,
+                    y: usize::min(first, self.document.len().saturating_sub(1)),
+                };
+            }
+        }
+    }
+
+    fn move_word(&mut self, motion: WordMotion, count: usize) {
+        for _ in 0..count {
+            match self.word_target(self.cursor_position, motion) {
+                Some(position) => self.cursor_position = position,
+                None => break,
+            }
+        }
+    }
+
+    fn word_target(&self, from: Position, motion: WordMotion) -> Option<Position> {
+        let subword = self.config.subword_motions;
+        let words = |y: usize| {
+            self.document
+                .row(y)
+                .map_or_else(Vec::new, |row| row.words(subword).collect::<Vec<_>>())
+        };
+        let is_empty = |y: usize| self.document.row(y).is_some_and(Row::is_empty);
+        let Position { x, y } = from;
+
+        match motion {
+            WordMotion::Forward => {
+                if let Some(&(start, _)) = words(y).iter().find(|&&(start, _)| start > x) {
+                    return Some(Position { x: start, y });
+                }
+                (y.saturating_add(1)..self.document.len()).find_map(|y| {
+                    if is_empty(y) {
+                        return Some(Position { x: 0, y });
+                    }
+                    words(y).first().map(|&(x, _)| Position { x, y })
+                })
+            }
+            WordMotion::Backward => {
+                if let Some(&(start, _)) = words(y).iter().rev().find(|&&(start, _)| start < x) {
+                    return Some(Position { x: start, y });
+                }
+                (0..y).rev().find_map(|y| {
+                    if is_empty(y) {
+                        return Some(Position { x: 0, y });
+                    }
+                    words(y).last().map(|&(x, _)| Position { x, y })
+                })
+            }
+            WordMotion::End => {
+                if let Some(&(_, end)) =
+                    words(y).iter().find(|&&(_, end)| end.saturating_sub(1) > x)
+                {
+                    return Some(Position {
+                        x: end.saturating_sub(1),
+                        y,
+                    });
+                }
+                (y.saturating_add(1)..self.document.len()).find_map(|y| {
+                    words(y).first().map(|&(_, end)| Position {
+                        x: end.saturating_sub(1),
+                        y,
+                    })
+                })
+            }
+        }
+    }
+
+    fn select_subword(&mut self, around: bool) {
+        let Position { x, y } = self.cursor_position;
+        if let Some((start, end)) = self
+            .document
+            .row(y)
+            .and_then(|row| row.subword_bounds(x, around))
+        {
+            self.selection_anchor = Position { x: start, y };
+            self.cursor_position = Position {
+                x: end.saturating_sub(1),
+                y,
+            };
+        }
+    }
+
+    fn replay_macro(&mut self, register: char, count: usize) {
+        let register = if register == '@' {
+            match self.last_macro {
+                Some(register) => register,
+                None => return,
+            }
+        } else {
+            register
+        };
+        let Some(keys) = self.macros.get(&register) else {
+            self.status_message = StatusMessage::from(format!("Macro @{} is empty", register));
+            return;
+        };
+
+        self.last_macro = Some(register);
+        if !self.replaying && !keys.is_empty() {
+            self.replaying = true;
+            self.document.begin_undo();
+        }
+        for _ in 0..count {
+            for key in keys {
+                self.pending_input.push_back(Event::Key(*key));
+            }
+        }
+    }
+
+    fn edit_macro(&mut self, register: &str) {
+        let mut chars = register.chars();
+        let register = match (chars.next(), chars.next()) {
+            (Some(register), None) if register.is_ascii_alphanumeric() => register,
+            _ => {
+                self.status_message = StatusMessage::from(String::from("Usage: :macro <register>"));
+                return;
+            }
+        };
+
+        let notation = macros::to_notation(self.macros.get(&register).map_or(&[], Vec::as_slice));
+        let prompt = format!("Macro @{}: ", register);
+        let Some(edited) = self
+            .prompt_with_default(&prompt, &notation, |_, _, _| {})
+            .unwrap_or(None)
+            .filter(|edited| !edited.is_empty())
+        else {
+            self.status_message = StatusMessage::from(String::from("Aborted macro edit"));
+            return;
+        };
+
+        match macros::from_notation(&edited) {
+            Ok(keys) => {
+                self.macros.insert(register, keys);
+                self.status_message = StatusMessage::from(format!("Updated macro @{}", register));
+            }
+            Err(err) => self.status_message = StatusMessage::from(err),
+        }
+    }
+
+    fn command_line(&mut self) {
+        let path_commands: Vec<String> = self
+            .config
+            .commands
+            .iter()
+            .filter(|(_, command)| command.completes_paths())
+            .map(|(name, _)| name.clone())
+            .collect();
+        self.prompt_completion = Some(Box::new(move |input| {
+            completion::command_paths(input, &path_commands)
+        }));
+        if let Some(command) = self
+            .prompt_with_history(HistoryKind::Command, ":", |editor, key, input| {
+                if !matches!(key.code, KeyCode::Tab | KeyCode::BackTab) {
+                    editor.prompt_hint = editor.command_hint(input);
+                }
+            })
+            .unwrap_or(None)
+            .filter(|command| !command.trim().is_empty())
+        {
+            match self.expand_tokens(command.trim()) {
+                Ok(command) => self.execute_command(&command),
+                Err(err) => self.status_message = StatusMessage::from(err),
+            }
+        }
+    }
+
+    fn command_hint(&self, input: &str) -> String {
+        let name = input.split_whitespace().next().unwrap_or_default();
+        self.config
+            .commands
+            .get(name)
+            .and_then(UserCommand::hint)
+            .map_or_else(String::new, |hint| format!("  {}", hint))
+    }
+
+    fn run_user_command(&mut self, name: &str, args: &str) -> bool {
+        let Some(command) = self.config.commands.get(name).cloned() else {
+            return false;
+        };
+        if self.command_depth >= commands::MAX_DEPTH {
+            self.status_message =
+                StatusMessage::from(format!("Commands nested too deeply at :{}", name));
+            return true;
+        }
+        self.command_depth = self.command_depth.saturating_add(1);
+        for step in command.steps() {
+            match commands::substitute(step, args).and_then(|step| self.expand_tokens(&step)) {
+                Ok(step) => self.execute_command(step.trim().trim_start_matches(':')),
+                Err(err) => {
+                    self.status_message = StatusMessage::from(format!(":{}: {}", name, err));
+                    break;
+                }
+            }
+        }
+        self.command_depth = self.command_depth.saturating_sub(1);
+        true
+    }
+
+    fn normal(&mut self, notation: &str) {
+        let keys = match macros::from_notation(notation) {
+            Ok(keys) => keys,
+            Err(err) => {
+                self.status_message = StatusMessage::from(err);
+                return;
+            }
+        };
+        let queued = self.pending_input.len();
+        for key in keys.into_iter().rev() {
+            self.pending_input.push_front(Event::Key(key));
+        }
+        while self.pending_input.len() > queued && !self.should_quit {
+            if let Err(err) = self.process_keypress() {
+                self.status_message = StatusMessage::from(err.to_string());
+                break;
+            }
+        }
+    }
+
+    fn substitute_confirmed(&mut self, substitution: &ex::Substitution) {
+        let pattern = substitution.pattern.as_str();
+        let replacement = substitution.replacement.as_str();
+        let question = format!("Replace with {}?", replacement);
+        let dialog = Dialog::new(&question, &["yes", "no", "all", "cancel"], 0);
+        let last = usize::min(substitution.last, self.document.len().saturating_sub(1));
+        let mut count = 0;
+        let mut found = false;
+        let mut replace_all = false;
+        self.document.begin_undo();
+        'rows: for y in substitution.first..=last {
+            let mut from = 0;
+            while let Some(row) = self.document.row(y) {
+                let line = row.as_str().to_string();
+                let Some(start) = line[from..].find(pattern).map(|offset| from + offset) else {
+                    break;
+                };
+                found = true;
+                if !replace_all {
+                    self.cursor_position = Position {
+                        x: row.grapheme_index(start),
+                        y,
+                    };
+                    self.scroll();
+                    match self.ask(&dialog) {
+                        Some('y') => (),
+                        Some('a') => replace_all = true,
+                        Some('n') if substitution.global => {
+                            from = start + pattern.len();
+                            continue;
+                        }
+                        Some('n') => break,
+                        _ => break 'rows,
+                    }
+                }
+                let mut replaced = line;
+                replaced.replace_range(start..start + pattern.len(), replacement);
+                self.document.replace_row(y, &replaced);
+                count += 1;
+                from = start + replacement.len();
+                if !substitution.global {
+                    break;
+                }
+            }
+        }
+        self.document.end_undo();
+        self.status_message = StatusMessage::from(if found {
+            format!("{} substitution(s)", count)
+        } else {
+            format!("Pattern not found: {}", pattern)
+        });
+    }
+
+    fn run_shell(&mut self, command: &str) {
+        if command.is_empty() {
+            self.status_message = StatusMessage::from(String::from("Usage: :!<command>"));
+            return;
+        }
+        self.status_message = StatusMessage::from(match transform::pipe_through(command, "") {
+            Ok(output) if output.trim().is_empty() => format!("`{}` finished", command),
+            Ok(output) => output.trim_end().lines().collect::<Vec<_>>().join(" | "),
+            Err(err) => err.to_string(),
+        });
+    }
+
+    fn expansion(&self, token: &str) -> Option<String> {
+        match token {
+            "%" => self.document.filename.clone(),
+            "cword" => self.word_under_cursor().map(|(_, word)| word),
+            "sel" => self.selection().map(|(start, end)| {
+                let end = Position {
+                    x: end.x.saturating_add(1),
+                    y: end.y,
+                };
+                self.document.text_between(&start, &end)
+            }),
+            "search" => self.last_search.clone(),
+            _ => None,
+        }
+    }
+
+    fn expand_tokens(&self, command: &str) -> Result<String, String> {
+        let value = |token: &str| {
+            self.expansion(token)
+                .ok_or_else(|| format!("Nothing to expand for {}", token))
+        };
+        let mut words = Vec::new();
+        for (i, word) in command.split(' ').enumerate() {
+            words.push(if i > 0 && word == "%" {
+                value("%")?
+            } else {
+                word.to_string()
+            });
+        }
+        let mut rest = words.join(" ");
+        let mut expanded = String::new();
+        while let Some(start) = rest.find('<') {
+            expanded.push_str(&rest[..start]);
+            rest.drain(..start);
+            let token = rest[1..].split_once('>').map(|(token, _)| token);
+            match token.filter(|token| EXPANSION_TOKENS.contains(token)) {
+                Some(token) => {
+                    expanded.push_str(&value(token)?);
+                    rest.drain(..token.len() + 2);
+                }
+                None => {
+                    expanded.push('<');
+                    rest.remove(0);
+                }
+            }
+        }
+        expanded.push_str(&rest);
+        Ok(expanded)
+    }
+
+    fn prompt_insertion(&mut self, key: KeyEvent) -> Option<String> {
+        let text = match (key.modifiers, key.code) {
+            (KeyModifiers::CONTROL, KeyCode::Char('w')) => self.expansion("cword"),
+            (KeyModifiers::CONTROL, KeyCode::Char('s')) => self.expansion("sel"),
+            (_, KeyCode::Char('%')) => self.expansion("%"),
+            (_, KeyCode::Char('/')) => self.expansion("search"),
+            (_, KeyCode::Char(name)) if Registers::is_valid(name) => {
+                self.registers.sync();
+                self.registers
+                    .get(Some(name))
+                    .map(|register| register.text.clone())
+            }
+            _ => None,
+        }?;
+        Some(text.trim_end_matches('\n').replace('\n', " "))
+    }
+
+    fn execute_command(&mut self, command: &str) {
+        let (name, args) = command
+            .split_once(char::is_whitespace)
+            .unwrap_or((command, ""));
+        let args = args.trim();
+
+        if let Ok(line) = command.parse::<usize>() {
+            self.goto_line(line);
+            return;
+        }
+        if let Some(shell) = command.strip_prefix('!') {
+            self.run_shell(shell.trim());
+            return;
+        }
+
+        if self.read_only
+            && !READ_ONLY_COMMANDS.contains(&name)
+            && !self.config.commands.contains_key(name)
+        {
+            self.report_read_only();
+            return;
+        }
+
+        let line = self.cursor_position.y;
+        if let Some(Ok(substitution)) = ex::parse_substitution(command, line, self.document.len()) {
+            if substitution.confirm {
+                self.substitute_confirmed(&substitution);
+                return;
+            }
+        }
+        if let Some(result) = self.preserving_view(|document| ex::execute(document, command, line))
+        {
+            let message = result.unwrap_or_else(|err| err);
+            self.status_message = StatusMessage::from(message);
+            return;
+        }
+
+        match name {
+            "goto" => {
+                if let Ok(byte) = args.parse::<usize>() {
+                    self.jump_to(self.document.position_of_byte(byte));
+                } else {
+                    self.status_message =
+                        StatusMessage::from(String::from("Usage: :goto <byte offset>"));
+                }
+            }
+            "view" => self.view(),
+            "normal" | "norm" => self.normal(args),
+            "w" | "write" => self.save_file(false),
+            "w!" | "write!" => self.save_file(true),
+            "e" | "edit" => self.edit_file(args, false),
+            "e!" | "edit!" => self.edit_file(args, true),
+            "n" | "next" => self.edit_arg(true, false),
+            "n!" | "next!" => self.edit_arg(true, true),
+            "N" | "prev" | "previous" => self.edit_arg(false, false),
+            "N!" | "prev!" | "previous!" => self.edit_arg(false, true),
+            "args" => self.show_args(),
+            "undo-info" => self.show_undo_info(),
+            "template" => self.insert_template(args),
+            "license-header" => self.license_header(args),
+            "timings" => {
+                self.timings = match self.timings {
+                    Some(_) => None,
+                    None => Some(Timings::default()),
+                };
+                self.terminal.invalidate();
+            }
+            "eol" => self.set_line_ending(args),
+            "set" => self.set_option(args),
+            "noh" | "nohlsearch" => {
+                self.set_search_query(None).ok();
+            }
+            "lines" => self.line_jump(),
+            "symbols" => self.symbol_jump(),
+            "macro" => self.edit_macro(args),
+            "delete-file" => self.delete_file(),
+            "rename" => self.rename_file(args, false),
+            "rename!" => self.rename_file(args, true),
+            "update-refs" if self.read_only => self.report_read_only(),
+            "update-refs" => match args.split_once(' ') {
+                Some((old, new)) => {
+                    if !self.update_references(old.trim(), new.trim()) {
+                        self.status_message =
+                            StatusMessage::from(format!("No references to {}", old.trim()));
+                    }
+                }
+                None => {
+                    self.status_message =
+                        StatusMessage::from(String::from("Usage: :update-refs <old> <new>"));
+                }
+            },
+            "restore-file" => self.restore_file(args),
+            "checkhealth" => self.check_health(),
+            "paths" => self.show_paths(),
+            "capabilities" => {
+                self.status_message = StatusMessage::from(format!(
+                    "capabilities: {}",
+                    self.terminal.capabilities().summary()
+                ));
+            }
+            "messages" | "mes" => self.show_messages(),
+            "share" => self.share_selection(),
+            "selection-stats" => self.copy_selection_stats(),
+            "ansi" => self.convert_escapes(args),
+            "tree" => self.toggle_file_tree(),
+            "map" => self.map_key(args),
+            "unmap" => self.unmap_key(args),
+            "align-table" => self.align_table(),
+            "theme" => self.set_theme(args),
+            "diff" => self.diff_with_saved(args),
+            "revert" => self.revert(),
+            "registers" | "reg" => self.list_registers(),
+            "grep" => self.grep_project(args),
+            "bgrep" => self.find_all(args),
+            "copen" | "cw" => self.quickfix_pane(),
+            "cfilter" => self.filter_quickfix(args, false),
+            "cfilter!" => self.filter_quickfix(args, true),
+            "dups" => self.report_duplicates(),
+            "dedup" => self.delete_duplicates(),
+            "hash" => self.show_hash(args),
+            "transform" => self.transform_selection(args),
+            "json-format" => self.transform_json(false),
+            "json-minify" => self.transform_json(true),
+            "note" => self.annotate(args),
+            "unnote" => self.annotate(""),
+            "nnext" => self.jump_to_annotation(SearchDirection::Forward),
+            "nprev" => self.jump_to_annotation(SearchDirection::Backward),
+            "review-export" => self.export_review(args),
+            "cnext" | "cn" => {
+                let entry = self.quickfix.select_next().is_some();
+                self.jump_to_quickfix(entry);
+            }
+            "cprev" | "cp" => {
+                let entry = self.quickfix.select_previous().is_some();
+                self.jump_to_quickfix(entry);
+            }
+            _ => {
+                if !self.run_user_command(name, args) {
+                    self.status_message = StatusMessage::from(format!("Unknown command: {}", name));
+                }
+            }
+        }
+    }
+
+    fn set_line_ending(&mut self, ending: &str) {
+        match ending.to_ascii_lowercase().as_str() {
+            "" => (),
+            "lf" | "unix" => self.document.set_crlf(false),
+            "crlf" | "dos" => self.document.set_crlf(true),
+            _ => {
+                self.status_message = StatusMessage::from(String::from("Usage: :eol [lf|crlf]"));
+                return;
+            }
+        }
+        self.status_message = StatusMessage::from(format!(
+            "Line endings: {}",
+            if self.document.is_crlf() {
+                "CRLF"
+            } else {
+                "LF"
+            }
+        ));
+    }
+
+    fn set_option(&mut self, option: &str) {
+        self.search_matches = None;
+        match option {
+            "wrap" => {
+                self.config.wrap = true;
+                self.window.offset.x = 0;
+                self.window.top_segment = 0;
+            }
+            "nowrap" => {
+                self.config.wrap = false;
+                self.window.top_segment = 0;
+            }
+            "readonly" => {
+                self.lock = None;
+                self.read_only = true;
+            }
+            "noreadonly" => self.acquire_lock(true),
+            "scrollbar" => self.config.scrollbar = true,
+            "noscrollbar" => self.config.scrollbar = false,
+            "subword" => self.config.subword_motions = true,
+            "nosubword" => self.config.subword_motions = false,
+            "ignorecase" => self.config.ignore_case = true,
+            "noignorecase" => self.config.ignore_case = false,
+            "wrapscan" => self.config.wrap_scan = true,
+            "nowrapscan" => self.config.wrap_scan = false,
+            "smartcase" => self.config.smart_case = true,
+            "nosmartcase" => self.config.smart_case = false,
+            "trash" => self.config.trash = true,
+            "notrash" => self.config.trash = false,
+            "autopairs" => self.config.auto_pairs = true,
+            "noautopairs" => self.config.auto_pairs = false,
+            "expandtab" => self.config.expandtab = true,
+            "noexpandtab" => self.config.expandtab = false,
+            _ if option.starts_with("search=") => {
+                let name = &option["search=".len()..];
+                if self.search_providers.contains(name) {
+                    self.config.search_provider = name.to_string();
+                    let query = self.search_query.take();
+                    self.set_search_query(query).ok();
+                } else {
+                    let names: Vec<&str> = self.search_providers.names().collect();
+                    self.status_message = StatusMessage::from(format!(
+                        "Unknown search provider: {} (available: {})",
+                        name,
+                        names.join(", ")
+                    ));
+                }
+            }
+            "tab_width" => {
+                if let Some(width) = self.prompt_number("Tab width: ", 1, usize::MAX) {
+                    self.config.tab_width = width;
+                }
+            }
+            _ if option.starts_with("tab_width=") => {
+                match input::number(&option["tab_width=".len()..], 1, usize::MAX) {
+                    Ok(width) => self.config.tab_width = width,
+                    Err(err) => {
+                        self.status_message = StatusMessage::from(format!("tab_width: {}", err));
+                    }
+                }
+            }
+            _ => {
+                self.status_message = StatusMessage::from(format!("Unknown option: {}", option));
+            }
+        }
+    }
+
+    fn selected_rows(&self) -> (usize, usize) {
+        match self.selection() {
+            Some((start, end)) => (start.y, end.y),
+            None => (0, self.document.len().saturating_sub(1)),
+        }
+    }
+
+    fn duplicate_rows(&self) -> Vec<usize> {
+        let (start, end) = self.selected_rows();
+        let mut seen = HashSet::new();
+        (start..=end)
+            .filter(|&y| {
+                self.document
+                    .row(y)
+                    .is_some_and(|row| !seen.insert(row.as_str()))
+            })
+            .collect()
+    }
+
+    fn find_all(&mut self, query: &str) {
+        if query.is_empty() {
+            self.status_message = StatusMessage::from(String::from("Usage: :bgrep <text>"));
+            return;
+        }
+        let matcher = match self.matcher(query) {
+            Ok(matcher) => matcher,
+            Err(err) => {
+                self.status_message = StatusMessage::from(err);
+                return;
+            }
+        };
+        let filename = &self.document.filename;
+        let entries: Vec<quickfix::Entry> = self
+            .document
+            .rows()
+            .enumerate()
+            .flat_map(|(y, row)| {
+                row.matches(matcher.as_ref())
+                    .into_iter()
+                    .map(move |(x, _)| quickfix::Entry {
+                        filename: filename.clone(),
+                        position: Position { x, y },
+                        message: format!("{}: {}", y.saturating_add(1), row.as_str().trim()),
+                    })
+            })
+            .collect();
+        let found = !entries.is_empty();
+        self.quickfix.set(entries);
+        self.jump_to_quickfix(found);
+    }
+
+    fn grep_project(&mut self, query: &str) {
+        if query.is_empty() {
+            self.status_message = StatusMessage::from(String::from("Usage: :grep <text>"));
+            return;
+        }
+        let matcher = match self.matcher(query) {
+            Ok(matcher) => matcher,
+            Err(err) => {
+                self.status_message = StatusMessage::from(err);
+                return;
+            }
+        };
+        let cwd = env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
+        let root = finder::project_root(&cwd);
+        let open = self
+            .document
+            .filename
+            .as_deref()
+            .and_then(|filename| fs::canonicalize(filename).ok());
+        let mut entries = Vec::new();
+        for file in finder::files(&root) {
+            let path = root.join(&file);
+            let filename = path
+                .strip_prefix(&cwd)
+                .unwrap_or(&path)
+                .to_string_lossy()
+                .into_owned();
+            let text = if open.is_some() && fs::canonicalize(&path).ok() == open {
+                self.document.contents()
+            } else {
+                match fs::read(&path) {
+                    Ok(bytes) if !bytes.contains(&0) => {
+                        String::from_utf8_lossy(&bytes).into_owned()
+                    }
+                    _ => continue,
+                }
+            };
+            for (y, line) in text.lines().enumerate() {
+                let row = Row::from(line);
+                entries.extend(row.matches(matcher.as_ref()).into_iter().map(|(x, _)| {
+                    quickfix::Entry {
+                        filename: Some(filename.clone()),
+                        position: Position { x, y },
+                        message: format!("{}: {}", y.saturating_add(1), line.trim()),
+                    }
+                }));
+            }
+            if entries.len() >= GREP_RESULTS {
+                entries.truncate(GREP_RESULTS);
+                break;
+            }
+        }
+        if entries.is_empty() {
+            self.quickfix.clear();
+            self.status_message = StatusMessage::from(format!("No matches for {}", query));
+            return;
+        }
+        self.quickfix.set(entries);
+        self.quickfix_pane();
+    }
+
+    fn quickfix_pane(&mut self) {
+        if self.quickfix.is_empty() {
+            self.status_message = StatusMessage::from(String::from("Quickfix list is empty"));
+            return;
+        }
+        let labels: Vec<String> = self
+            .quickfix
+            .entries()
+            .iter()
+            .map(quickfix::Entry::label)
+            .collect();
+        let mut overlay = Overlay::new("");
+        overlay.height = Some(self.terminal.size().height as usize / 2);
+        overlay.selected = self.quickfix.index();
+        let chosen = self.pick(
+            "Results (ESC = cancel, Up | Down = select): ",
+            overlay,
+            |editor, query| editor.filter_quickfix_pane(&labels, query),
+        );
+
+        if let Some(Some(index)) = chosen {
+            let found = self.quickfix.select(index).is_some();
+            self.jump_to_quickfix(found);
+        } else {
+            self.status_message = StatusMessage::from(format!(
+                "{} result(s) (:copen to browse, :cn | :cp to navigate)",
+                self.quickfix.len()
+            ));
+        }
+    }
+
+    fn filter_quickfix_pane(&mut self, labels: &[String], query: &str) -> Option<usize> {
+        let overlay = self.overlay.as_mut()?;
+        let matches = fuzzy::rank(query, labels.iter().map(String::as_str));
+        overlay.title = format!("Results {}/{}", matches.len(), labels.len());
+        overlay.items = matches[overlay.window(matches.len(), OVERLAY_ITEMS)]
+            .iter()
+            .map(|&(index, _)| labels[index].clone())
+            .collect();
+        matches.get(overlay.selected).map(|&(index, _)| index)
+    }
+
+    fn filter_quickfix(&mut self, pattern: &str, invert: bool) {
+        if self.quickfix.is_empty() {
+            self.status_message = StatusMessage::from(String::from("Quickfix list is empty"));
+            return;
+        }
+        let before = self.quickfix.len();
+        self.quickfix
+            .retain(|entry| entry.message.contains(pattern) != invert);
+        self.status_message = StatusMessage::from(format!(
+            "{} of {} result(s) kept",
+            self.quickfix.len(),
+            before
+        ));
+    }
+
+    fn report_duplicates(&mut self) {
+        let duplicates = self.duplicate_rows();
+        let entries = duplicates
+            .iter()
+            .map(|&y| quickfix::Entry {
+                filename: self.document.filename.clone(),
+                position: Position { x: 0, y },
+                message: format!(
+                    "duplicate: {}",
+                    self.document.row(y).map_or("", Row::as_str)
+                ),
+            })
+            .collect();
+        self.quickfix.set(entries);
+        self.status_message = StatusMessage::from(if duplicates.is_empty() {
+            String::from("No duplicate lines")
+        } else {
+            format!(
+                "{} duplicate line(s) (:cn | :cp to navigate, :dedup to delete)",
+                duplicates.len()
+            )
+        });
+    }
+
+    fn delete_duplicates(&mut self) {
+        let duplicates = self.duplicate_rows();
+        self.document.begin_undo();
+        self.document.track_edits();
+        for &y in duplicates.iter().rev() {
+            let (start, end) = if y.saturating_add(1) < self.document.len() {
+                (Position { x: 0, y }, Position { x: 0, y: y + 1 })
+            } else {
+                let previous = y.saturating_sub(1);
+                (
+                    Position {
+                        x: self.document.row(previous).map_or(0, Row::len),
+                        y: previous,
+                    },
+                    Position {
+                        x: self.document.row(y).map_or(0, Row::len),
+                        y,
+                    },
+                )
+            };
+            self.document.delete_range(&start, &end);
+        }
+        self.remap_view();
+        self.document.end_undo();
+
+        self.terminal_mode = TerminalMode::Normal;
+        self.status_message =
+            StatusMessage::from(format!("Deleted {} duplicate line(s)", duplicates.len()));
+    }
+
+    fn show_hash(&mut self, algorithm: &str) {
+        let (text, scope) = match self.selection() {
+            Some((start, end)) => {
+                let end = Position {
+                    x: end.x.saturating_add(1),
+                    y: end.y,
+                };
+                (self.document.text_between(&start, &end), "selection")
+            }
+            None => (self.document.contents(), "buffer"),
+        };
+        let (name, digest) = match algorithm {
+            "" | "sha256" => ("sha256", hex(&Sha256::digest(text.as_bytes()))),
+            "md5" => ("md5", hex(&Md5::digest(text.as_bytes()))),
+            _ => {
+                self.status_message =
+                    StatusMessage::from(format!("Unknown hash: {} (md5 | sha256)", algorithm));
+                return;
+            }
+        };
+        self.status_message = StatusMessage::from(format!("{} of {}: {}", name, scope, digest));
+    }
+
+    fn refresh_git_signs(&mut self) -> bool {
+        if !self.config.git_gutter || self.document.filename.is_none() {
+            return false;
+        }
+        self.refresh_git_marks();
+        true
+    }
+
+    fn refresh_git_marks(&mut self) {
+        self.diff_marks.clear();
+        self.diff_base = None;
+        if !self.config.git_gutter {
+            return;
+        }
+        let Some(head) = self
+            .document
+            .filename
+            .as_deref()
+            .and_then(git::head_contents)
+        else {
+            return;
+        };
+        self.diff_base = Some(head.lines().map(String::from).collect());
+        self.update_diff_marks();
+    }
+
+    fn update_diff_marks(&mut self) {
+        let Some(base) = &self.diff_base else {
+            return;
+        };
+        let old: Vec<&str> = base.iter().map(String::as_str).collect();
+        let new: Vec<&str> = self.document.lines().collect();
+        self.diff_marks = diff::marks(&diff::diff(&old, &new));
+        self.diff_revision = self.document.revision();
+    }
+
+    fn diff_marks_tick(&mut self) -> bool {
+        if self.diff_base.is_none() || self.diff_revision == self.document.revision() {
+            return false;
+        }
+        self.update_diff_marks();
+        true
+    }
+
+    fn diff_with_saved(&mut self, args: &str) {
+        let split = match args {
+            "" | "unified" => false,
+            "split" => true,
+            "off" => {
+                self.diff_marks.clear();
+                self.diff_base = None;
+                self.diff_view = None;
+                return;
+            }
+            _ => {
+                self.status_message =
+                    StatusMessage::from(String::from("Usage: :diff [unified|split|off]"));
+                return;
+            }
+        };
+        let Some(filename) = self.document.filename.clone() else {
+            self.status_message = StatusMessage::from(String::from("No file to diff against"));
+            return;
+        };
+        let saved = match fs::read_to_string(&filename) {
+            Ok(saved) => saved,
+            Err(err) => {
+                self.status_message = StatusMessage::from(format!("{}: {}", filename, err));
+                return;
+            }
+        };
+
+        let old: Vec<&str> = saved.lines().collect();
+        let new: Vec<&str> = self.document.lines().collect();
+        let hunks = diff::diff(&old, &new);
+        self.diff_marks = diff::marks(&hunks);
+        self.diff_base = Some(old.iter().map(|line| line.to_string()).collect());
+        self.diff_revision = self.document.revision();
+        if !hunks.is_empty() {
+            let title = format!(
+                "{} saved -> buffer | j/k scroll | n/N hunk | Tab split | q close",
+                filename
+            );
+            self.diff_view = Some(DiffView::new(&title, &old, &new, &hunks, split));
+        }
+
+        let entries = hunks
+            .iter()
+            .map(|hunk| {
+                let mut message = format!(
+                    "-{},{} +{},{}",
+                    hunk.old_start.saturating_add(1),
+                    hunk.removed.len(),
+                    hunk.new_start.saturating_add(1),
+                    hunk.added
+                );
+                if let Some(line) = hunk.removed.first() {
+                    message.push_str(&format!(" was: {}", line));
+                }
+                quickfix::Entry {
+                    filename: Some(filename.clone()),
+                    position: Position {
+                        x: 0,
+                        y: usize::min(hunk.new_start, new.len().saturating_sub(1)),
+                    },
+                    message,
+                }
+            })
+            .collect();
+        self.quickfix.set(entries);
+
+        self.status_message = StatusMessage::from(if hunks.is_empty() {
+            String::from("No unsaved changes")
+        } else {
+            format!(
+                "{} changed region(s) since last save (:cn | :cp to navigate, :diff off to hide)",
+                hunks.len()
+            )
+        });
+    }
+
+    fn revert(&mut self) {
+        let Some(filename) = self.document.filename.clone() else {
+            self.status_message = StatusMessage::from(String::from("No file to revert to"));
+            return;
+        };
+        if !self.document.is_dirty() {
+            self.status_message = StatusMessage::from(String::from("No unsaved changes"));
+            return;
+        }
+        let saved = match fs::read_to_string(&filename) {
+            Ok(saved) => saved.lines().collect::<Vec<_>>().join("\n"),
+            Err(err) => {
+                self.status_message = StatusMessage::from(format!("{}: {}", filename, err));
+                return;
+            }
+        };
+        let y = self.document.len().saturating_sub(1);
+        let end = Position {
+            x: self.document.row(y).map_or(0, Row::len),
+            y,
+        };
+        self.document.begin_undo();
+        self.document
+            .replace_text(&Position::default(), &end, &saved);
+        self.document.end_undo();
+        self.document.mark_saved();
+        self.cursor_position = self.document.clamp(&self.cursor_position);
+        self.terminal_mode = TerminalMode::Normal;
+        self.diff_view = None;
+        self.refresh_git_marks();
+        self.status_message = StatusMessage::from(format!(
+            "Reverted to the saved {}; u brings the changes back",
+            filename
+        ));
+    }
+
+    fn transform_selection(&mut self, name: &str) {
+        let Some((start, end)) = self.selection() else {
+            self.status_message = StatusMessage::from(String::from("No selection to transform"));
+            return;
+        };
+        let name = if name.is_empty() {
+            let Some(name) = self.choose_transform() else {
+                return;
+            };
+            name
+        } else {
+            name.to_string()
+        };
+
+        let end = Position {
+            x: end.x.saturating_add(1),
+            y: end.y,
+        };
+        let text = self.document.text_between(&start, &end);
+        match self.transforms.apply(&name, &text) {
+            Ok(transformed) => {
+                self.document.begin_undo();
+                self.preserving_view(|document| document.replace_text(&start, &end, &transformed));
+                self.document.end_undo();
+                self.terminal_mode = TerminalMode::Normal;
+            }
+            Err(err) => self.status_message = StatusMessage::from(err),
+        }
+    }
+
+    fn choose_transform(&mut self) -> Option<String> {
+        let names: Vec<String> = self.transforms.names().map(str::to_string).collect();
+        let mut selected: usize = 0;
+        let mut choice = None;
+
+        self.prompt(
+            "Transform (ESC = cancel, Up | Down = select): ",
+            |editor, key, query| {
+                match key.code {
+                    KeyCode::Up => selected = selected.saturating_sub(1),
+                    KeyCode::Down => selected = selected.saturating_add(1),
+                    _ => selected = 0,
+                }
+
+                let matches = fuzzy::rank(query, names.iter().map(String::as_str));
+                choice = None;
+                if matches.is_empty() {
+                    editor.prompt_hint = String::from(" [no match]");
+                    return;
+                }
+                selected = usize::min(selected, matches.len().saturating_sub(1));
+                let (index, _) = matches[selected];
+                choice = names.get(index).cloned();
+                editor.prompt_hint =
+                    format!(" [{}/{}] {}", selected + 1, matches.len(), names[index]);
+            },
+        )
+        .unwrap_or(None)?;
+        choice
+    }
+
+    fn transform_json(&mut self, minify: bool) {
+        let (start, end) = match self.selection() {
+            Some((start, end)) => (
+                start,
+                Position {
+                    x: end.x.saturating_add(1),
+                    y: end.y,
+                },
+            ),
+            None => {
+                let y = self.document.len().saturating_sub(1);
+                let x = self.document.row(y).map_or(0, Row::len);
+                (Position::default(), Position { x, y })
+            }
+        };
+        let text = self.document.text_between(&start, &end);
+        let result = if minify {
+            json::minify(&text)
+        } else {
+            json::format(&text, self.config.tab_width)
+        };
+
+        match result {
+            Ok(json) => {
+                if json != text {
+                    self.document.begin_undo();
+                    self.preserving_view(|document| document.replace_text(&start, &end, &json));
+                    self.document.end_undo();
+                }
+                self.quickfix.clear();
+                self.terminal_mode = TerminalMode::Normal;
+            }
+            Err(err) => {
+                let line = err.line.saturating_sub(1);
+                let column = err.column.saturating_sub(1);
+                let position = Position {
+                    x: if line == 0 {
+                        start.x.saturating_add(column)
+                    } else {
+                        column
+                    },
+                    y: start.y.saturating_add(line),
+                };
+                self.quickfix.set(vec![quickfix::Entry {
+                    filename: self.document.filename.clone(),
+                    position,
+                    message: format!(
+                        "JSON error at {}:{}: {}",
+                        position.y.saturating_add(1),
+                        position.x.saturating_add(1),
+                        err.message
+                    ),
+                }]);
+                self.terminal_mode = TerminalMode::Normal;
+                self.jump_to_quickfix(true);
+            }
         }
     }
 
-    pub fn run(&mut self) {
-        Terminal::clear_screen();
-        loop {
-            if let Err(err) = self.refresh_screen() {
-                die(err);
-            }
-            if self.should_quit {
-                break;
-            }
-            if let Err(err) = self.process_keypress() {
-                die(err);
+    fn jump_to_quickfix(&mut self, found: bool) {
+        let Some(entry) = self.quickfix.current().filter(|_| found) else {
+            self.status_message = StatusMessage::from(String::from("Quickfix list is empty"));
+            return;
+        };
+        let position = entry.position;
+        let message = entry.message.clone();
+        if let Some(filename) = entry
+            .filename
+            .clone()
+            .filter(|filename| !self.is_open(filename))
+        {
+            self.edit_file(&filename, false);
+            if !self.is_open(&filename) {
+                return;
             }
         }
+        self.status_message = StatusMessage::from(format!(
+            "({}/{}) {}",
+            self.quickfix.index().saturating_add(1),
+            self.quickfix.len(),
+            message
+        ));
+        self.jump_to(position);
     }
 
-    fn refresh_screen(&mut self) -> Result<(), std::io::Error> {
-        Terminal::clear_screen();
-        Terminal::position_cursor(&Position::default());
-        if self.should_quit {
-            Terminal::quit();
-        } else {
-            self.draw_rows();
-            self.draw_status_bar();
-            self.draw_message_bar();
-            Terminal::position_cursor(&Position {
-                x: self.cursor_position.x.saturating_sub(self.offset.x),
-                y: self.cursor_position.y.saturating_sub(self.offset.y),
-            });
+    fn is_open(&self, filename: &str) -> bool {
+        let Some(open) = self.document.filename.as_deref() else {
+            return false;
+        };
+        open == filename
+            || fs::canonicalize(open)
+                .ok()
+                .is_some_and(|open| fs::canonicalize(filename).ok() == Some(open))
+    }
+
+    fn annotate(&mut self, comment: &str) {
+        let y = self.cursor_position.y;
+        if comment.is_empty() && self.document.review().get(y).is_none() {
+            self.status_message = StatusMessage::from(String::from("No note on this line"));
+            return;
         }
-        Terminal::flush()
+        self.status_message = match self.document.annotate(y, comment) {
+            Err(err) => StatusMessage::from(format!("Could not save notes: {}", err)),
+            Ok(()) if comment.is_empty() => StatusMessage::from(String::from("Note removed")),
+            Ok(()) => StatusMessage::from(format!("Note added to line {}", y.saturating_add(1))),
+        };
     }
 
-    fn draw_row(&self, row: &Row) {
-        let start = self.offset.x;
-        let width = self.terminal.size().width as usize;
-        let end = start.saturating_add(width);
-        let row = row.render(start, end);
-        println!("{row}\r");
+    fn jump_to_annotation(&mut self, direction: SearchDirection) {
+        let review = self.document.review();
+        let Some(y) = review.next(self.cursor_position.y, direction) else {
+            self.status_message = StatusMessage::from(String::from("No more notes"));
+            return;
+        };
+        let index = review.iter().take_while(|&(line, _)| line < y).count();
+        self.status_message = StatusMessage::from(format!(
+            "({}/{}) {}",
+            index.saturating_add(1),
+            review.len(),
+            review.get(y).unwrap_or_default()
+        ));
+        self.jump_to(Position { x: 0, y });
     }
 
-    fn draw_rows(&self) {
-        let height = self.terminal.size().height;
-        for terminal_row in 0..height {
-            Terminal::clear_current_line();
-            if let Some(row) = self
+    fn export_review(&mut self, args: &str) {
+        let Some(filename) = self.document.filename.clone() else {
+            self.status_message = StatusMessage::from(String::from("No file name"));
+            return;
+        };
+        if self.document.review().is_empty() {
+            self.status_message = StatusMessage::from(String::from("No notes to export"));
+            return;
+        }
+        let target = if args.is_empty() {
+            format!("{}.review.md", filename)
+        } else {
+            args.to_string()
+        };
+        let report = self.document.review().to_markdown(&filename, |y| {
+            self.document
+                .row(y)
+                .map_or_else(String::new, |row| row.as_str().to_string())
+        });
+        self.status_message = match fs::write(&target, report) {
+            Ok(()) => StatusMessage::from(format!(
+                "Exported {} note(s) to {}",
+                self.document.review().len(),
+                target
+            )),
+            Err(err) => StatusMessage::from(format!("Could not write {}: {}", target, err)),
+        };
+    }
+
+    fn set_theme(&mut self, name: &str) {
+        if name.is_empty() {
+            self.status_message = StatusMessage::from(format!("Theme: {}", self.theme.name));
+            return;
+        }
+        match Theme::load(name) {
+            Ok(mut theme) => {
+                theme.apply(&self.config.colours);
+                theme.adapt(self.terminal.capabilities());
+                self.theme = theme;
+                self.config.theme = name.to_string();
+            }
+            Err(err) => self.status_message = StatusMessage::from(err),
+        }
+    }
+
+    fn align_table(&mut self) {
+        let y = self.cursor_position.y;
+        let is_table_row = |index: usize| {
+            self.document
+                .row(index)
+                .is_some_and(|row| table::is_table_row(row.as_str()))
+        };
+        if !is_table_row(y) {
+            self.status_message = StatusMessage::from(String::from("No table under cursor"));
+            return;
+        }
+        let mut start = y;
+        while start > 0 && is_table_row(start - 1) {
+            start -= 1;
+        }
+        let mut end = y;
+        while is_table_row(end + 1) {
+            end += 1;
+        }
+
+        let lines: Vec<&str> = (start..=end)
+            .filter_map(|index| self.document.row(index).map(Row::as_str))
+            .collect();
+        let aligned = table::align(&lines);
+        self.document.begin_undo();
+        self.document.track_edits();
+        for (index, line) in (start..=end).zip(&aligned) {
+            if self
                 .document
-                .row(self.offset.y.saturating_add(terminal_row as usize))
+                .row(index)
+                .is_some_and(|row| row.as_str() != line)
             {
-                self.draw_row(row);
-            } else {
-                Terminal::set_text_colour(Color::DarkCyan);
-                println!("~\r");
-                Terminal::reset_colours();
+                self.document.replace_row(index, line);
             }
         }
+        self.remap_view();
+        self.document.end_undo();
     }
 
-    fn draw_status_bar(&self) {
-        let mut status: String;
-        let width = self.terminal.size().width as usize;
-        let mut filename = String::from("[unnamed]");
+    fn preserving_view<T>(&mut self, edit: impl FnOnce(&mut Document) -> T) -> T {
+        self.document.track_edits();
+        let result = edit(&mut self.document);
+        self.remap_view();
+        result
+    }
 
-        let modified_state = if self.document.is_dirty() {
-            " [modified]"
-        } else {
-            ""
-        };
-        
-        let current_mode = current_mode(self.terminal_mode);
-        
-        if let Some(name) = &self.document.filename {
-            filename = name.clone();
-            filename.truncate(20);
+    fn remap_view(&mut self) {
+        for change in self.document.take_edits() {
+            self.cursor_position = change.map(self.cursor_position);
+            self.selection_anchor = change.map(self.selection_anchor);
+            self.window.offset.y = change
+                .map(Position {
+                    x: 0,
+                    y: self.window.offset.y,
+                })
+                .y;
         }
-        status = format!(
-            "{}:{}:{}{}",
-            filename,
-            self.cursor_position.y.saturating_add(1),
-            self.cursor_position.x.saturating_add(1),
-            modified_state
-        );
-
-        let file_indicator = format!("{} | {}", self.document.file_type(), current_mode);
+        self.cursor_position = self.document.clamp(&self.cursor_position);
+    }
 
-        let len = status.len() + file_indicator.len();
-        status.push_str(&" ".repeat(width.saturating_sub(len)));
-        status = format!("{}{}", status, file_indicator);
-        status.truncate(width);
+    fn selection_stats(&self) -> Option<[usize; 4]> {
+        let (start, end) = self.selection()?;
+        let end = Position {
+            x: end.x.saturating_add(1),
+            y: end.y,
+        };
+        let text = self.document.text_between(&start, &end);
+        Some([
+            end.y.saturating_sub(start.y).saturating_add(1),
+            text.split_whitespace().count(),
+            text.graphemes(true).count(),
+            text.len(),
+        ])
+    }
 
-        Terminal::set_colours(Colors::new(STATUS_FG_COLOR, STATUS_BG_COLOR));
-        println!("{}\r", status);
-        Terminal::reset_colours();
+    fn copy_selection_stats(&mut self) {
+        let Some([lines, words, chars, bytes]) = self.selection_stats() else {
+            self.status_message = StatusMessage::from(String::from("No selection"));
+            return;
+        };
+        let stats = format!(
+            "{} lines, {} words, {} chars, {} bytes",
+            lines, words, chars, bytes
+        );
+        self.status_message = StatusMessage::from(
+            match clipboard::copy(&stats, self.terminal.capabilities().osc52) {
+                Ok(()) => format!("Copied {}", stats),
+                Err(err) => format!("{} (clipboard failed: {})", stats, err),
+            },
+        );
     }
 
-    fn draw_message_bar(&self) {
-        Terminal::clear_current_line();
-        let message = &self.status_message;
-        if Instant::now() - message.time < Duration::new(5, 0) {
-            let mut text = message.message.clone();
-            text.truncate(self.terminal.size().width as usize);
-            print!("{}", text);
+    fn share_selection(&mut self) {
+        let Some((start, end)) = self.selection() else {
+            self.status_message = StatusMessage::from(String::from("No selection to share"));
+            return;
+        };
+        let end = Position {
+            x: end.x.saturating_add(1),
+            y: end.y,
+        };
+        let text = self.document.text_between(&start, &end);
+        self.terminal_mode = TerminalMode::Normal;
+
+        let mut shared = Vec::new();
+        let path = env::temp_dir().join(format!("texty-share-{}.txt", process::id()));
+        match fs::write(&path, &text) {
+            Ok(()) => shared.push(path.display().to_string()),
+            Err(err) => shared.push(format!("file failed: {}", err)),
+        }
+        match clipboard::copy(&text, self.terminal.capabilities().osc52) {
+            Ok(()) => shared.push(String::from("clipboard")),
+            Err(err) => shared.push(format!("clipboard failed: {}", err)),
+        }
+        if let Some(command) = &self.config.paste_command {
+            match transform::pipe_through(command, &text) {
+                Ok(url) => shared.push(url.trim().to_string()),
+                Err(err) => shared.push(format!("paste failed: {}", err)),
+            }
         }
+
+        self.status_message = StatusMessage::from(format!("Shared to {}", shared.join(" | ")));
     }
 
-    fn save_file(&mut self) {
-        if self.document.filename.is_none() {
-            let new_name = self.prompt("Save as: ", |_, _, _| {}).unwrap_or(None);
-            if new_name.is_none() {
-                self.status_message = StatusMessage::from(String::from("Aborted save"));
-                return;
+    fn check_health(&mut self) {
+        let mut problems = Config::check();
+        problems.extend(FileType::check_syntaxes());
+        problems.extend(keymap_conflicts(&self.config.keymaps));
+        for action in &self.config.idle_actions {
+            if idle_action(action).is_none() {
+                problems.push(format!(
+                    "unknown idle action `{}`, expected one of: diagnostics, git-signs",
+                    action
+                ));
+            }
+        }
+        for name in self
+            .config
+            .statusline
+            .iter()
+            .chain(&self.config.tabline_segments)
+        {
+            if status_segment(name).is_none() && !self.config.status_commands.contains_key(name) {
+                problems.push(format!(
+                    "unknown statusline segment `{}`, expected words, percent, clock, saved, blame or a status_commands entry",
+                    name
+                ));
             }
-            self.document.filename = new_name;
         }
 
-        self.status_message = if self.document.save().is_ok() {
-            StatusMessage::from(String::from("Successfully saved file"))
+        self.status_message = StatusMessage::from(if problems.is_empty() {
+            String::from("checkhealth: OK")
         } else {
-            StatusMessage::from(String::from("Failed to save file"))
+            format!(
+                "checkhealth: {} problem(s): {}",
+                problems.len(),
+                problems.join("; ")
+            )
+        });
+    }
+
+    fn show_paths(&mut self) {
+        let show = |dir: Option<PathBuf>| {
+            dir.map_or_else(|| String::from("(none)"), |dir| dir.display().to_string())
         };
+        self.status_message = StatusMessage::from(format!(
+            "config: {} | state: {} | cache: {}",
+            show(paths::config_dir()),
+            show(paths::state_dir()),
+            show(paths::cache_dir())
+        ));
     }
 
-    fn quit(&mut self) {
-        if self.document.is_dirty() {
-            let confirmation = self
-                .prompt("Confirm to quit without saving? [y/N] ", |_, _, _| {})
-                .unwrap_or(None);
-            if let Some(s) = confirmation {
-                let s = s.to_ascii_lowercase();
-                if s == *"y" || s == *"yes" {
-                    self.should_quit = true;
-                } else {
-                    self.status_message = StatusMessage::from(String::from("Aborted quit"));
-                }
-            } else {
-                self.status_message = StatusMessage::from(String::from("Aborted quit"));
+    fn jump_to_bracket(&mut self) {
+        let Position { x, y } = self.cursor_position;
+        let len = self.document.row(y).map_or(0, Row::len);
+        let target = (x..len).find_map(|x| self.document.matching_pair(&Position { x, y }));
+        match target {
+            Some((position, _)) => self.jump_to(position),
+            None => {
+                self.status_message = StatusMessage::from(String::from("No matching bracket"));
             }
-        } else {
-            self.should_quit = true;
         }
     }
 
-    fn search(&mut self) {
-        let current_position = self.cursor_position;
-        let mut direction = SearchDirection::Forward;
+    fn goto_percent(&mut self, percent: usize) {
+        if percent > 100 {
+            self.status_message = StatusMessage::from(String::from("Percentage out of range"));
+            return;
+        }
+        let len = self.document.len();
+        let y = percent
+            .saturating_mul(len)
+            .saturating_add(99)
+            .saturating_div(100)
+            .saturating_sub(1);
+        self.jump_to(Position {
+            x: 0,
+            y: usize::min(y, len.saturating_sub(1)),
+        });
+    }
 
-        let query = self
-            .prompt(
-                "Search (ESC = cancel, Left | Right = nav): ",
-                |editor, key, query| {
-                    let mut moved = false;
-                    match key.code {
-                        KeyCode::Right => {
-                            direction = SearchDirection::Forward;
-                            moved = true;
-                            editor.move_cursor(KeyCode::Right);
-                        }
-                        KeyCode::Left => {
-                            direction = SearchDirection::Backward;
-                        }
-                        _ => direction = SearchDirection::Forward,
-                    }
-                    if let Some(position) =
-                        editor
-                            .document
-                            .find(query, &editor.cursor_position, direction)
-                    {
-                        editor.cursor_position = position;
-                        editor.scroll();
-                    } else if moved {
-                        editor.move_cursor(KeyCode::Left);
-                    }
+    fn jump_to(&mut self, position: Position) {
+        self.record_jump(self.cursor_position);
+        self.cursor_position = position;
+    }
 
-                    editor.document.highlight(Some(query));
-                },
-            )
-            .unwrap_or(None);
+    fn record_jump(&mut self, from: Position) {
+        self.document.set_mark('\'', from);
+        self.jumps.truncate(self.jump_index);
+        if self.jumps.last() != Some(&from) {
+            self.jumps.push(from);
+        }
+        if self.jumps.len() > JUMP_LIST_SIZE {
+            self.jumps.remove(0);
+        }
+        self.jump_index = self.jumps.len();
+    }
 
-        if query.is_none() {
-            self.cursor_position = current_position;
-            self.scroll();
+    fn jump_back(&mut self) {
+        if self.jump_index == 0 {
+            return;
+        }
+        if self.jump_index == self.jumps.len() {
+            if self.jumps.last() != Some(&self.cursor_position) {
+                self.jumps.push(self.cursor_position);
+            }
+            self.jump_index = self.jumps.len().saturating_sub(1);
+            if self.jump_index == 0 {
+                return;
+            }
         }
+        self.jump_index -= 1;
+        self.cursor_position = self.jumps[self.jump_index];
+    }
 
-        self.document.highlight(None);
+    fn jump_forward(&mut self) {
+        if self.jump_index.saturating_add(1) < self.jumps.len() {
+            self.jump_index += 1;
+            self.cursor_position = self.jumps[self.jump_index];
+        }
     }
 
-    fn process_keypress(&mut self) -> Result<(), std::io::Error> {
-        let event = Terminal::read_key()?;
+    fn goto_line(&mut self, line: usize) {
+        let y = usize::min(line, self.document.len()).saturating_sub(1);
+        self.jump_to(Position { x: 0, y });
+        let height = self.terminal.size().height as usize;
+        if y < self.window.offset.y || y >= self.window.offset.y.saturating_add(height) {
+            self.center_on(y);
+        }
+        self.scroll();
+    }
 
-        if let Event::Key(key) = event {
-            match (key.modifiers, key.code) {
-                (KeyModifiers::CONTROL, KeyCode::Char('q')) => self.quit(),
-                (KeyModifiers::CONTROL, KeyCode::Char('s')) => self.save_file(),
-                (KeyModifiers::CONTROL, KeyCode::Char('f')) => self.search(),
-                (_, KeyCode::Char(c)) => {
-                    if self.terminal_mode == TerminalMode::Normal {
-                        match c {
-                            'h' => self.move_cursor(KeyCode::Left),
-                            'j' => self.move_cursor(KeyCode::Down),
-                            'k' => self.move_cursor(KeyCode::Up),
-                            'l' => self.move_cursor(KeyCode::Right),
-                            'i' => self.terminal_mode = TerminalMode::Insert,
-                            _ => ()
-                        }
-                    } else {
-                        self.document.insert(&self.cursor_position, c);
-                        self.move_cursor(KeyCode::Right);
-                    }
-                }
-                (_, KeyCode::Enter) => {
-                    if self.terminal_mode == TerminalMode::Insert {
-                        self.document.insert(&self.cursor_position, '\n');
-                        self.move_cursor(KeyCode::Right);
-                    }
-                }
-                (_, KeyCode::Delete) => {
-                    if self.terminal_mode == TerminalMode::Insert {
-                        self.document.delete(&self.cursor_position);
-                    }
-                },
-                (_, KeyCode::Backspace) => {
-                    if self.terminal_mode == TerminalMode::Insert && (self.cursor_position.x > 0 || self.cursor_position.y > 0) {
-                        self.move_cursor(KeyCode::Left);
-                        self.document.delete(&self.cursor_position);
+    fn goto_line_prompt(&mut self) {
+        let last = usize::max(self.document.len(), 1);
+        if let Some(line) = self.prompt_number("Go to line: ", 1, last) {
+            self.goto_line(line);
+        }
+    }
+
+    fn jump_to_mark(&mut self, name: char) {
+        match self.document.mark(name) {
+            Some(position) => self.jump_to(position),
+            None => self.status_message = StatusMessage::from(format!("Mark '{} not set", name)),
+        }
+    }
+
+    fn prompt_for(
+        &mut self,
+        prompt: &str,
+        expected: &Expected,
+        history: Option<HistoryKind>,
+    ) -> Option<String> {
+        let mut input = String::new();
+        loop {
+            self.prompt_history = history;
+            self.prompt_completion = expected.completion();
+            input = self
+                .prompt_with_default(prompt, &input, |editor, key, _| {
+                    if !matches!(key.code, KeyCode::Tab | KeyCode::BackTab) {
+                        editor.prompt_hint.clear();
                     }
-                },
-                (_, KeyCode::Esc) => self.terminal_mode = TerminalMode::Normal,
-                (_, KeyCode::Up)
-                | (_, KeyCode::Down)
-                | (_, KeyCode::Left)
-                | (_, KeyCode::Right)
-                | (_, KeyCode::PageUp)
-                | (_, KeyCode::PageDown)
-                | (_, KeyCode::Home)
-                | (_, KeyCode::End) => self.move_cursor(key.code),
-                _ => (),
+                })
+                .unwrap_or(None)
+                .filter(|input| !input.is_empty())?;
+            match expected.check(&input) {
+                Ok(answer) => return Some(answer),
+                Err(err) => self.prompt_hint = format!("  [{}]", err),
             }
         }
-        self.scroll();
+    }
 
-        Ok(())
+    fn prompt_number(&mut self, prompt: &str, min: usize, max: usize) -> Option<usize> {
+        self.prompt_for(prompt, &Expected::Number { min, max }, None)
+            .and_then(|answer| answer.parse().ok())
+    }
+
+    fn prompt<C>(&mut self, prompt: &str, callback: C) -> Result<Option<String>, std::io::Error>
+    where
+        C: FnMut(&mut Self, KeyEvent, &String),
+    {
+        self.prompt_with_default(prompt, "", callback)
+    }
+
+    fn prompt_with_history<C>(
+        &mut self,
+        kind: HistoryKind,
+        prompt: &str,
+        callback: C,
+    ) -> Result<Option<String>, std::io::Error>
+    where
+        C: FnMut(&mut Self, KeyEvent, &String),
+    {
+        self.prompt_history = Some(kind);
+        self.prompt_with_default(prompt, "", callback)
     }
 
-    fn prompt<C>(&mut self, prompt: &str, mut callback: C) -> Result<Option<String>, std::io::Error>
+    fn prompt_with_default<C>(
+        &mut self,
+        prompt: &str,
+        default: &str,
+        mut callback: C,
+    ) -> Result<Option<String>, std::io::Error>
     where
         C: FnMut(&mut Self, KeyEvent, &String),
     {
-        let mut result = String::new();
+        let history = self.prompt_history.take();
+        let source = self.prompt_completion.take();
+        let mut completion: Option<Completion> = None;
+        let mut recalled = history.map_or(0, |kind| self.history.entries(kind).len());
+        let mut draft = String::new();
+        let mut result = String::from(default);
+        let mut inserting = false;
+        let mut cancelled = false;
         loop {
-            self.status_message = StatusMessage::from(format!("{}{}", prompt, result));
+            self.status_message =
+                StatusMessage::from(format!("{}{}{}", prompt, result, self.prompt_hint));
             self.refresh_screen()?;
-            let event = Terminal::read_key()?;
+            let event = self.next_event()?;
 
             if let Event::Key(key) = event {
+                if inserting {
+                    inserting = false;
+                    self.prompt_hint.clear();
+                    if let Some(text) = self.prompt_insertion(key) {
+                        result.push_str(&text);
+                    }
+                    callback(self, key, &result);
+                    continue;
+                }
+                let completing = matches!(key.code, KeyCode::Tab | KeyCode::BackTab);
+                if !completing && completion.take().is_some() {
+                    self.prompt_hint.clear();
+                }
                 match key.code {
-                    KeyCode::Backspace => result.truncate(result.len().saturating_sub(1)),
+                    KeyCode::Tab | KeyCode::BackTab if source.is_some() => {
+                        match &mut completion {
+                            Some(candidates) if !candidates.is_unique() => {
+                                if key.code == KeyCode::Tab {
+                                    candidates.next();
+                                } else {
+                                    candidates.previous();
+                                }
+                            }
+                            _ => {
+                                completion = source
+                                    .as_ref()
+                                    .and_then(|source| Completion::new(source, &result))
+                            }
+                        }
+                        self.prompt_hint = match &completion {
+                            Some(candidates) => {
+                                result = candidates.current().to_string();
+                                candidates.hint()
+                            }
+                            None => String::from(" [no completions]"),
+                        };
+                    }
+                    KeyCode::Backspace => {
+                        result.pop();
+                    }
                     KeyCode::Enter => break,
-                    KeyCode::Char(c) => {
-                        if !c.is_control() {
-                            result.push(c);
+                    KeyCode::Char('r') if key.modifiers == KeyModifiers::CONTROL => {
+                        inserting = true;
+                        self.prompt_hint = String::from(
+                            " [insert: % file, / search, ^W word, ^S selection, a-z register]",
+                        );
+                        continue;
+                    }
+                    KeyCode::Up | KeyCode::Down if history.is_some() => {
+                        let entries = history.map_or(&[][..], |kind| self.history.entries(kind));
+                        if recalled == entries.len() {
+                            draft = result.clone();
                         }
+                        recalled = if key.code == KeyCode::Up {
+                            recalled.saturating_sub(1)
+                        } else {
+                            usize::min(recalled.saturating_add(1), entries.len())
+                        };
+                        result = entries
+                            .get(recalled)
+                            .cloned()
+                            .unwrap_or_else(|| draft.clone());
                     }
+                    KeyCode::Char(c) if !c.is_control() => result.push(c),
                     KeyCode::Esc => {
-                        result.clear();
+                        cancelled = true;
                         break;
                     }
                     _ => (),
                 }
 
                 callback(self, key, &result);
+            } else if let Event::Resize(width, height) = event {
+                self.resize(width, height);
             }
         }
         self.status_message = StatusMessage::from(String::new());
-        if result.is_empty() {
+        self.prompt_hint.clear();
+        if cancelled {
             return Ok(None);
         }
+        if let Some(kind) = history.filter(|_| !result.is_empty()) {
+            self.history.push(kind, &result).ok();
+        }
         Ok(Some(result))
     }
 
+    fn resize(&mut self, width: u16, height: u16) {
+        self.terminal.resize(width, height);
+        let len = self.document.len();
+        if self.window.offset.y > len {
+            self.window.offset.y = len;
+            self.window.top_segment = 0;
+        }
+        if self.config.wrap {
+            self.window.offset.x = 0;
+            let len = self.document.row(self.window.offset.y).map_or(0, Row::len);
+            self.window.top_segment = usize::min(
+                self.window.top_segment,
+                self.layout().segment_count(len).saturating_sub(1),
+            );
+        }
+        self.scroll();
+    }
+
+    fn scroll_margin(&self) -> usize {
+        let height = self.terminal.size().height as usize;
+        usize::min(self.config.scroll_margin, height.saturating_sub(1) / 2)
+    }
+
+    fn scroll_wrapped(&mut self) {
+        let Position { x, y } = self.cursor_position;
+        let height = self.terminal.size().height as usize;
+        let margin = self.scroll_margin();
+        let layout = self.layout();
+        let cursor_line = layout.line_at(&self.document, x, y);
+        let mut top = self.top_line();
+
+        if top.row >= self.document.len() && top.row > 0 {
+            top = VisualLine::default();
+        }
+        if (cursor_line.row, cursor_line.segment) < (top.row, top.segment) {
+            top = cursor_line;
+        }
+        while layout.distance(&self.document, top, cursor_line) < margin {
+            match layout.previous(&self.document, top) {
+                Some(previous) => top = previous,
+                None => break,
+            }
+        }
+        while layout.distance(&self.document, top, cursor_line) >= height.saturating_sub(margin) {
+            match layout.next(&self.document, top) {
+                Some(next) => top = next,
+                None => break,
+            }
+        }
+
+        self.window.offset = Position { x: 0, y: top.row };
+        self.window.top_segment = top.segment;
+    }
+
     fn scroll(&mut self) {
+        if self.config.wrap {
+            self.scroll_wrapped();
+            return;
+        }
         let Position { x, y } = self.cursor_position;
-        let width = self.terminal.size().width as usize;
+        let layout = self.layout();
+        let row_width = layout.row_width(&self.document, y);
+        let x = layout.column_at(&self.document, x, y);
+        let width = self.text_width();
         let height = self.terminal.size().height as usize;
-        let offset = &mut self.offset;
+        let margin = self.scroll_margin();
+        let offset = &mut self.window.offset;
 
-        if y < offset.y {
-            offset.y = y;
-        } else if y >= offset.y.saturating_add(height) {
-            offset.y = y.saturating_sub(height).saturating_add(1);
+        if y < offset.y.saturating_add(margin) {
+            offset.y = y.saturating_sub(margin);
+        } else if y.saturating_add(margin) >= offset.y.saturating_add(height) {
+            offset.y = y
+                .saturating_add(margin)
+                .saturating_sub(height)
+                .saturating_add(1);
         }
         if x < offset.x {
             offset.x = x;
         } else if x >= offset.x.saturating_add(width) {
             offset.x = x.saturating_sub(width).saturating_add(1);
         }
+        if offset.x > 0 && x == offset.x {
+            offset.x -= 1;
+        } else if row_width > offset.x.saturating_add(width)
+            && x.saturating_add(1) >= offset.x.saturating_add(width)
+        {
+            offset.x += 1;
+        }
+    }
+
+    fn move_visual_line(&mut self, key: KeyCode) {
+        if !self.config.wrap {
+            self.move_cursor(key);
+            return;
+        }
+        let layout = self.layout();
+        let Position { x, y } = self.cursor_position;
+        let current = layout.line_at(&self.document, x, y);
+        let column = layout.column_of(
+            layout.column_at(&self.document, x, y),
+            layout.row_width(&self.document, y),
+        );
+        let target = if key == KeyCode::Down {
+            layout.next(&self.document, current)
+        } else {
+            layout.previous(&self.document, current)
+        };
+
+        if let Some(target) = target {
+            let (start, end) = layout.bounds(target);
+            let column = usize::min(start.saturating_add(column), end.saturating_sub(1));
+            self.cursor_position = Position {
+                x: layout.grapheme_at(&self.document, column, target.row),
+                y: target.row,
+            };
+        }
     }
 
     fn move_cursor(&mut self, key: KeyCode) {
@@ -399,11 +5684,7 @@ impl Editor {
 
         match key {
             KeyCode::Up => y = y.saturating_sub(1),
-            KeyCode::Down => {
-                if y < height {
-                    y = y.saturating_add(1);
-                }
-            }
+            KeyCode::Down if y < height => y = y.saturating_add(1),
             KeyCode::Left => {
                 if x > 0 {
                     x -= 1;
@@ -447,14 +5728,131 @@ impl Editor {
 }
 
 fn die(e: std::io::Error) {
-    crossterm::terminal::disable_raw_mode().ok();
+    Terminal::restore();
     Terminal::clear_screen();
     panic!("{}", e);
 }
 
+fn is_binary(text: &str) -> bool {
+    let control = text
+        .chars()
+        .filter(|&c| c.is_control() && !matches!(c, '\n' | '\r' | '\t'))
+        .count();
+    text.contains('\0') || control.saturating_mul(10) > text.chars().count()
+}
+
+fn format_size(bytes: usize) -> String {
+    match bytes {
+        0..=1023 => format!("{} B", bytes),
+        1024..=1_048_575 => format!("{:.1} KiB", bytes as f64 / 1024.0),
+        _ => format!("{:.1} MiB", bytes as f64 / 1_048_576.0),
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn control_action(c: char, insert: bool) -> Option<Action> {
+    let action = match c {
+        'q' => Action::Quit,
+        's' => Action::Save,
+        'f' => Action::Search,
+        'n' => Action::ToggleLineNumbers,
+        'l' => Action::LineJump,
+        't' => Action::SymbolJump,
+        'p' => Action::FindFile,
+        'e' => Action::ToggleFileTree,
+        'g' => Action::GotoLinePrompt,
+        'r' => Action::Redo,
+        '/' | '7' => Action::ToggleComment(1),
+        'o' if !insert => Action::JumpBack,
+        _ => return None,
+    };
+    Some(action)
+}
+
+fn keymap_conflicts(keymaps: &BTreeMap<String, BTreeMap<String, String>>) -> Vec<String> {
+    let mut problems = Vec::new();
+    let mut bound: BTreeMap<(String, String), (String, String)> = BTreeMap::new();
+    for (file_type, keymap) in keymaps {
+        let resolved = FileType::from(&format!("file.{}", file_type))
+            .name()
+            .to_lowercase();
+        let resolved = if resolved == FileType::default().name().to_lowercase() {
+            file_type.to_lowercase()
+        } else {
+            resolved
+        };
+        for (key, command) in keymap {
+            let notation = match macros::single_key(key) {
+                Ok(notation) => notation,
+                Err(err) => {
+                    problems.push(format!("keymaps.{}: {}", file_type, err));
+                    continue;
+                }
+            };
+            let shadowed = macros::from_notation(&notation)
+                .ok()
+                .and_then(|keys| keys.first().copied())
+                .filter(|key| key.modifiers == KeyModifiers::CONTROL)
+                .and_then(|key| match key.code {
+                    KeyCode::Char(c) => control_action(c, false),
+                    _ => None,
+                });
+            if let Some(action) = shadowed {
+                problems.push(format!(
+                    "keymaps.{}: `{}` hides the built-in {:?}; bind another key",
+                    file_type, key, action
+                ));
+            }
+            let here = (file_type.clone(), command.clone());
+            match bound.insert((resolved.clone(), notation), here) {
+                Some((other, other_command)) if other_command != *command => {
+                    problems.push(format!(
+                        "keymaps.{}: `{}` is also bound in keymaps.{} to `{}`; keep one of them",
+                        file_type, key, other, other_command
+                    ));
+                }
+                _ => (),
+            }
+        }
+    }
+    problems
+}
+
+fn idle_action(name: &str) -> Option<Handler> {
+    match name {
+        "diagnostics" => Some(Editor::run_diagnostics),
+        "git-signs" => Some(Editor::refresh_git_signs),
+        _ => None,
+    }
+}
+
+fn status_segment(name: &str) -> Option<fn(&Editor) -> String> {
+    match name {
+        "words" => Some(Editor::word_count),
+        "byte" => Some(Editor::byte_position),
+        "percent" => Some(Editor::percentage),
+        "clock" => Some(Editor::clock),
+        "saved" => Some(Editor::saved_age),
+        "blame" => Some(Editor::blame),
+        _ => None,
+    }
+}
+
 fn current_mode(mode: TerminalMode) -> String {
     match mode {
         TerminalMode::Normal => String::from("Normal"),
-        TerminalMode::Insert => String::from("Insert")
+        TerminalMode::Insert => String::from("Insert"),
+        TerminalMode::Visual => String::from("Visual"),
     }
-}
\ No newline at end of file
+}
+
+fn relative_to_cwd(path: &Path) -> String {
+    let cwd = env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
+    path.strip_prefix(&cwd)
+        .unwrap_or(path)
+        .display()
+        .to_string()
+}
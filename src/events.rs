@@ -0,0 +1,25 @@
+use crate::editor::Editor;
+use std::collections::HashMap;
+
+#[derive(PartialEq, Eq, Hash, Clone, Copy)]
+pub enum EditorEvent {
+    Tick,
+    Idle,
+}
+
+pub type Handler = fn(&mut Editor) -> bool;
+
+#[derive(Default)]
+pub struct EventBus {
+    handlers: HashMap<EditorEvent, Vec<Handler>>,
+}
+
+impl EventBus {
+    pub fn subscribe(&mut self, event: EditorEvent, handler: Handler) {
+        self.handlers.entry(event).or_default().push(handler);
+    }
+
+    pub fn handlers(&self, event: EditorEvent) -> Vec<Handler> {
+        self.handlers.get(&event).cloned().unwrap_or_default()
+    }
+}
@@ -0,0 +1,209 @@
+use crate::Document;
+
+pub struct Substitution {
+    pub first: usize,
+    pub last: usize,
+    pub pattern: String,
+    pub replacement: String,
+    pub global: bool,
+    pub confirm: bool,
+}
+
+pub fn execute(
+    document: &mut Document,
+    command: &str,
+    line: usize,
+) -> Option<Result<String, String>> {
+    if let Some(substitution) = parse_substitution(command, line, document.len()) {
+        return Some(substitution.and_then(|substitution| {
+            if substitution.confirm {
+                return Err(String::from("The c flag needs an interactive editor"));
+            }
+            substitute(document, &substitution)
+        }));
+    }
+    let (range, rest) = parse_range(command, line, document.len());
+    let (first, last) = range.unwrap_or((line, line));
+    if rest == "d" || rest == "delete" {
+        return Some(delete_rows(document, first, last));
+    }
+    None
+}
+
+pub fn parse_substitution(
+    command: &str,
+    line: usize,
+    len: usize,
+) -> Option<Result<Substitution, String>> {
+    let (range, rest) = parse_range(command, line, len);
+    let (first, last) = range.unwrap_or((line, line));
+    Some(
+        parse_substitute(rest)?.map(|(pattern, replacement, flags)| Substitution {
+            first,
+            last,
+            pattern,
+            replacement,
+            global: flags.contains('g'),
+            confirm: flags.contains('c'),
+        }),
+    )
+}
+
+fn parse_range(command: &str, line: usize, len: usize) -> (Option<(usize, usize)>, &str) {
+    if let Some(rest) = command.strip_prefix('%') {
+        return (Some((0, len.saturating_sub(1))), rest);
+    }
+    let Some((first, rest)) = parse_address(command, line, len) else {
+        return (None, command);
+    };
+    if let Some((last, rest)) = rest
+        .strip_prefix(',')
+        .and_then(|rest| parse_address(rest, line, len))
+    {
+        return (
+            Some((usize::min(first, last), usize::max(first, last))),
+            rest,
+        );
+    }
+    (Some((first, first)), rest)
+}
+
+fn parse_address(text: &str, line: usize, len: usize) -> Option<(usize, &str)> {
+    if let Some(rest) = text.strip_prefix('.') {
+        return Some((line, rest));
+    }
+    if let Some(rest) = text.strip_prefix('$') {
+        return Some((len.saturating_sub(1), rest));
+    }
+    let digits = text.len() - text.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    let number: usize = text[..digits].parse().ok()?;
+    Some((number.saturating_sub(1), &text[digits..]))
+}
+
+fn parse_substitute(command: &str) -> Option<Result<(String, String, String), String>> {
+    let rest = command
+        .strip_prefix("substitute")
+        .or_else(|| command.strip_prefix('s'))?;
+    let delimiter = rest.chars().next()?;
+    if delimiter.is_alphanumeric() || delimiter.is_whitespace() || delimiter == '\\' {
+        return None;
+    }
+
+    let mut parts = vec![String::new()];
+    let mut chars = rest[delimiter.len_utf8()..].chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some(next) if next == delimiter => parts.last_mut()?.push(next),
+                Some(next) => {
+                    parts.last_mut()?.push(c);
+                    parts.last_mut()?.push(next);
+                }
+                None => parts.last_mut()?.push(c),
+            },
+            c if c == delimiter && parts.len() < 3 => parts.push(String::new()),
+            c => parts.last_mut()?.push(c),
+        }
+    }
+
+    let pattern = parts.first().cloned().unwrap_or_default();
+    let replacement = parts.get(1).cloned().unwrap_or_default();
+    let flags = parts.get(2).cloned().unwrap_or_default();
+    if pattern.is_empty() {
+        return Some(Err(String::from("Empty search pattern")));
+    }
+    if let Some(flag) = flags.chars().find(|&flag| flag != 'g' && flag != 'c') {
+        return Some(Err(format!("Unknown substitute flag: {}", flag)));
+    }
+    Some(Ok((pattern, replacement, flags)))
+}
+
+fn substitute(document: &mut Document, substitution: &Substitution) -> Result<String, String> {
+    let pattern = substitution.pattern.as_str();
+    let replacement = substitution.replacement.as_str();
+    let mut count = 0;
+    document.begin_undo();
+    let last = usize::min(substitution.last, document.len().saturating_sub(1));
+    for y in substitution.first..=last {
+        let Some(row) = document.row(y) else {
+            break;
+        };
+        let line = row.as_str();
+        let matches = line.matches(pattern).count();
+        if matches == 0 {
+            continue;
+        }
+        let replaced = if substitution.global {
+            count += matches;
+            line.replace(pattern, replacement)
+        } else {
+            count += 1;
+            line.replacen(pattern, replacement, 1)
+        };
+        document.replace_row(y, &replaced);
+    }
+    document.end_undo();
+
+    if count == 0 {
+        Err(format!("Pattern not found: {}", pattern))
+    } else {
+        Ok(format!("{} substitution(s)", count))
+    }
+}
+
+fn delete_rows(document: &mut Document, first: usize, last: usize) -> Result<String, String> {
+    let len = document.len();
+    if first >= len {
+        return Err(String::from("Invalid range"));
+    }
+    let last = usize::min(last, len.saturating_sub(1));
+    document.delete_rows(first, last);
+    Ok(format!("{} line(s) deleted", last - first + 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parts(command: &str) -> (String, String, String) {
+        parse_substitute(command).unwrap().unwrap()
+    }
+
+    #[test]
+    fn unescapes_only_the_delimiter() {
+        assert_eq!(
+            parts(r"s/a\/b/c\/d/g"),
+            ("a/b".into(), "c/d".into(), "g".into())
+        );
+        assert_eq!(parts(r"s#a\#b#x#"), ("a#b".into(), "x".into(), "".into()));
+        assert_eq!(
+            parts(r"s/a\nb/\t/"),
+            (r"a\nb".into(), r"\t".into(), "".into())
+        );
+        assert_eq!(parts(r"s/a\\/b/"), (r"a\\".into(), "b".into(), "".into()));
+        assert_eq!(parts(r"s/a\"), (r"a\".into(), "".into(), "".into()));
+        assert_eq!(parts(r"s/\//x/"), ("/".into(), "x".into(), "".into()));
+    }
+
+    #[test]
+    fn keeps_further_delimiters_in_the_flags() {
+        assert_eq!(
+            parts("substitute|a|b|gc"),
+            ("a".into(), "b".into(), "gc".into())
+        );
+        assert_eq!(
+            parse_substitute("s/a/b/g/"),
+            Some(Err(String::from("Unknown substitute flag: /")))
+        );
+    }
+
+    #[test]
+    fn rejects_empty_patterns_and_word_delimiters() {
+        assert_eq!(
+            parse_substitute("s//x/"),
+            Some(Err(String::from("Empty search pattern")))
+        );
+        assert!(parse_substitute(r"s\a\b\").is_none());
+        assert!(parse_substitute("sax").is_none());
+    }
+}
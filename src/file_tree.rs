@@ -0,0 +1,228 @@
+use crate::Theme;
+use crossterm::event::{KeyCode, KeyEvent};
+use crossterm::style::{Colors, ResetColor, SetBackgroundColor, SetColors, SetForegroundColor};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf, MAIN_SEPARATOR_STR};
+use unicode_segmentation::UnicodeSegmentation;
+
+pub enum TreeAction {
+    None,
+    Open(PathBuf),
+    Create(PathBuf),
+    Rename(PathBuf),
+    Delete(PathBuf),
+    Unfocus,
+    Close,
+}
+
+struct Entry {
+    path: PathBuf,
+    name: String,
+    depth: usize,
+    is_dir: bool,
+}
+
+pub struct FileTree {
+    root: PathBuf,
+    expanded: HashSet<PathBuf>,
+    entries: Vec<Entry>,
+    selected: usize,
+    offset: usize,
+    pub focused: bool,
+}
+
+impl FileTree {
+    pub fn new(root: &Path) -> Self {
+        let mut tree = Self {
+            root: root.to_path_buf(),
+            expanded: HashSet::new(),
+            entries: Vec::new(),
+            selected: 0,
+            offset: 0,
+            focused: true,
+        };
+        tree.refresh();
+        tree
+    }
+
+    pub fn refresh(&mut self) {
+        let selected = self.selected_path().map(Path::to_path_buf);
+        self.entries.clear();
+        self.expanded.retain(|dir| dir.is_dir());
+        let root = self.root.clone();
+        self.list(&root, 0);
+        self.selected = selected
+            .and_then(|path| self.entries.iter().position(|entry| entry.path == path))
+            .unwrap_or_else(|| usize::min(self.selected, self.entries.len().saturating_sub(1)));
+    }
+
+    fn list(&mut self, dir: &Path, depth: usize) {
+        let Ok(read) = fs::read_dir(dir) else {
+            return;
+        };
+        let mut children: Vec<(bool, String, PathBuf)> = read
+            .filter_map(Result::ok)
+            .map(|entry| {
+                let path = entry.path();
+                let name = entry.file_name().to_string_lossy().into_owned();
+                (!path.is_dir(), name, path)
+            })
+            .filter(|(_, name, _)| name != ".git")
+            .collect();
+        children.sort();
+        for (is_file, name, path) in children {
+            let expanded = !is_file && self.expanded.contains(&path);
+            self.entries.push(Entry {
+                path: path.clone(),
+                name,
+                depth,
+                is_dir: !is_file,
+            });
+            if expanded {
+                self.list(&path, depth + 1);
+            }
+        }
+    }
+
+    pub fn selected_path(&self) -> Option<&Path> {
+        self.entries
+            .get(self.selected)
+            .map(|entry| entry.path.as_path())
+    }
+
+    pub fn select(&mut self, path: &Path) {
+        for ancestor in path.ancestors().skip(1) {
+            if ancestor.starts_with(&self.root) && ancestor != self.root {
+                self.expanded.insert(ancestor.to_path_buf());
+            }
+        }
+        self.refresh();
+        if let Some(index) = self.entries.iter().position(|entry| entry.path == path) {
+            self.selected = index;
+        }
+    }
+
+    fn target_dir(&self) -> PathBuf {
+        match self.entries.get(self.selected) {
+            Some(entry) if entry.is_dir && self.expanded.contains(&entry.path) => {
+                entry.path.clone()
+            }
+            Some(entry) => entry
+                .path
+                .parent()
+                .map_or_else(|| self.root.clone(), Path::to_path_buf),
+            None => self.root.clone(),
+        }
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) -> TreeAction {
+        let last = self.entries.len().saturating_sub(1);
+        match key.code {
+            KeyCode::Char('j') | KeyCode::Down => {
+                self.selected = usize::min(self.selected.saturating_add(1), last);
+            }
+            KeyCode::Char('k') | KeyCode::Up => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Char('g') | KeyCode::Home => self.selected = 0,
+            KeyCode::Char('G') | KeyCode::End => self.selected = last,
+            KeyCode::Enter | KeyCode::Char('l') | KeyCode::Right => {
+                let Some(entry) = self.entries.get(self.selected) else {
+                    return TreeAction::None;
+                };
+                if !entry.is_dir {
+                    return TreeAction::Open(entry.path.clone());
+                }
+                let path = entry.path.clone();
+                if !self.expanded.remove(&path) {
+                    self.expanded.insert(path);
+                }
+                self.refresh();
+            }
+            KeyCode::Char('h') | KeyCode::Left => {
+                let Some(entry) = self.entries.get(self.selected) else {
+                    return TreeAction::None;
+                };
+                if entry.is_dir && self.expanded.remove(&entry.path) {
+                    self.refresh();
+                } else if let Some(parent) = entry.path.parent() {
+                    if let Some(index) = self.entries.iter().position(|entry| entry.path == parent)
+                    {
+                        self.selected = index;
+                    }
+                }
+            }
+            KeyCode::Char('a') => return TreeAction::Create(self.target_dir()),
+            KeyCode::Char('r') => {
+                if let Some(path) = self.selected_path() {
+                    return TreeAction::Rename(path.to_path_buf());
+                }
+            }
+            KeyCode::Char('d') => {
+                if let Some(path) = self.selected_path() {
+                    return TreeAction::Delete(path.to_path_buf());
+                }
+            }
+            KeyCode::Char('R') => self.refresh(),
+            KeyCode::Char('q') => return TreeAction::Close,
+            KeyCode::Esc | KeyCode::Tab => return TreeAction::Unfocus,
+            _ => (),
+        }
+        TreeAction::None
+    }
+
+    pub fn scroll(&mut self, height: usize) {
+        if self.selected < self.offset {
+            self.offset = self.selected;
+        } else if self.selected >= self.offset.saturating_add(height) {
+            self.offset = self.selected.saturating_sub(height.saturating_sub(1));
+        }
+    }
+
+    pub fn cursor_row(&self) -> usize {
+        self.selected.saturating_sub(self.offset)
+    }
+
+    pub fn draw_line(&self, y: usize, width: usize, open: Option<&Path>, theme: &Theme) -> String {
+        let inner = width.saturating_sub(1);
+        let index = y.saturating_add(self.offset);
+        let text = self.entries.get(index).map_or_else(String::new, |entry| {
+            let marker = match (entry.is_dir, self.expanded.contains(&entry.path)) {
+                (true, true) => "▾ ",
+                (true, false) => "▸ ",
+                (false, _) => "  ",
+            };
+            let slash = if entry.is_dir { MAIN_SEPARATOR_STR } else { "" };
+            format!(
+                "{}{}{}{}",
+                "  ".repeat(entry.depth),
+                marker,
+                entry.name,
+                slash
+            )
+        });
+        let text: String = text.graphemes(true).take(inner).collect();
+        let padded = format!("{:<inner$}", text, inner = inner);
+        let colours = if index == self.selected && self.focused {
+            format!("{}", SetBackgroundColor(theme.selection_bg.0))
+        } else if self
+            .entries
+            .get(index)
+            .is_some_and(|entry| Some(entry.path.as_path()) == open)
+        {
+            format!(
+                "{}",
+                SetColors(Colors::new(theme.status_fg.0, theme.status_bg.0))
+            )
+        } else {
+            String::new()
+        };
+        format!(
+            "{}{}{}{}│{}",
+            colours,
+            padded,
+            ResetColor,
+            SetForegroundColor(theme.gutter_fg.0),
+            ResetColor
+        )
+    }
+}
@@ -1,23 +1,70 @@
+use crate::outline;
+use crate::Config;
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, PoisonError};
+
+const BUILTIN_SYNTAXES: [&str; 7] = [
+    include_str!("../syntaxes/rust.toml"),
+    include_str!("../syntaxes/python.toml"),
+    include_str!("../syntaxes/go.toml"),
+    include_str!("../syntaxes/toml.toml"),
+    include_str!("../syntaxes/markdown.toml"),
+    include_str!("../syntaxes/html.toml"),
+    include_str!("../syntaxes/shell.toml"),
+];
+
+const DEFAULT_PAIRS: [&str; 5] = ["()", "[]", "{}", "\"\"", "''"];
+
+static SYNTAXES: Mutex<Option<Arc<Vec<Syntax>>>> = Mutex::new(None);
+
 pub struct FileType {
     name: String,
     hl_opts: HighlightingOptions,
+    auto_pairs: Vec<(char, char)>,
+    match_tags: bool,
+    match_pairs: Vec<(String, String)>,
+    outline: Vec<String>,
 }
 
-#[derive(Default)]
+#[derive(Deserialize, Default, Clone)]
+#[serde(default)]
 pub struct HighlightingOptions {
     numbers: bool,
-    strings: bool,
+    strings: Vec<char>,
     characters: bool,
-    comments: bool,
+    line_comment: Option<String>,
+    block_comment: Option<(String, String)>,
     primary_keywords: Vec<String>,
     secondary_keywords: Vec<String>,
 }
 
+#[derive(Deserialize, Clone)]
+struct Syntax {
+    name: String,
+    #[serde(default)]
+    extensions: Vec<String>,
+    auto_pairs: Option<Vec<String>>,
+    #[serde(default)]
+    match_tags: bool,
+    #[serde(default)]
+    match_pairs: Vec<(String, String)>,
+    #[serde(default)]
+    outline: Vec<String>,
+    #[serde(flatten)]
+    hl_opts: HighlightingOptions,
+}
+
 impl Default for FileType {
     fn default() -> Self {
         Self {
             name: String::from("No file type"),
             hl_opts: HighlightingOptions::default(),
+            auto_pairs: parse_pairs(&DEFAULT_PAIRS),
+            match_tags: false,
+            match_pairs: Vec::new(),
+            outline: Vec::new(),
         }
     }
 }
@@ -28,95 +75,101 @@ impl FileType {
     }
 
     pub fn from(file_name: &str) -> Self {
-        if file_name.ends_with(".rs") {
-            return Self {
-                name: String::from("Rust"),
-                hl_opts: HighlightingOptions {
-                    numbers: true,
-                    strings: true,
-                    characters: true,
-                    comments: true,
-                    primary_keywords: vec![
-                        "as".to_string(),
-                        "break".to_string(),
-                        "const".to_string(),
-                        "continue".to_string(),
-                        "crate".to_string(),
-                        "else".to_string(),
-                        "enum".to_string(),
-                        "extern".to_string(),
-                        "false".to_string(),
-                        "fn".to_string(),
-                        "for".to_string(),
-                        "if".to_string(),
-                        "impl".to_string(),
-                        "in".to_string(),
-                        "let".to_string(),
-                        "loop".to_string(),
-                        "match".to_string(),
-                        "mod".to_string(),
-                        "move".to_string(),
-                        "mut".to_string(),
-                        "pub".to_string(),
-                        "ref".to_string(),
-                        "return".to_string(),
-                        "self".to_string(),
-                        "Self".to_string(),
-                        "static".to_string(),
-                        "struct".to_string(),
-                        "super".to_string(),
-                        "trait".to_string(),
-                        "true".to_string(),
-                        "type".to_string(),
-                        "unsafe".to_string(),
-                        "use".to_string(),
-                        "where".to_string(),
-                        "while".to_string(),
-                        "dyn".to_string(),
-                        "abstract".to_string(),
-                        "become".to_string(),
-                        "box".to_string(),
-                        "do".to_string(),
-                        "final".to_string(),
-                        "macro".to_string(),
-                        "override".to_string(),
-                        "priv".to_string(),
-                        "typeof".to_string(),
-                        "unsized".to_string(),
-                        "virtual".to_string(),
-                        "yield".to_string(),
-                        "async".to_string(),
-                        "await".to_string(),
-                        "try".to_string(),
-                    ],
-                    secondary_keywords: vec![
-                        "bool".to_string(),
-                        "char".to_string(),
-                        "i8".to_string(),
-                        "i16".to_string(),
-                        "i32".to_string(),
-                        "i64".to_string(),
-                        "isize".to_string(),
-                        "u8".to_string(),
-                        "u16".to_string(),
-                        "u32".to_string(),
-                        "u64".to_string(),
-                        "usize".to_string(),
-                        "f32".to_string(),
-                        "f64".to_string(),
-                        "str".to_string(),
-                        "String".to_string(),
-                    ],
-                },
-            };
-        }
+        let Some(extension) = Path::new(file_name)
+            .extension()
+            .and_then(|ext| ext.to_str())
+        else {
+            return Self::default();
+        };
+
+        syntaxes()
+            .iter()
+            .find(|syntax| syntax.extensions.iter().any(|ext| ext == extension))
+            .cloned()
+            .map_or_else(Self::default, |syntax| Self {
+                name: syntax.name,
+                hl_opts: syntax.hl_opts,
+                auto_pairs: syntax
+                    .auto_pairs
+                    .map_or_else(|| parse_pairs(&DEFAULT_PAIRS), |pairs| parse_pairs(&pairs)),
+                match_tags: syntax.match_tags,
+                match_pairs: syntax.match_pairs,
+                outline: syntax.outline,
+            })
+    }
 
-        Self::default()
+    pub fn reload_syntaxes() {
+        *SYNTAXES.lock().unwrap_or_else(PoisonError::into_inner) = None;
+    }
+
+    pub fn check_syntaxes() -> Vec<String> {
+        let mut problems = Vec::new();
+        for path in syntax_files() {
+            let result = fs::read_to_string(&path)
+                .map_err(|err| err.to_string())
+                .and_then(|source| {
+                    toml::from_str::<Syntax>(&source).map_err(|err| err.message().to_string())
+                })
+                .and_then(|syntax| outline::compile(&syntax.outline).map(|_| ()));
+            if let Err(err) = result {
+                problems.push(format!("{}: {}", path.display(), err));
+            }
+        }
+        problems
     }
 
     pub fn highlighting_options(&self) -> &HighlightingOptions {
         &self.hl_opts
     }
+
+    pub fn auto_pairs(&self) -> &[(char, char)] {
+        &self.auto_pairs
+    }
+
+    pub fn match_tags(&self) -> bool {
+        self.match_tags
+    }
+
+    pub fn match_pairs(&self) -> &[(String, String)] {
+        &self.match_pairs
+    }
+
+    pub fn outline(&self) -> &[String] {
+        &self.outline
+    }
+}
+
+fn syntaxes() -> Arc<Vec<Syntax>> {
+    let mut cached = SYNTAXES.lock().unwrap_or_else(PoisonError::into_inner);
+    let syntaxes = cached.get_or_insert_with(|| {
+        let user_syntaxes = syntax_files()
+            .into_iter()
+            .filter_map(|path| fs::read_to_string(path).ok());
+        let builtin_syntaxes = BUILTIN_SYNTAXES.iter().map(|source| source.to_string());
+        Arc::new(
+            user_syntaxes
+                .chain(builtin_syntaxes)
+                .filter_map(|source| toml::from_str::<Syntax>(&source).ok())
+                .collect(),
+        )
+    });
+    Arc::clone(syntaxes)
+}
+
+pub fn syntax_files() -> Vec<PathBuf> {
+    let Some(dir) = Config::path().and_then(|path| path.parent().map(|dir| dir.join("syntaxes")))
+    else {
+        return Vec::new();
+    };
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "toml"))
+        .collect();
+    paths.sort();
+    paths
 }
 
 impl HighlightingOptions {
@@ -124,16 +177,22 @@ impl HighlightingOptions {
         self.numbers
     }
 
-    pub fn strings(&self) -> bool {
-        self.strings
+    pub fn strings(&self) -> &[char] {
+        &self.strings
     }
 
     pub fn characters(&self) -> bool {
         self.characters
     }
 
-    pub fn comments(&self) -> bool {
-        self.comments
+    pub fn line_comment(&self) -> Option<&str> {
+        self.line_comment.as_deref()
+    }
+
+    pub fn block_comment(&self) -> Option<(&str, &str)> {
+        self.block_comment
+            .as_ref()
+            .map(|(start, end)| (start.as_str(), end.as_str()))
     }
 
     pub fn primary_keywords(&self) -> &Vec<String> {
@@ -144,3 +203,16 @@ impl HighlightingOptions {
         &self.secondary_keywords
     }
 }
+
+fn parse_pairs<S: AsRef<str>>(pairs: &[S]) -> Vec<(char, char)> {
+    pairs
+        .iter()
+        .filter_map(|pair| {
+            let mut chars = pair.as_ref().chars();
+            match (chars.next(), chars.next(), chars.next()) {
+                (Some(open), Some(close), None) => Some((open, close)),
+                _ => None,
+            }
+        })
+        .collect()
+}
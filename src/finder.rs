@@ -0,0 +1,154 @@
+use regex::Regex;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const MAX_FILES: usize = 50_000;
+const IGNORE_FILE: &str = ".gitignore";
+
+struct Rule {
+    base: PathBuf,
+    pattern: Regex,
+    negated: bool,
+    dir_only: bool,
+}
+
+impl Rule {
+    fn parse(base: &Path, line: &str) -> Option<Self> {
+        let line = line.trim_end();
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+        let (negated, line) = match line.strip_prefix('!') {
+            Some(rest) => (true, rest),
+            None => (false, line.strip_prefix('\\').unwrap_or(line)),
+        };
+        let (dir_only, line) = match line.strip_suffix('/') {
+            Some(rest) => (true, rest),
+            None => (false, line),
+        };
+        let anchored = line.contains('/');
+        let glob = glob_to_regex(line.trim_start_matches('/'));
+        let pattern = if anchored {
+            format!("^{}$", glob)
+        } else {
+            format!("^(?:.*/)?{}$", glob)
+        };
+        Some(Self {
+            base: base.to_path_buf(),
+            pattern: Regex::new(&pattern).ok()?,
+            negated,
+            dir_only,
+        })
+    }
+
+    fn matches(&self, path: &Path, is_dir: bool) -> bool {
+        if self.dir_only && !is_dir {
+            return false;
+        }
+        path.strip_prefix(&self.base).is_ok_and(|relative| {
+            self.pattern
+                .is_match(&relative.to_string_lossy().replace('\\', "/"))
+        })
+    }
+}
+
+fn glob_to_regex(glob: &str) -> String {
+    let mut regex = String::new();
+    let mut chars = glob.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                if chars.peek() == Some(&'/') {
+                    chars.next();
+                    regex.push_str("(?:.*/)?");
+                } else {
+                    regex.push_str(".*");
+                }
+            }
+            '*' => regex.push_str("[^/]*"),
+            '?' => regex.push_str("[^/]"),
+            '[' => {
+                let class: String = chars.by_ref().take_while(|&c| c != ']').collect();
+                regex.push('[');
+                regex.push_str(&class.replacen('!', "^", 1).replace('\\', "\\\\"));
+                regex.push(']');
+            }
+            c => regex.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    regex
+}
+
+fn load_rules(dir: &Path, rules: &mut Vec<Rule>) {
+    if let Ok(contents) = fs::read_to_string(dir.join(IGNORE_FILE)) {
+        rules.extend(contents.lines().filter_map(|line| Rule::parse(dir, line)));
+    }
+}
+
+fn is_ignored(rules: &[Rule], path: &Path, is_dir: bool) -> bool {
+    rules
+        .iter()
+        .rev()
+        .find(|rule| rule.matches(path, is_dir))
+        .is_some_and(|rule| !rule.negated)
+}
+
+pub fn project_root(start: &Path) -> PathBuf {
+    start
+        .ancestors()
+        .find(|dir| dir.join(".git").exists())
+        .unwrap_or(start)
+        .to_path_buf()
+}
+
+pub fn files(root: &Path) -> Vec<String> {
+    let mut rules = Vec::new();
+    let ancestors: Vec<&Path> = root.ancestors().skip(1).collect();
+    if let Some(top) = ancestors
+        .iter()
+        .position(|dir| dir.join(".git").exists())
+        .filter(|_| !root.join(".git").exists())
+    {
+        for dir in ancestors[..=top].iter().rev() {
+            load_rules(dir, &mut rules);
+        }
+    }
+
+    let mut files = Vec::new();
+    walk(root, root, &mut rules, &mut files);
+    files.sort();
+    files
+}
+
+fn walk(root: &Path, dir: &Path, rules: &mut Vec<Rule>, files: &mut Vec<String>) {
+    let inherited = rules.len();
+    load_rules(dir, rules);
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .collect();
+    paths.sort();
+    for path in paths {
+        if files.len() >= MAX_FILES {
+            break;
+        }
+        if path.file_name().is_some_and(|name| name == ".git") {
+            continue;
+        }
+        let is_dir = path.is_dir();
+        if is_ignored(rules, &path, is_dir) {
+            continue;
+        }
+        if is_dir {
+            if !path.is_symlink() {
+                walk(root, &path, rules, files);
+            }
+        } else if let Ok(relative) = path.strip_prefix(root) {
+            files.push(relative.to_string_lossy().into_owned());
+        }
+    }
+    rules.truncate(inherited);
+}
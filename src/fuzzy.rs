@@ -0,0 +1,56 @@
+const MATCH_SCORE: usize = 1;
+const CONSECUTIVE_BONUS: usize = 5;
+const BOUNDARY_BONUS: usize = 3;
+
+pub fn score(query: &str, candidate: &str) -> Option<usize> {
+    let mut query_chars = query.chars().filter(|c| !c.is_whitespace()).peekable();
+    let mut score = 0;
+    let mut previous: Option<char> = None;
+    let mut previous_matched = false;
+
+    for c in candidate.chars() {
+        let Some(&wanted) = query_chars.peek() else {
+            break;
+        };
+        if c.to_lowercase().eq(wanted.to_lowercase()) {
+            score += MATCH_SCORE;
+            if previous_matched {
+                score += CONSECUTIVE_BONUS;
+            }
+            if is_boundary(previous, c) {
+                score += BOUNDARY_BONUS;
+            }
+            previous_matched = true;
+            query_chars.next();
+        } else {
+            previous_matched = false;
+        }
+        previous = Some(c);
+    }
+
+    if query_chars.peek().is_some() {
+        return None;
+    }
+    Some(score)
+}
+
+pub fn rank<'a, I>(query: &str, candidates: I) -> Vec<(usize, usize)>
+where
+    I: Iterator<Item = &'a str>,
+{
+    let mut ranked: Vec<(usize, usize)> = candidates
+        .enumerate()
+        .filter_map(|(index, candidate)| score(query, candidate).map(|score| (index, score)))
+        .collect();
+    ranked.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    ranked
+}
+
+fn is_boundary(previous: Option<char>, c: char) -> bool {
+    match previous {
+        None => true,
+        Some(previous) => {
+            !previous.is_alphanumeric() || (previous.is_lowercase() && c.is_uppercase())
+        }
+    }
+}
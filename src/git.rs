@@ -0,0 +1,132 @@
+use crate::clock;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+pub fn head_contents(filename: &str) -> Option<String> {
+    let (dir, name) = split(filename)?;
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .arg("show")
+        .arg(format!("HEAD:./{}", name))
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[derive(Default)]
+pub struct Blame {
+    key: Option<(String, usize, String)>,
+    text: String,
+    pending: Option<Receiver<String>>,
+}
+
+impl Blame {
+    pub fn text(&self, filename: &str, line: usize, current: &str) -> &str {
+        match &self.key {
+            Some((name, at, text)) if name == filename && *at == line && text == current => {
+                &self.text
+            }
+            _ => "",
+        }
+    }
+
+    pub fn request<F>(&mut self, filename: &str, line: usize, current: &str, contents: F)
+    where
+        F: FnOnce() -> String,
+    {
+        let key = (filename.to_string(), line, current.to_string());
+        if self.key.as_ref() == Some(&key) {
+            return;
+        }
+        self.key = Some(key);
+        let contents = contents();
+        self.text.clear();
+        let (sender, receiver) = mpsc::channel();
+        let filename = filename.to_string();
+        thread::spawn(move || {
+            let text = blame_line(&filename, line, &contents).unwrap_or_default();
+            sender.send(text).ok();
+        });
+        self.pending = Some(receiver);
+    }
+
+    pub fn poll(&mut self) -> bool {
+        let Some(text) = self
+            .pending
+            .as_ref()
+            .and_then(|pending| pending.try_recv().ok())
+        else {
+            return false;
+        };
+        self.pending = None;
+        self.text = text;
+        !self.text.is_empty()
+    }
+}
+
+fn blame_line(filename: &str, line: usize, contents: &str) -> Option<String> {
+    let (dir, name) = split(filename)?;
+    let mut child = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(["blame", "--porcelain", "--contents", "-", "-L"])
+        .arg(format!("{},{}", line + 1, line + 1))
+        .arg("--")
+        .arg(name)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .ok()?;
+    child.stdin.take()?.write_all(contents.as_bytes()).ok()?;
+    let output = child.wait_with_output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let porcelain = String::from_utf8_lossy(&output.stdout);
+    let mut lines = porcelain.lines();
+    let hash = lines.next()?.split(' ').next()?;
+    if hash.chars().all(|c| c == '0') {
+        return Some(String::from("not committed yet"));
+    }
+    let field = |key: &str| {
+        porcelain
+            .lines()
+            .find_map(|line| line.strip_prefix(key))
+            .map(str::to_string)
+    };
+    let author = field("author ").unwrap_or_default();
+    let age = field("author-time ")
+        .and_then(|time| time.parse::<u64>().ok())
+        .and_then(|time| {
+            let now = SystemTime::now().duration_since(UNIX_EPOCH).ok()?;
+            Some(clock::age(now.saturating_sub(Duration::from_secs(time))))
+        })
+        .unwrap_or_default();
+    Some(format!(
+        "{} {}, {}",
+        &hash[..hash.len().min(7)],
+        author,
+        age
+    ))
+}
+
+fn split(filename: &str) -> Option<(&Path, String)> {
+    let path = Path::new(filename);
+    let dir = path
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    let name = path.file_name()?.to_string_lossy().into_owned();
+    Some((dir, name))
+}
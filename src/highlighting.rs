@@ -1,4 +1,5 @@
-use crossterm::style::Color;
+use crate::Theme;
+use crossterm::style::{Attribute, Color};
 
 #[derive(PartialEq, Clone, Copy)]
 pub enum Type {
@@ -10,35 +11,31 @@ pub enum Type {
     PrimaryKeywords,
     SecondaryKeywords,
     Match,
+    Bracket,
+    Ansi(Color),
 }
 
 impl Type {
-    pub fn to_colour(self) -> Color {
+    pub fn to_colour(self, theme: &Theme) -> Color {
+        let colour = match self {
+            Type::Number => theme.number,
+            Type::String => theme.string,
+            Type::Character => theme.character,
+            Type::Comment => theme.comment,
+            Type::PrimaryKeywords => theme.primary_keywords,
+            Type::SecondaryKeywords => theme.secondary_keywords,
+            Type::Match => theme.search_match,
+            Type::Bracket => theme.bracket_match,
+            Type::None => theme.none,
+            Type::Ansi(colour) => return colour,
+        };
+        colour.0
+    }
+
+    pub fn attribute(self, theme: &Theme) -> Option<Attribute> {
         match self {
-            Type::Number => Color::Rgb {
-                r: 244,
-                g: 162,
-                b: 97,
-            },
-            Type::String => Color::Rgb {
-                r: 233,
-                g: 237,
-                b: 201,
-            },
-            Type::Character => Color::Rgb {
-                r: 255,
-                g: 200,
-                b: 221,
-            },
-            Type::Comment => Color::Rgb {
-                r: 133,
-                g: 153,
-                b: 0,
-            },
-            Type::PrimaryKeywords => Color::Green,
-            Type::SecondaryKeywords => Color::Yellow,
-            Type::Match => Color::Cyan,
-            Type::None => Color::White,
+            Type::Comment if theme.italics => Some(Attribute::Italic),
+            _ => None,
         }
     }
 }
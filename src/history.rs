@@ -0,0 +1,70 @@
+use crate::paths;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::Error;
+use std::path::PathBuf;
+
+const HISTORY_SIZE: usize = 100;
+const HISTORY_FILE: &str = "history.json";
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum HistoryKind {
+    Search,
+    Command,
+    SaveAs,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct History {
+    search: Vec<String>,
+    command: Vec<String>,
+    save_as: Vec<String>,
+    #[serde(skip)]
+    path: Option<PathBuf>,
+}
+
+impl History {
+    pub fn load() -> Self {
+        let path = paths::state_dir().map(|dir| dir.join(HISTORY_FILE));
+        let mut history = path
+            .as_ref()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|contents| serde_json::from_str::<Self>(&contents).ok())
+            .unwrap_or_default();
+        history.path = path;
+        history
+    }
+
+    pub fn entries(&self, kind: HistoryKind) -> &[String] {
+        match kind {
+            HistoryKind::Search => &self.search,
+            HistoryKind::Command => &self.command,
+            HistoryKind::SaveAs => &self.save_as,
+        }
+    }
+
+    pub fn push(&mut self, kind: HistoryKind, entry: &str) -> Result<(), Error> {
+        let entries = match kind {
+            HistoryKind::Search => &mut self.search,
+            HistoryKind::Command => &mut self.command,
+            HistoryKind::SaveAs => &mut self.save_as,
+        };
+        entries.retain(|existing| existing != entry);
+        entries.push(entry.to_string());
+        if entries.len() > HISTORY_SIZE {
+            entries.remove(0);
+        }
+        self.save()
+    }
+
+    fn save(&self) -> Result<(), Error> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, serde_json::to_string(self)?)
+    }
+}
@@ -0,0 +1,98 @@
+use crate::paths;
+use crate::transform;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use std::time::Instant;
+
+struct Job {
+    commands: Vec<String>,
+    dir: PathBuf,
+    filename: PathBuf,
+}
+
+#[derive(Default)]
+pub struct Hooks {
+    jobs: Option<Sender<Job>>,
+    results: Option<Receiver<String>>,
+}
+
+impl Hooks {
+    pub fn run(&mut self, commands: &[String], dir: &Path, filename: &Path) {
+        if commands.is_empty() {
+            return;
+        }
+        let jobs = self.jobs.get_or_insert_with(|| {
+            let (jobs, queue) = mpsc::channel::<Job>();
+            let (sender, results) = mpsc::channel();
+            thread::spawn(move || {
+                for job in queue {
+                    for command in &job.commands {
+                        if sender.send(run_hook(command, &job)).is_err() {
+                            return;
+                        }
+                    }
+                }
+            });
+            self.results = Some(results);
+            jobs
+        });
+        jobs.send(Job {
+            commands: commands.to_vec(),
+            dir: dir.to_path_buf(),
+            filename: filename.to_path_buf(),
+        })
+        .ok();
+    }
+
+    pub fn poll(&self) -> Vec<String> {
+        self.results
+            .as_ref()
+            .map_or_else(Vec::new, |results| results.try_iter().collect())
+    }
+}
+
+fn run_hook(command: &str, job: &Job) -> String {
+    let started = Instant::now();
+    let output = transform::shell(command)
+        .current_dir(&job.dir)
+        .env("TEXTY_FILE", &job.filename)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .output();
+    match output {
+        Ok(output) if output.status.success() => format!(
+            "Hook `{}` done in {:.1}s",
+            command,
+            started.elapsed().as_secs_f32()
+        ),
+        Ok(output) => {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            match stderr.lines().rev().find(|line| !line.trim().is_empty()) {
+                Some(line) => format!(
+                    "Hook `{}` failed ({}): {}",
+                    command,
+                    output.status,
+                    line.trim()
+                ),
+                None => format!("Hook `{}` failed ({})", command, output.status),
+            }
+        }
+        Err(err) => format!("Hook `{}` failed: {}", command, err),
+    }
+}
+
+pub fn matching<'a, I>(hooks: I, filename: &Path) -> Option<(PathBuf, Vec<String>)>
+where
+    I: Iterator<Item = (&'a String, &'a Vec<String>)>,
+{
+    hooks
+        .filter_map(|(dir, commands)| {
+            let dir = paths::expand_home(dir).canonicalize().ok()?;
+            filename.starts_with(&dir).then_some((dir, commands))
+        })
+        .max_by_key(|(dir, _)| dir.components().count())
+        .map(|(dir, commands)| (dir, commands.clone()))
+}
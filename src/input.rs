@@ -0,0 +1,94 @@
+use crate::completion::{self, Source};
+use std::path::Path;
+
+pub enum Expected {
+    Number { min: usize, max: usize },
+    Path { must_exist: bool },
+    Choice(Vec<String>),
+}
+
+impl Expected {
+    pub fn check(&self, input: &str) -> Result<String, String> {
+        let input = input.trim();
+        match self {
+            Self::Number { min, max } => number(input, *min, *max).map(|value| value.to_string()),
+            Self::Path { must_exist } => path(input, *must_exist),
+            Self::Choice(options) => choice(input, options),
+        }
+    }
+
+    pub fn completion(&self) -> Option<Source> {
+        match self {
+            Self::Number { .. } => None,
+            Self::Path { .. } => Some(Box::new(completion::paths)),
+            Self::Choice(options) => {
+                let options = options.clone();
+                Some(Box::new(move |input| {
+                    options
+                        .iter()
+                        .filter(|option| option.starts_with(input))
+                        .cloned()
+                        .collect()
+                }))
+            }
+        }
+    }
+}
+
+pub fn number(input: &str, min: usize, max: usize) -> Result<usize, String> {
+    let value = input
+        .parse::<usize>()
+        .map_err(|_| format!("{} is not a number", input))?;
+    match (value < min || value > max, max) {
+        (false, _) => Ok(value),
+        (true, usize::MAX) => Err(format!("Enter a number of at least {}", min)),
+        (true, _) => Err(format!("Enter a number from {} to {}", min, max)),
+    }
+}
+
+fn path(input: &str, must_exist: bool) -> Result<String, String> {
+    let path = Path::new(input);
+    if input.is_empty() {
+        return Err(String::from("Enter a file name"));
+    }
+    if path.is_dir() {
+        return Err(format!("{} is a directory", input));
+    }
+    if must_exist && !path.exists() {
+        return Err(format!("{} does not exist", input));
+    }
+    match path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+    {
+        Some(parent) if !parent.is_dir() => Err(format!("No directory {}", parent.display())),
+        _ => Ok(input.to_string()),
+    }
+}
+
+fn choice(input: &str, options: &[String]) -> Result<String, String> {
+    if let Some(exact) = options
+        .iter()
+        .find(|option| option.eq_ignore_ascii_case(input))
+    {
+        return Ok(exact.clone());
+    }
+    let prefix = input.to_lowercase();
+    let matches: Vec<&String> = options
+        .iter()
+        .filter(|option| option.to_lowercase().starts_with(&prefix))
+        .collect();
+    match matches.as_slice() {
+        [only] => Ok((*only).clone()),
+        [] => Err(format!("Choose one of {}", options.join(", "))),
+        _ => Err(format!(
+            "{} could be {}",
+            input,
+            matches
+                .iter()
+                .map(|option| option.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        )),
+    }
+}
@@ -0,0 +1,87 @@
+use std::ops::Range;
+use unicode_segmentation::UnicodeSegmentation;
+
+const MAX_TABLE_SIZE: usize = 40_000;
+
+pub fn changes(old: &str, new: &str) -> (Vec<Range<usize>>, Vec<Range<usize>>) {
+    let old_tokens = tokens(old);
+    let new_tokens = tokens(new);
+    let (n, m) = (old_tokens.len(), new_tokens.len());
+    if n.saturating_mul(m) > MAX_TABLE_SIZE {
+        return trimmed(&old_tokens, &new_tokens);
+    }
+
+    let mut lcs = vec![0u32; (n + 1) * (m + 1)];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lcs[i * (m + 1) + j] = if old_tokens[i].1 == new_tokens[j].1 {
+                lcs[(i + 1) * (m + 1) + j + 1] + 1
+            } else {
+                u32::max(lcs[(i + 1) * (m + 1) + j], lcs[i * (m + 1) + j + 1])
+            };
+        }
+    }
+
+    let (mut removed, mut added) = (Vec::new(), Vec::new());
+    let (mut i, mut j) = (0, 0);
+    while i < n || j < m {
+        if i < n && j < m && old_tokens[i].1 == new_tokens[j].1 {
+            i += 1;
+            j += 1;
+        } else if j < m && (i == n || lcs[i * (m + 1) + j + 1] >= lcs[(i + 1) * (m + 1) + j]) {
+            push(&mut added, new_tokens[j].0.clone());
+            j += 1;
+        } else {
+            push(&mut removed, old_tokens[i].0.clone());
+            i += 1;
+        }
+    }
+    (removed, added)
+}
+
+fn trimmed(
+    old: &[(Range<usize>, &str)],
+    new: &[(Range<usize>, &str)],
+) -> (Vec<Range<usize>>, Vec<Range<usize>>) {
+    let prefix = old
+        .iter()
+        .zip(new)
+        .take_while(|(old, new)| old.1 == new.1)
+        .count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(old, new)| old.1 == new.1)
+        .count();
+    let span = |tokens: &[(Range<usize>, &str)]| {
+        let middle = &tokens[prefix..tokens.len() - suffix];
+        match (middle.first(), middle.last()) {
+            (Some(first), Some(last)) => {
+                let mut ranges = Vec::new();
+                push(&mut ranges, first.0.start..last.0.end);
+                ranges
+            }
+            _ => Vec::new(),
+        }
+    };
+    (span(old), span(new))
+}
+
+fn push(ranges: &mut Vec<Range<usize>>, range: Range<usize>) {
+    match ranges.last_mut() {
+        Some(last) if last.end == range.start => last.end = range.end,
+        _ => ranges.push(range),
+    }
+}
+
+fn tokens(line: &str) -> Vec<(Range<usize>, &str)> {
+    let mut tokens: Vec<(Range<usize>, &str)> = Vec::new();
+    let mut index = 0;
+    for word in line.split_word_bounds() {
+        let len = word.graphemes(true).count();
+        tokens.push((index..index + len, word));
+        index += len;
+    }
+    tokens
+}
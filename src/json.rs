@@ -0,0 +1,43 @@
+use serde::Serialize;
+use serde_json::ser::PrettyFormatter;
+use serde_json::{Serializer, Value};
+
+pub struct SyntaxError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+pub fn format(text: &str, indent: usize) -> Result<String, SyntaxError> {
+    let value = parse(text)?;
+    let indent = " ".repeat(indent);
+    let mut formatted = Vec::new();
+    let mut serializer = Serializer::with_formatter(
+        &mut formatted,
+        PrettyFormatter::with_indent(indent.as_bytes()),
+    );
+    value.serialize(&mut serializer).map_err(to_syntax_error)?;
+    Ok(String::from_utf8_lossy(&formatted).to_string())
+}
+
+pub fn minify(text: &str) -> Result<String, SyntaxError> {
+    let value = parse(text)?;
+    serde_json::to_string(&value).map_err(to_syntax_error)
+}
+
+fn parse(text: &str) -> Result<Value, SyntaxError> {
+    serde_json::from_str(text).map_err(to_syntax_error)
+}
+
+fn to_syntax_error(err: serde_json::Error) -> SyntaxError {
+    let message = err.to_string();
+    let message = match message.rsplit_once(" at line ") {
+        Some((message, _)) => message.to_string(),
+        None => message,
+    };
+    SyntaxError {
+        line: err.line(),
+        column: err.column(),
+        message,
+    }
+}
@@ -0,0 +1,115 @@
+use crate::Document;
+
+#[derive(Default, Clone, Copy, PartialEq)]
+pub struct VisualLine {
+    pub row: usize,
+    pub segment: usize,
+}
+
+pub struct Layout {
+    width: usize,
+    tab_width: usize,
+}
+
+impl Layout {
+    pub fn new(width: usize, tab_width: usize) -> Self {
+        Self {
+            width: usize::max(width, 1),
+            tab_width,
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn segment_count(&self, len: usize) -> usize {
+        usize::max(len.div_ceil(self.width), 1)
+    }
+
+    pub fn segment_of(&self, column: usize, len: usize) -> usize {
+        usize::min(
+            column / self.width,
+            self.segment_count(len).saturating_sub(1),
+        )
+    }
+
+    pub fn column_of(&self, column: usize, len: usize) -> usize {
+        let column = column.saturating_sub(self.segment_of(column, len).saturating_mul(self.width));
+        usize::min(column, self.width.saturating_sub(1))
+    }
+
+    pub fn bounds(&self, line: VisualLine) -> (usize, usize) {
+        let start = line.segment.saturating_mul(self.width);
+        (start, start.saturating_add(self.width))
+    }
+
+    pub fn row_width(&self, document: &Document, y: usize) -> usize {
+        document
+            .row(y)
+            .map_or(0, |row| row.display_width(self.tab_width))
+    }
+
+    pub fn column_at(&self, document: &Document, x: usize, y: usize) -> usize {
+        document
+            .row(y)
+            .map_or(0, |row| row.display_column(x, self.tab_width))
+    }
+
+    pub fn grapheme_at(&self, document: &Document, column: usize, y: usize) -> usize {
+        document
+            .row(y)
+            .map_or(0, |row| row.grapheme_at_column(column, self.tab_width))
+    }
+
+    pub fn line_at(&self, document: &Document, x: usize, y: usize) -> VisualLine {
+        VisualLine {
+            row: y,
+            segment: self.segment_of(self.column_at(document, x, y), self.row_width(document, y)),
+        }
+    }
+
+    pub fn next(&self, document: &Document, line: VisualLine) -> Option<VisualLine> {
+        document.row(line.row)?;
+        if line.segment.saturating_add(1) < self.segment_count(self.row_width(document, line.row)) {
+            return Some(VisualLine {
+                row: line.row,
+                segment: line.segment.saturating_add(1),
+            });
+        }
+        document.row(line.row.saturating_add(1))?;
+        Some(VisualLine {
+            row: line.row.saturating_add(1),
+            segment: 0,
+        })
+    }
+
+    pub fn previous(&self, document: &Document, line: VisualLine) -> Option<VisualLine> {
+        if line.segment > 0 {
+            return Some(VisualLine {
+                row: line.row,
+                segment: line.segment.saturating_sub(1),
+            });
+        }
+        let row = line.row.checked_sub(1)?;
+        Some(VisualLine {
+            row,
+            segment: self
+                .segment_count(self.row_width(document, row))
+                .saturating_sub(1),
+        })
+    }
+
+    pub fn distance(&self, document: &Document, from: VisualLine, to: VisualLine) -> usize {
+        let mut distance = 0;
+        let mut line = from;
+        while line != to {
+            distance += 1;
+            match self.next(document, line) {
+                Some(next) => line = next,
+                None => break,
+            }
+        }
+        distance
+    }
+}
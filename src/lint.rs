@@ -0,0 +1,35 @@
+use crate::json;
+use crate::transform;
+
+pub fn check(text: &str, extension: Option<&str>, command: Option<&str>) -> Option<String> {
+    if let Some(command) = command {
+        return run(command, text);
+    }
+    if extension == Some("json") {
+        return json::minify(text)
+            .err()
+            .map(|err| format!("line {}, column {}: {}", err.line, err.column, err.message));
+    }
+    None
+}
+
+fn run(command: &str, text: &str) -> Option<String> {
+    let output = match transform::run_with_input(transform::shell(command), text) {
+        Ok(output) => output,
+        Err(err) => return Some(format!("could not run `{}`: {}", command, err)),
+    };
+    if output.status.success() {
+        return None;
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let line = stderr
+        .lines()
+        .chain(stdout.lines())
+        .map(str::trim)
+        .find(|line| !line.is_empty());
+    Some(line.map_or_else(
+        || format!("`{}` exited with {}", command, output.status),
+        str::to_string,
+    ))
+}
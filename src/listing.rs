@@ -0,0 +1,64 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+pub const PARENT: &str = "../";
+const HEADER_ROWS: usize = 2;
+
+pub struct Listing {
+    pub dir: PathBuf,
+}
+
+impl Listing {
+    pub fn new(dir: &Path) -> io::Result<Self> {
+        Ok(Self {
+            dir: fs::canonicalize(dir)?,
+        })
+    }
+
+    pub fn contents(&self) -> io::Result<String> {
+        let mut children: Vec<(bool, String)> = fs::read_dir(&self.dir)?
+            .filter_map(Result::ok)
+            .map(|entry| {
+                let name = entry.file_name().to_string_lossy().into_owned();
+                (!entry.path().is_dir(), name)
+            })
+            .collect();
+        children.sort();
+
+        let mut lines = vec![format!("\" {}", self.dir.display()), PARENT.to_string()];
+        lines.extend(children.into_iter().map(
+            |(is_file, name)| {
+                if is_file {
+                    name
+                } else {
+                    format!("{}/", name)
+                }
+            },
+        ));
+        Ok(lines.join("\n"))
+    }
+
+    pub fn entry(&self, line: &str, index: usize) -> Option<PathBuf> {
+        if index + 1 < HEADER_ROWS {
+            None
+        } else if line == PARENT {
+            self.parent()
+        } else {
+            Some(self.dir.join(line.trim_end_matches('/')))
+        }
+    }
+
+    pub fn parent(&self) -> Option<PathBuf> {
+        self.dir.parent().map(Path::to_path_buf)
+    }
+
+    pub fn row_of(&self, contents: &str, path: &Path) -> Option<usize> {
+        let name = path.file_name()?.to_string_lossy();
+        contents
+            .lines()
+            .skip(HEADER_ROWS)
+            .position(|line| line.trim_end_matches('/') == name)
+            .map(|row| row + HEADER_ROWS)
+    }
+}
@@ -0,0 +1,96 @@
+use std::fs::{self, OpenOptions};
+use std::io::{Error, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::process::{self, Command, Stdio};
+
+pub struct Lock {
+    path: PathBuf,
+}
+
+impl Lock {
+    pub fn acquire(filename: &str, steal: bool) -> Result<Self, Error> {
+        let path = sibling(filename, "texty-lock");
+        if steal {
+            fs::write(&path, format!("{}\n", process::id()))?;
+            return Ok(Self { path });
+        }
+        match create(&path) {
+            Err(err) if err.kind() == ErrorKind::AlreadyExists => {
+                if read_pid(&path).is_some_and(|pid| pid != process::id() && is_running(pid)) {
+                    return Err(err);
+                }
+                fs::remove_file(&path).ok();
+                create(&path)?;
+            }
+            result => result?,
+        }
+        Ok(Self { path })
+    }
+
+    pub fn release(&self) {
+        if read_pid(&self.path) == Some(process::id()) {
+            fs::remove_file(&self.path).ok();
+        }
+    }
+
+    pub fn holder(filename: &str) -> Option<String> {
+        let lock = sibling(filename, "texty-lock");
+        if let Some(pid) = read_pid(&lock) {
+            if pid != process::id() && is_running(pid) {
+                return Some(format!("texty (pid {})", pid));
+            }
+        }
+        let swap = sibling(filename, "swp");
+        swap.exists()
+            .then(|| format!("vim (swap file {})", swap.display()))
+    }
+}
+
+impl Drop for Lock {
+    fn drop(&mut self) {
+        self.release();
+    }
+}
+
+fn create(path: &Path) -> Result<(), Error> {
+    let mut file = OpenOptions::new().write(true).create_new(true).open(path)?;
+    writeln!(file, "{}", process::id())
+}
+
+fn read_pid(path: &Path) -> Option<u32> {
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|contents| contents.trim().parse::<u32>().ok())
+}
+
+pub fn sibling(filename: &str, extension: &str) -> PathBuf {
+    let path = Path::new(filename);
+    let name = path
+        .file_name()
+        .map_or_else(String::new, |name| name.to_string_lossy().into_owned());
+    path.with_file_name(format!(".{}.{}", name, extension))
+}
+
+pub fn is_running(pid: u32) -> bool {
+    if cfg!(target_os = "linux") {
+        return Path::new("/proc").join(pid.to_string()).exists();
+    }
+    let output = if cfg!(windows) {
+        Command::new("tasklist")
+            .args(["/FI", &format!("PID eq {}", pid), "/NH"])
+            .stderr(Stdio::null())
+            .output()
+    } else {
+        Command::new("kill")
+            .args(["-0", &pid.to_string()])
+            .stderr(Stdio::null())
+            .output()
+    };
+    match output {
+        Ok(output) if cfg!(windows) => {
+            String::from_utf8_lossy(&output.stdout).contains(&format!(" {} ", pid))
+        }
+        Ok(output) => output.status.success(),
+        Err(_) => true,
+    }
+}
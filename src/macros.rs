@@ -0,0 +1,102 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+const NAMED_KEYS: [(&str, KeyCode); 13] = [
+    ("CR", KeyCode::Enter),
+    ("Esc", KeyCode::Esc),
+    ("BS", KeyCode::Backspace),
+    ("Del", KeyCode::Delete),
+    ("Tab", KeyCode::Tab),
+    ("Up", KeyCode::Up),
+    ("Down", KeyCode::Down),
+    ("Left", KeyCode::Left),
+    ("Right", KeyCode::Right),
+    ("Home", KeyCode::Home),
+    ("End", KeyCode::End),
+    ("PageUp", KeyCode::PageUp),
+    ("PageDown", KeyCode::PageDown),
+];
+
+pub fn to_notation(keys: &[KeyEvent]) -> String {
+    let mut notation = String::new();
+    for key in keys {
+        let mut prefix = String::new();
+        if key.modifiers.contains(KeyModifiers::CONTROL) {
+            prefix.push_str("C-");
+        }
+        if key.modifiers.contains(KeyModifiers::ALT) {
+            prefix.push_str("A-");
+        }
+
+        let name = match key.code {
+            KeyCode::Char('<') => String::from("lt"),
+            KeyCode::Char(c) if prefix.is_empty() => {
+                notation.push(c);
+                continue;
+            }
+            KeyCode::Char(c) => c.to_string(),
+            code => match NAMED_KEYS.iter().find(|(_, named)| *named == code) {
+                Some((name, _)) => name.to_string(),
+                None => continue,
+            },
+        };
+        notation.push_str(&format!("<{}{}>", prefix, name));
+    }
+    notation
+}
+
+pub fn from_notation(notation: &str) -> Result<Vec<KeyEvent>, String> {
+    let mut keys = Vec::new();
+    let mut rest = notation;
+
+    while let Some(c) = rest.chars().next() {
+        if c == '<' {
+            if let Some(end) = rest.find('>') {
+                keys.push(parse_key(&rest[1..end])?);
+                rest = &rest[end + 1..];
+                continue;
+            }
+        }
+        keys.push(KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE));
+        rest = &rest[c.len_utf8()..];
+    }
+
+    Ok(keys)
+}
+
+fn parse_key(name: &str) -> Result<KeyEvent, String> {
+    let mut modifiers = KeyModifiers::NONE;
+    let mut name = name;
+    loop {
+        if let Some(rest) = name.strip_prefix("C-") {
+            modifiers |= KeyModifiers::CONTROL;
+            name = rest;
+        } else if let Some(rest) = name.strip_prefix("A-") {
+            modifiers |= KeyModifiers::ALT;
+            name = rest;
+        } else {
+            break;
+        }
+    }
+
+    if name.eq_ignore_ascii_case("lt") {
+        return Ok(KeyEvent::new(KeyCode::Char('<'), modifiers));
+    }
+    if let Some((_, code)) = NAMED_KEYS
+        .iter()
+        .find(|(named, _)| named.eq_ignore_ascii_case(name))
+    {
+        return Ok(KeyEvent::new(*code, modifiers));
+    }
+    let mut chars = name.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Ok(KeyEvent::new(KeyCode::Char(c), modifiers)),
+        _ => Err(format!("Unknown key <{}>", name)),
+    }
+}
+
+pub fn single_key(notation: &str) -> Result<String, String> {
+    match from_notation(notation)?.as_slice() {
+        [key] => Ok(to_notation(&[*key])),
+        _ => Err(format!("Expected a single key, got {}", notation)),
+    }
+}
@@ -1,20 +1,157 @@
+mod action;
+mod ansi;
+mod backup;
+mod batch;
+mod capabilities;
+mod cli;
+mod clipboard;
+mod clock;
+mod commands;
+mod completion;
+mod config;
+mod dialog;
+mod diff;
+mod diff_view;
 mod document;
 mod editor;
+mod events;
+mod ex;
+mod file_tree;
 mod filetype;
+mod finder;
+mod fuzzy;
+mod git;
 mod highlighting;
+mod history;
+mod hooks;
+mod input;
+mod intraline;
+mod json;
+mod layout;
+mod lint;
+mod listing;
+mod lock;
+mod macros;
+mod outline;
+mod overlay;
+mod paths;
+mod quickfix;
+mod refs;
+mod registers;
+mod review;
 mod row;
+mod row_store;
+mod search;
+mod session;
+mod status_commands;
+mod swap;
+mod table;
+mod tail;
+mod templates;
 mod terminal;
+mod theme;
+mod timings;
+mod transform;
+mod trash;
+mod undo;
+mod watch;
+mod window;
 
+pub use action::Action;
+pub use action::Motion;
+pub use action::Operator;
+pub use capabilities::Capabilities;
+pub use cli::Args;
+pub use commands::UserCommand;
+pub use config::AutosaveTarget;
+pub use config::Backup;
+pub use config::Config;
+pub use config::LineNumbers;
+pub use config::SaveGuard;
+pub use dialog::Dialog;
+pub use diff_view::DiffView;
+pub use document::DiskStamp;
 pub use document::Document;
+pub use document::ShiftDirection;
 use editor::Editor;
 pub use editor::Position;
 pub use editor::SearchDirection;
+pub use events::EditorEvent;
+pub use events::EventBus;
+pub use file_tree::FileTree;
 pub use filetype::FileType;
 pub use filetype::HighlightingOptions;
+pub use history::History;
+pub use history::HistoryKind;
+pub use layout::Layout;
+pub use layout::VisualLine;
+pub use listing::Listing;
+pub use lock::Lock;
+pub use overlay::Overlay;
+pub use quickfix::Quickfix;
+pub use registers::Register;
+pub use registers::Registers;
+pub use review::Review;
 pub use row::Row;
+pub use row_store::RowStore;
+pub use search::Matcher;
+pub use search::SearchProviders;
+pub use session::Session;
+use std::io::{self, IsTerminal};
+use std::process;
+pub use swap::Swap;
+pub use templates::Templates;
+pub use terminal::ColourSupport;
 pub use terminal::Size;
 pub use terminal::Terminal;
+pub use theme::Theme;
+pub use timings::Timings;
+pub use trash::Trash;
+pub use undo::Change;
+pub use undo::UndoHistory;
+pub use watch::Watcher;
+pub use window::Window;
+
+const SNAPSHOT_WIDTH: u16 = 80;
+const SNAPSHOT_HEIGHT: u16 = 24;
 
 fn main() {
-    Editor::default().run();
+    let args = Args::parse_env();
+    if let Some(script) = &args.batch {
+        if let Err(err) = batch::run(script, args.filename()) {
+            eprintln!("texty: {}", err);
+            process::exit(1);
+        }
+        return;
+    }
+    if let Some(script) = &args.snapshot {
+        let result = macros::from_notation(script).and_then(|keys| {
+            Editor::headless(&args, SNAPSHOT_WIDTH, SNAPSHOT_HEIGHT)
+                .snapshot(keys)
+                .map_err(|err| err.to_string())
+        });
+        match result {
+            Ok(screen) => print!("{}", screen),
+            Err(err) => {
+                eprintln!("texty: {}", err);
+                process::exit(1);
+            }
+        }
+        return;
+    }
+    if !io::stdout().is_terminal() {
+        eprintln!("texty: stdout is not a terminal; use --batch '<commands>' <file> to edit non-interactively");
+        process::exit(1);
+    }
+
+    if args.tail {
+        let filenames: Vec<&str> = args.files.iter().map(String::as_str).collect();
+        if let Err(err) = tail::run(&filenames) {
+            eprintln!("texty: {}", err);
+            process::exit(1);
+        }
+        return;
+    }
+
+    Editor::new(&args).run();
 }
@@ -0,0 +1,36 @@
+use regex::Regex;
+
+pub struct Symbol {
+    pub line: usize,
+    pub name: String,
+    pub label: String,
+}
+
+pub fn compile(patterns: &[String]) -> Result<Vec<Regex>, String> {
+    patterns
+        .iter()
+        .map(|pattern| {
+            Regex::new(pattern).map_err(|err| format!("invalid outline pattern: {}", err))
+        })
+        .collect()
+}
+
+pub fn symbols<'a, I>(patterns: &[Regex], lines: I) -> Vec<Symbol>
+where
+    I: Iterator<Item = &'a str>,
+{
+    let mut symbols = Vec::new();
+    for (line, text) in lines.enumerate() {
+        let Some(captures) = patterns.iter().find_map(|pattern| pattern.captures(text)) else {
+            continue;
+        };
+        let whole = captures.get(0).map_or("", |found| found.as_str());
+        let name = captures.get(1).map_or(whole, |found| found.as_str());
+        symbols.push(Symbol {
+            line,
+            name: name.trim().to_string(),
+            label: text.trim().trim_end_matches('{').trim_end().to_string(),
+        });
+    }
+    symbols
+}
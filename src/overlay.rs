@@ -0,0 +1,76 @@
+use crate::Theme;
+use crossterm::style::{Colors, ResetColor, SetBackgroundColor, SetColors};
+use std::ops::Range;
+use unicode_segmentation::UnicodeSegmentation;
+
+pub struct Overlay {
+    pub title: String,
+    pub items: Vec<String>,
+    pub selected: usize,
+    pub height: Option<usize>,
+    first: usize,
+}
+
+impl Overlay {
+    pub fn new(title: &str) -> Self {
+        Self {
+            title: title.to_string(),
+            items: Vec::new(),
+            selected: 0,
+            height: None,
+            first: 0,
+        }
+    }
+
+    // `items` only holds a window of the matches, so `selected` can point at any of `total`
+    // matches; the returned range says which of them to list.
+    pub fn window(&mut self, total: usize, limit: usize) -> Range<usize> {
+        self.selected = usize::min(self.selected, total.saturating_sub(1));
+        self.first = usize::min(
+            self.selected.saturating_sub(limit / 2),
+            total.saturating_sub(limit),
+        );
+        self.first..usize::min(self.first.saturating_add(limit), total)
+    }
+
+    pub fn draw(&self, lines: &mut [String], width: usize, theme: &Theme) {
+        let start = self
+            .height
+            .map_or(0, |height| lines.len().saturating_sub(height));
+        let Some((header, rows)) = lines[start..].split_first_mut() else {
+            return;
+        };
+        *header = format!(
+            "{}{}{}",
+            SetColors(Colors::new(theme.status_fg.0, theme.status_bg.0)),
+            fit(&self.title, width),
+            ResetColor
+        );
+        let offset = self.selected.saturating_sub(rows.len().saturating_sub(1));
+        for (index, line) in rows.iter_mut().enumerate() {
+            let index = index.saturating_add(offset);
+            let item = index
+                .checked_sub(self.first)
+                .and_then(|index| self.items.get(index));
+            *line = match item {
+                Some(item) if index == self.selected => format!(
+                    "{}{}{}",
+                    SetBackgroundColor(theme.selection_bg.0),
+                    fit(&format!("> {}", item), width),
+                    ResetColor
+                ),
+                Some(item) => fit(&format!("  {}", item), width),
+                None => String::new(),
+            };
+        }
+    }
+}
+
+fn fit(text: &str, width: usize) -> String {
+    let graphemes: Vec<&str> = text.graphemes(true).collect();
+    if graphemes.len() <= width {
+        return format!("{:<width$}", text, width = width);
+    }
+    let start = graphemes.len().saturating_sub(width.saturating_sub(1));
+    format!("…{}", graphemes[start..].concat())
+}
@@ -0,0 +1,103 @@
+use std::env;
+use std::fs;
+use std::io::Error;
+use std::path::PathBuf;
+
+const APP: &str = "texty";
+
+pub fn config_dir() -> Option<PathBuf> {
+    if let Some(dir) = env::var_os("TEXTY_CONFIG_DIR") {
+        return Some(PathBuf::from(dir));
+    }
+    if let Some(dir) = env::var_os("XDG_CONFIG_HOME") {
+        return Some(PathBuf::from(dir).join(APP));
+    }
+    let dir = platform_config_dir()?;
+    match legacy_dir(".config") {
+        Some(legacy) if !dir.exists() && legacy.exists() => Some(legacy),
+        _ => Some(dir),
+    }
+}
+
+pub fn state_dir() -> Option<PathBuf> {
+    if let Some(dir) = env::var_os("TEXTY_STATE_DIR") {
+        return Some(PathBuf::from(dir));
+    }
+    if let Some(dir) = env::var_os("XDG_STATE_HOME") {
+        return Some(PathBuf::from(dir).join(APP));
+    }
+    platform_state_dir()
+}
+
+pub fn cache_dir() -> Option<PathBuf> {
+    if let Some(dir) = env::var_os("TEXTY_CACHE_DIR") {
+        return Some(PathBuf::from(dir));
+    }
+    if let Some(dir) = env::var_os("XDG_CACHE_HOME") {
+        return Some(PathBuf::from(dir).join(APP));
+    }
+    platform_cache_dir()
+}
+
+pub fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), home()) {
+        (Some(rest), Some(home)) => home.join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+pub fn migrate() -> Result<(), Error> {
+    let (Some(legacy), Some(state)) = (legacy_dir(".local/state"), state_dir()) else {
+        return Ok(());
+    };
+    if legacy == state || !legacy.is_dir() || state.exists() {
+        return Ok(());
+    }
+    if let Some(parent) = state.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::rename(legacy, state)
+}
+
+fn home() -> Option<PathBuf> {
+    env::var_os("HOME").map(PathBuf::from)
+}
+
+fn legacy_dir(base: &str) -> Option<PathBuf> {
+    Some(home()?.join(base).join(APP))
+}
+
+fn platform_config_dir() -> Option<PathBuf> {
+    if cfg!(windows) {
+        env::var_os("APPDATA").map(|dir| PathBuf::from(dir).join(APP))
+    } else if cfg!(target_os = "macos") {
+        Some(home()?.join("Library/Application Support").join(APP))
+    } else {
+        Some(home()?.join(".config").join(APP))
+    }
+}
+
+fn platform_state_dir() -> Option<PathBuf> {
+    if cfg!(windows) {
+        env::var_os("LOCALAPPDATA").map(|dir| PathBuf::from(dir).join(APP).join("state"))
+    } else if cfg!(target_os = "macos") {
+        Some(
+            home()?
+                .join("Library/Application Support")
+                .join(APP)
+                .join("state"),
+        )
+    } else {
+        Some(home()?.join(".local/state").join(APP))
+    }
+}
+
+fn platform_cache_dir() -> Option<PathBuf> {
+    if cfg!(windows) {
+        env::var_os("LOCALAPPDATA").map(|dir| PathBuf::from(dir).join(APP).join("cache"))
+    } else if cfg!(target_os = "macos") {
+        Some(home()?.join("Library/Caches").join(APP))
+    } else {
+        Some(home()?.join(".cache").join(APP))
+    }
+}
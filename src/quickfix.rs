@@ -0,0 +1,80 @@
+use crate::Position;
+
+pub struct Entry {
+    pub filename: Option<String>,
+    pub position: Position,
+    pub message: String,
+}
+
+impl Entry {
+    pub fn label(&self) -> String {
+        match &self.filename {
+            Some(filename) => format!("{}:{}", filename, self.message),
+            None => self.message.clone(),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct Quickfix {
+    entries: Vec<Entry>,
+    current: usize,
+}
+
+impl Quickfix {
+    pub fn set(&mut self, entries: Vec<Entry>) {
+        self.entries = entries;
+        self.current = 0;
+    }
+
+    pub fn retain<F>(&mut self, keep: F)
+    where
+        F: FnMut(&Entry) -> bool,
+    {
+        self.entries.retain(keep);
+        self.current = 0;
+    }
+
+    pub fn clear(&mut self) {
+        self.set(Vec::new());
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn index(&self) -> usize {
+        self.current
+    }
+
+    pub fn entries(&self) -> &[Entry] {
+        &self.entries
+    }
+
+    pub fn current(&self) -> Option<&Entry> {
+        self.entries.get(self.current)
+    }
+
+    pub fn select(&mut self, index: usize) -> Option<&Entry> {
+        if index < self.entries.len() {
+            self.current = index;
+        }
+        self.current()
+    }
+
+    pub fn select_next(&mut self) -> Option<&Entry> {
+        if self.current.saturating_add(1) < self.entries.len() {
+            self.current += 1;
+        }
+        self.current()
+    }
+
+    pub fn select_previous(&mut self) -> Option<&Entry> {
+        self.current = self.current.saturating_sub(1);
+        self.current()
+    }
+}
@@ -0,0 +1,141 @@
+use std::path::{Component, Path, PathBuf};
+
+pub struct Edit {
+    pub line: usize,
+    pub start: usize,
+    pub old: String,
+    pub new: String,
+}
+
+pub fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => (),
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+pub fn relative(from_dir: &Path, to: &Path) -> PathBuf {
+    let from: Vec<Component> = from_dir.components().collect();
+    let to: Vec<Component> = to.components().collect();
+    let common = from.iter().zip(&to).take_while(|(a, b)| a == b).count();
+    let mut relative = PathBuf::new();
+    for _ in common..from.len() {
+        relative.push("..");
+    }
+    for component in &to[common..] {
+        relative.push(component);
+    }
+    relative
+}
+
+fn forms(root: &Path, dir: &Path, path: &Path) -> Vec<String> {
+    let slashed = |path: &Path| path.to_string_lossy().replace('\\', "/");
+    let from_dir = slashed(&relative(dir, path));
+    let from_root = slashed(&relative(root, path));
+    let mut forms = vec![
+        format!("/{}", from_root),
+        from_root.clone(),
+        if from_dir.starts_with("../") {
+            from_dir.clone()
+        } else {
+            format!("./{}", from_dir)
+        },
+        from_dir.clone(),
+    ];
+    if path.extension().is_some() {
+        let without = |form: &str| {
+            form.rsplit_once('.')
+                .filter(|(stem, _)| !stem.ends_with('/') && !stem.is_empty())
+                .map_or_else(|| form.to_string(), |(stem, _)| stem.to_string())
+        };
+        let stems: Vec<String> = forms.iter().map(|form| without(form)).collect();
+        forms.extend(stems);
+    }
+    if path.extension().is_some_and(|extension| extension == "py") {
+        forms.push(
+            from_root
+                .trim_end_matches(".py")
+                .trim_end_matches("/__init__")
+                .replace('/', "."),
+        );
+    }
+    for form in &mut forms {
+        if !form.contains(['/', '.']) {
+            form.clear();
+        }
+    }
+    forms
+}
+
+pub fn replacements(root: &Path, dir: &Path, old: &Path, new: &Path) -> Vec<(String, String)> {
+    let old_forms = forms(root, dir, old);
+    let count = if old.extension() == new.extension() {
+        old_forms.len()
+    } else {
+        4
+    };
+    let mut pairs: Vec<(String, String)> = old_forms
+        .into_iter()
+        .zip(forms(root, dir, new))
+        .take(count)
+        .filter(|(old, new)| !old.is_empty() && !new.is_empty() && old != new)
+        .collect();
+    pairs.sort_by(|a, b| b.0.len().cmp(&a.0.len()).then_with(|| a.0.cmp(&b.0)));
+    pairs.dedup_by(|a, b| a.0 == b.0);
+    pairs
+}
+
+fn is_path_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '_' | '-' | '/' | '.')
+}
+
+pub fn find(text: &str, pairs: &[(String, String)]) -> Vec<Edit> {
+    let mut edits = Vec::new();
+    for (line, content) in text.lines().enumerate() {
+        let mut start = 0;
+        while start < content.len() {
+            let rest = &content[start..];
+            let before = content[..start].chars().next_back();
+            let found = pairs.iter().find(|(old, _)| {
+                rest.starts_with(old.as_str()) && !before.is_some_and(is_path_char) && {
+                    let mut after = rest[old.len()..].chars();
+                    match after.next() {
+                        Some('.') => !after.next().is_some_and(char::is_alphanumeric),
+                        Some(c) => !is_path_char(c),
+                        None => true,
+                    }
+                }
+            });
+            match found {
+                Some((old, new)) => {
+                    edits.push(Edit {
+                        line,
+                        start,
+                        old: old.clone(),
+                        new: new.clone(),
+                    });
+                    start += old.len();
+                }
+                None => start += rest.chars().next().map_or(1, char::len_utf8),
+            }
+        }
+    }
+    edits
+}
+
+pub fn apply(text: &str, edits: &[Edit]) -> String {
+    let mut lines: Vec<String> = text.split_inclusive('\n').map(str::to_string).collect();
+    for edit in edits.iter().rev() {
+        if let Some(line) = lines.get_mut(edit.line) {
+            line.replace_range(edit.start..edit.start + edit.old.len(), &edit.new);
+        }
+    }
+    lines.concat()
+}
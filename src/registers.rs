@@ -0,0 +1,140 @@
+use crate::paths;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::fs;
+use std::io::Error;
+use std::path::PathBuf;
+
+const DELETE_HISTORY: usize = 9;
+const SHARED_FILE: &str = "registers.json";
+
+#[derive(Default, Clone, Serialize, Deserialize)]
+pub struct Register {
+    pub text: String,
+    pub linewise: bool,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+pub struct Registers {
+    unnamed: Register,
+    yanked: Option<Register>,
+    deleted: VecDeque<Register>,
+    #[serde(default)]
+    small_delete: Option<Register>,
+    named: HashMap<char, Register>,
+    #[serde(skip)]
+    path: Option<PathBuf>,
+    #[serde(skip)]
+    dirty: bool,
+}
+
+impl Register {
+    pub fn new(text: String, linewise: bool) -> Self {
+        Self { text, linewise }
+    }
+}
+
+impl Registers {
+    pub fn shared() -> Self {
+        let mut registers = Self {
+            path: paths::state_dir().map(|dir| dir.join(SHARED_FILE)),
+            ..Self::default()
+        };
+        registers.sync();
+        registers
+    }
+
+    // The file is small, so it is read again on every sync rather than trusting its mtime,
+    // which can stay the same across two quick writes. Unsaved local changes win.
+    pub fn sync(&mut self) {
+        if self.dirty {
+            return;
+        }
+        let Some(path) = self.path.take() else {
+            return;
+        };
+        let shared = fs::read_to_string(&path)
+            .ok()
+            .and_then(|contents| serde_json::from_str::<Self>(&contents).ok());
+        if let Some(shared) = shared {
+            *self = shared;
+        }
+        self.path = Some(path);
+    }
+
+    pub fn flush(&mut self) -> Result<(), Error> {
+        if !self.dirty {
+            return Ok(());
+        }
+        self.dirty = false;
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let temporary = path.with_extension(format!("{}.tmp", std::process::id()));
+        fs::write(&temporary, serde_json::to_string(&*self)?)?;
+        fs::rename(&temporary, path)
+    }
+
+    pub fn is_valid(name: char) -> bool {
+        matches!(name, '"' | '-') || name.is_ascii_alphanumeric()
+    }
+
+    pub fn yank(&mut self, name: Option<char>, register: Register) {
+        self.sync();
+        if name.is_none() {
+            self.yanked = Some(register.clone());
+        }
+        self.store(name, register);
+    }
+
+    pub fn delete(&mut self, name: Option<char>, register: Register) {
+        self.sync();
+        if name.is_none() {
+            if register.linewise || register.text.contains('\n') {
+                self.deleted.push_front(register.clone());
+                self.deleted.truncate(DELETE_HISTORY);
+            } else {
+                self.small_delete = Some(register.clone());
+            }
+        }
+        self.store(name, register);
+    }
+
+    fn store(&mut self, name: Option<char>, register: Register) {
+        self.dirty = true;
+        match name {
+            Some(name) if name.is_ascii_uppercase() => {
+                let existing = self.named.entry(name.to_ascii_lowercase()).or_default();
+                existing.text.push_str(&register.text);
+                existing.linewise |= register.linewise;
+                self.unnamed = existing.clone();
+            }
+            Some(name) if name.is_ascii_lowercase() => {
+                self.named.insert(name, register.clone());
+                self.unnamed = register;
+            }
+            _ => self.unnamed = register,
+        }
+    }
+
+    pub fn get(&self, name: Option<char>) -> Option<&Register> {
+        let register = match name {
+            None | Some('"') => Some(&self.unnamed),
+            Some('0') => self.yanked.as_ref(),
+            Some('-') => self.small_delete.as_ref(),
+            Some(name @ '1'..='9') => self.deleted.get(name as usize - '1' as usize),
+            Some(name) => self.named.get(&name.to_ascii_lowercase()),
+        };
+        register.filter(|register| !register.text.is_empty())
+    }
+
+    pub fn list(&self) -> Vec<(char, &Register)> {
+        let names = ['"', '-'].into_iter().chain('0'..='9').chain('a'..='z');
+        names
+            .filter_map(|name| self.get(Some(name)).map(|register| (name, register)))
+            .collect()
+    }
+}
@@ -0,0 +1,109 @@
+use crate::Change;
+use crate::Position;
+use crate::SearchDirection;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Error;
+use std::path::PathBuf;
+
+#[derive(Default)]
+pub struct Review {
+    notes: BTreeMap<usize, String>,
+}
+
+impl Review {
+    pub fn sidecar(filename: &str) -> PathBuf {
+        PathBuf::from(format!("{}.review", filename))
+    }
+
+    pub fn load(filename: &str) -> Self {
+        let Ok(contents) = fs::read_to_string(Self::sidecar(filename)) else {
+            return Self::default();
+        };
+        let notes = contents
+            .lines()
+            .filter_map(|line| {
+                let (number, comment) = line.split_once('\t')?;
+                let number: usize = number.parse().ok()?;
+                Some((number.checked_sub(1)?, comment.to_string()))
+            })
+            .collect();
+        Self { notes }
+    }
+
+    pub fn save(&self, filename: &str) -> Result<(), Error> {
+        let path = Self::sidecar(filename);
+        if self.notes.is_empty() {
+            return match fs::remove_file(path) {
+                Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err),
+                _ => Ok(()),
+            };
+        }
+        let contents: String = self
+            .notes
+            .iter()
+            .map(|(y, comment)| format!("{}\t{}\n", y.saturating_add(1), comment))
+            .collect();
+        fs::write(path, contents)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.notes.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.notes.len()
+    }
+
+    pub fn get(&self, y: usize) -> Option<&str> {
+        self.notes.get(&y).map(String::as_str)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (usize, &str)> {
+        self.notes.iter().map(|(&y, comment)| (y, comment.as_str()))
+    }
+
+    pub fn set(&mut self, y: usize, comment: &str) {
+        self.notes.insert(y, comment.to_string());
+    }
+
+    pub fn remove(&mut self, y: usize) -> bool {
+        self.notes.remove(&y).is_some()
+    }
+
+    pub fn next(&self, y: usize, direction: SearchDirection) -> Option<usize> {
+        match direction {
+            SearchDirection::Forward => self.notes.range(y.saturating_add(1)..).next(),
+            SearchDirection::Backward => self.notes.range(..y).next_back(),
+        }
+        .map(|(&y, _)| y)
+    }
+
+    pub fn remap(&mut self, change: &Change) {
+        let start = change.start;
+        let end = change.removed_end();
+        self.notes = std::mem::take(&mut self.notes)
+            .into_iter()
+            .filter(|&(y, _)| {
+                let inside = (start.y, start.x) <= (y, 0) && (y, 0) < (end.y, end.x);
+                let whole_line = y < end.y && (y > start.y || start.x == 0);
+                !(inside && whole_line)
+            })
+            .map(|(y, comment)| (change.map(Position { x: 0, y }).y, comment))
+            .collect();
+    }
+
+    pub fn to_markdown(&self, filename: &str, line: impl Fn(usize) -> String) -> String {
+        let mut report = format!("# Review of {}\n", filename);
+        for (y, comment) in self.iter() {
+            report.push_str(&format!(
+                "\n## {}:{}\n\n```\n{}\n```\n\n{}\n",
+                filename,
+                y.saturating_add(1),
+                line(y),
+                comment
+            ));
+        }
+        report
+    }
+}
@@ -1,5 +1,5 @@
-use crate::{highlighting, HighlightingOptions, SearchDirection};
-use crossterm::style::{Color, SetForegroundColor};
+use crate::{highlighting, HighlightingOptions, Matcher, SearchDirection, Theme};
+use crossterm::style::{Attribute, Color, SetAttribute, SetBackgroundColor, SetForegroundColor};
 use std::cmp;
 use unicode_segmentation::UnicodeSegmentation;
 
@@ -7,6 +7,10 @@ use unicode_segmentation::UnicodeSegmentation;
 pub struct Row {
     content: String,
     highlighting: Vec<highlighting::Type>,
+    is_highlighted: bool,
+    starts_in_comment: bool,
+    ends_in_comment: bool,
+    colours: Vec<(usize, usize, Color)>,
     len: usize,
 }
 
@@ -15,166 +19,241 @@ impl From<&str> for Row {
         Self {
             content: String::from(slice),
             highlighting: Vec::new(),
+            is_highlighted: false,
+            starts_in_comment: false,
+            ends_in_comment: false,
+            colours: Vec::new(),
             len: slice[..].graphemes(true).count(),
         }
     }
 }
 
 impl Row {
-    pub fn render(&self, start: usize, end: usize) -> String {
-        let end = cmp::min(end, self.content.len());
-        let start = cmp::min(start, end);
+    pub fn render(
+        &self,
+        start: usize,
+        end: usize,
+        selection: Option<(usize, usize)>,
+        matches: &[(usize, usize, highlighting::Type)],
+        tab_width: usize,
+        theme: &Theme,
+    ) -> String {
         let mut rendered = String::new();
         let mut current_highlighting = &highlighting::Type::None;
+        let mut selected = false;
+        let mut column: usize = 0;
+
+        for (i, grapheme) in self.content[..].graphemes(true).enumerate() {
+            if column >= end {
+                break;
+            }
+            let next_column = column.saturating_add(grapheme_width(grapheme, column, tab_width));
+            if next_column <= start {
+                column = next_column;
+                continue;
+            }
 
-        for (i, grapheme) in self.content[..]
-            .graphemes(true)
-            .enumerate()
-            .skip(start)
-            .take(end - start)
-        {
             if let Some(c) = grapheme.chars().next() {
-                let highlighting_type = self
-                    .highlighting
-                    .get(i)
-                    .unwrap_or(&highlighting::Type::None);
+                let is_selected = selection.is_some_and(|(from, to)| from <= i && i < to);
+                if is_selected != selected {
+                    selected = is_selected;
+                    let colour = if selected {
+                        theme.selection_bg.0
+                    } else {
+                        Color::Reset
+                    };
+                    rendered.push_str(format!("{}", SetBackgroundColor(colour)).as_str());
+                }
+
+                let highlighting_type = matches
+                    .iter()
+                    .find(|(from, to, _)| *from <= i && i < *to)
+                    .map_or_else(
+                        || {
+                            self.highlighting
+                                .get(i)
+                                .unwrap_or(&highlighting::Type::None)
+                        },
+                        |(_, _, highlighting_type)| highlighting_type,
+                    );
                 if highlighting_type != current_highlighting {
+                    if current_highlighting.attribute(theme).is_some() {
+                        rendered.push_str(&format!(
+                            "{}{}",
+                            SetAttribute(Attribute::NoItalic),
+                            SetAttribute(Attribute::NoUnderline)
+                        ));
+                    }
+                    if let Some(attribute) = highlighting_type.attribute(theme) {
+                        rendered.push_str(&format!("{}", SetAttribute(attribute)));
+                    }
                     current_highlighting = highlighting_type;
                     rendered.push_str(
-                        format!("{}", SetForegroundColor(highlighting_type.to_colour())).as_str(),
+                        format!("{}", SetForegroundColor(highlighting_type.to_colour(theme)))
+                            .as_str(),
                     );
                 }
 
-                if c == '\t' {
-                    rendered.push(' ');
+                if c == '\t' || column < start || next_column > end {
+                    let visible =
+                        cmp::min(next_column, end).saturating_sub(cmp::max(column, start));
+                    rendered.push_str(&" ".repeat(visible));
                 } else {
-                    rendered.push(c);
+                    rendered.push_str(grapheme);
                 }
             }
+            column = next_column;
         }
 
+        if selected {
+            rendered.push_str(format!("{}", SetBackgroundColor(Color::Reset)).as_str());
+        }
         rendered.push_str(format!("{}", SetForegroundColor(Color::Reset)).as_str());
 
         rendered
     }
 
-    pub fn insert(&mut self, at: usize, c: char) {
-        if at >= self.len() {
-            self.content.push(c);
-            self.len += 1;
-        } else {
-            let mut result = String::new();
-            let mut length = 0;
-
-            for (i, grapheme) in self.content[..].graphemes(true).enumerate() {
-                length += 1;
-                if i == at {
-                    length += 1;
-                    result.push(c);
-                }
-                result.push_str(grapheme);
-            }
-
-            self.len = length;
-            self.content = result;
+    pub fn display_column(&self, at: usize, tab_width: usize) -> usize {
+        let mut column: usize = 0;
+        for grapheme in self.content[..].graphemes(true).take(at) {
+            column = column.saturating_add(grapheme_width(grapheme, column, tab_width));
         }
+        column
     }
 
-    pub fn delete(&mut self, at: usize) {
-        if at < self.len() {
-            let mut result = String::new();
-            let mut length = 0;
+    pub fn display_width(&self, tab_width: usize) -> usize {
+        self.display_column(self.len, tab_width)
+    }
 
-            for (i, grapheme) in self.content[..].graphemes(true).enumerate() {
-                if i == at {
-                    continue;
-                }
-                length += 1;
-                result.push_str(grapheme);
+    pub fn grapheme_at_column(&self, target: usize, tab_width: usize) -> usize {
+        let mut column: usize = 0;
+        for (i, grapheme) in self.content[..].graphemes(true).enumerate() {
+            column = column.saturating_add(grapheme_width(grapheme, column, tab_width));
+            if column > target {
+                return i;
             }
+        }
+        self.len
+    }
+
+    pub fn insert(&mut self, at: usize, c: char) {
+        let index = self.byte_index(at);
+        self.content.insert(index, c);
+        self.colours.clear();
+        self.len += 1;
+    }
 
-            self.len = length;
-            self.content = result;
+    pub fn delete(&mut self, at: usize) {
+        if at < self.len() {
+            let start = self.byte_index(at);
+            let end = self.byte_index(at.saturating_add(1));
+            self.content.replace_range(start..end, "");
+            self.colours.clear();
+            self.len -= 1;
         }
     }
 
     pub fn append(&mut self, new: &Self) {
-        self.content = format!("{}{}", self.content, new.content);
+        self.content.push_str(&new.content);
+        self.colours.clear();
         self.len += new.len;
     }
 
     pub fn split(&mut self, at: usize) -> Self {
-        let mut first_row = String::new();
-        let mut second_row = String::new();
-        let mut first_len = 0;
-        let mut second_len = 0;
-
-        for (i, grapheme) in self.content[..].graphemes(true).enumerate() {
-            if i < at {
-                first_len += 1;
-                first_row.push_str(grapheme);
-            } else {
-                second_len += 1;
-                second_row.push_str(grapheme);
-            }
-        }
-
-        self.content = first_row;
-        self.len = first_len;
+        let at = cmp::min(at, self.len);
+        let second_row = self.content.split_off(self.byte_index(at));
+        self.colours.clear();
+        let second_len = self.len - at;
+        self.len = at;
 
         Self {
             content: second_row,
             highlighting: Vec::new(),
+            is_highlighted: false,
+            starts_in_comment: false,
+            ends_in_comment: false,
+            colours: Vec::new(),
             len: second_len,
         }
     }
 
-    pub fn find(&self, query: &str, at: usize, direction: SearchDirection) -> Option<usize> {
-        if at > self.len || query.is_empty() {
+    pub fn find(
+        &self,
+        matcher: &dyn Matcher,
+        at: usize,
+        direction: SearchDirection,
+    ) -> Option<usize> {
+        if at > self.len {
             return None;
         }
-
-        let (start, end) = if direction == SearchDirection::Forward {
-            (at, self.len)
+        let matches = self.matches(matcher);
+        if direction == SearchDirection::Forward {
+            matches.iter().find(|&&(start, _)| start >= at)
         } else {
-            (0, at)
-        };
+            matches.iter().rev().find(|&&(start, _)| start < at)
+        }
+        .map(|&(start, _)| start)
+    }
 
-        let substring = self.content[..]
-            .graphemes(true)
-            .skip(start)
-            .take(end - start)
-            .collect::<String>();
-        let matching_index = if direction == SearchDirection::Forward {
-            substring.find(query)
-        } else {
-            substring.rfind(query)
-        };
+    pub fn colours(&self) -> &[(usize, usize, Color)] {
+        &self.colours
+    }
 
-        if let Some(matching_index) = matching_index {
-            for (grapheme_index, (byte_index, _)) in
-                self.content[..].grapheme_indices(true).enumerate()
-            {
-                if matching_index == byte_index {
-                    return Some(start.saturating_add(grapheme_index));
-                }
-            }
-        }
+    pub fn set_colours(&mut self, colours: Vec<(usize, usize, Color)>) {
+        self.colours = colours;
+    }
+
+    pub fn is_highlighted(&self) -> bool {
+        self.is_highlighted
+    }
+
+    pub fn starts_in_comment(&self) -> bool {
+        self.starts_in_comment
+    }
 
-        None
+    pub fn ends_in_comment(&self) -> bool {
+        self.ends_in_comment
+    }
+
+    pub fn clear_highlighting(&mut self) {
+        self.highlighting = Vec::new();
+        self.is_highlighted = false;
+        self.starts_in_comment = false;
+        self.ends_in_comment = false;
     }
 
-    pub fn highlight(&mut self, opts: &HighlightingOptions, word: Option<&str>) {
+    pub fn highlight(&mut self, opts: &HighlightingOptions, starts_in_comment: bool) -> bool {
         self.highlighting = Vec::new();
+        self.is_highlighted = true;
+        self.starts_in_comment = starts_in_comment;
 
         let chars = self.content.chars().collect::<Vec<char>>();
 
         let mut index = 0;
+        let mut in_comment = match opts.block_comment() {
+            Some((_, end)) if starts_in_comment => {
+                !self.close_multiline_comment(&mut index, &chars, end)
+            }
+            _ => false,
+        };
         while let Some(c) = chars.get(index) {
+            if in_comment {
+                break;
+            }
+            if let Some((start, end)) = opts.block_comment() {
+                if starts_at(&chars, index, start) {
+                    for _ in start.chars() {
+                        self.highlighting.push(highlighting::Type::Comment);
+                        index += 1;
+                    }
+                    in_comment = !self.close_multiline_comment(&mut index, &chars, end);
+                    continue;
+                }
+            }
             if self.highlight_numbers(&mut index, opts, *c, &chars)
                 || self.highlight_strings(&mut index, opts, *c, &chars)
                 || self.highlight_char(&mut index, opts, *c, &chars)
-                || self.highlight_comments(&mut index, opts, *c, &chars)
+                || self.highlight_comments(&mut index, opts, &chars)
                 || self.highlight_primary_keywords(&mut index, opts, &chars)
                 || self.highlight_secondary_keywords(&mut index, opts, &chars)
             {
@@ -184,28 +263,141 @@ impl Row {
             index += 1;
         }
 
-        self.highlight_matches(word);
+        let mut char_index = 0;
+        self.highlighting = self.content[..]
+            .graphemes(true)
+            .map(|grapheme| {
+                let highlighting_type = self
+                    .highlighting
+                    .get(char_index)
+                    .copied()
+                    .unwrap_or(highlighting::Type::None);
+                char_index += grapheme.chars().count();
+                highlighting_type
+            })
+            .collect();
+
+        self.ends_in_comment = in_comment;
+        in_comment
     }
 
-    fn highlight_matches(&mut self, word: Option<&str>) {
-        if let Some(word) = word {
-            if word.is_empty() {
-                return;
+    fn close_multiline_comment(&mut self, index: &mut usize, chars: &[char], end: &str) -> bool {
+        while *index < chars.len() {
+            if starts_at(chars, *index, end) {
+                for _ in end.chars() {
+                    self.highlighting.push(highlighting::Type::Comment);
+                    *index += 1;
+                }
+                return true;
             }
-            let mut search_index = 0;
+            self.highlighting.push(highlighting::Type::Comment);
+            *index += 1;
+        }
+        false
+    }
+
+    pub fn brackets(&self) -> Vec<(usize, char)> {
+        self.content[..]
+            .graphemes(true)
+            .enumerate()
+            .filter_map(|(i, grapheme)| {
+                let c = match grapheme {
+                    "(" | ")" | "[" | "]" | "{" | "}" => grapheme.chars().next()?,
+                    _ => return None,
+                };
+                (!self.in_text(i)).then_some((i, c))
+            })
+            .collect()
+    }
 
-            while let Some(search_match) = self.find(word, search_index, SearchDirection::Forward) {
-                if let Some(next_index) = search_match.checked_add(word[..].graphemes(true).count())
-                {
-                    for i in search_index.saturating_add(search_match)..next_index {
-                        self.highlighting[i] = highlighting::Type::Match;
+    pub fn code_words(&self) -> Vec<(usize, usize, &str)> {
+        let mut words = Vec::new();
+        let mut start = None;
+        let graphemes: Vec<(usize, &str)> = self.content[..].grapheme_indices(true).collect();
+        for i in 0..=graphemes.len() {
+            let is_member = graphemes.get(i).is_some_and(|(_, grapheme)| {
+                grapheme
+                    .chars()
+                    .next()
+                    .is_some_and(|c| c.is_alphanumeric() || c == '_')
+            });
+            match (start, is_member) {
+                (None, true) => start = Some(i),
+                (Some(from), false) => {
+                    start = None;
+                    if !self.in_text(from) {
+                        let end = graphemes
+                            .get(i)
+                            .map_or(self.content.len(), |&(byte, _)| byte);
+                        words.push((from, i, &self.content[graphemes[from].0..end]));
                     }
-                    search_index = next_index;
-                } else {
-                    break;
                 }
+                _ => (),
             }
         }
+        words
+    }
+
+    pub fn tags(&self) -> Vec<(usize, usize, String, bool)> {
+        let chars: Vec<char> = self.content[..]
+            .graphemes(true)
+            .map(|grapheme| grapheme.chars().next().unwrap_or(' '))
+            .collect();
+        let is_name = |c: char| c.is_alphanumeric() || matches!(c, '-' | '_' | ':' | '.');
+        let mut tags = Vec::new();
+        for (i, &c) in chars.iter().enumerate() {
+            if c != '<' || self.in_text(i) {
+                continue;
+            }
+            let closing = chars.get(i + 1) == Some(&'/');
+            let start = if closing { i + 2 } else { i + 1 };
+            let mut end = start;
+            while end < chars.len() && is_name(chars[end]) {
+                end += 1;
+            }
+            if end == start {
+                continue;
+            }
+            let self_closing = !closing
+                && chars[end..]
+                    .iter()
+                    .position(|&c| c == '>')
+                    .is_some_and(|close| close > 0 && chars[end + close - 1] == '/');
+            if !self_closing {
+                let name: String = chars[start..end].iter().collect();
+                tags.push((i, end, name.to_lowercase(), closing));
+            }
+        }
+        tags
+    }
+
+    fn in_text(&self, at: usize) -> bool {
+        matches!(
+            self.highlighting.get(at),
+            Some(
+                highlighting::Type::String
+                    | highlighting::Type::Character
+                    | highlighting::Type::Comment
+            )
+        )
+    }
+
+    pub fn matches(&self, matcher: &dyn Matcher) -> Vec<(usize, usize)> {
+        let offsets: Vec<usize> = self
+            .content
+            .grapheme_indices(true)
+            .map(|(offset, _)| offset)
+            .collect();
+        matcher
+            .find_all(&self.content)
+            .into_iter()
+            .map(|range| {
+                let start = offsets
+                    .partition_point(|&offset| offset <= range.start)
+                    .saturating_sub(1);
+                (start, offsets.partition_point(|&offset| offset < range.end))
+            })
+            .collect()
     }
 
     fn highlight_strings(
@@ -215,12 +407,12 @@ impl Row {
         c: char,
         chars: &[char],
     ) -> bool {
-        if opts.strings() && c == '"' {
+        if opts.strings().contains(&c) {
             loop {
                 self.highlighting.push(highlighting::Type::String);
                 *index += 1;
                 if let Some(next_char) = chars.get(*index) {
-                    if next_char == &'"' {
+                    if next_char == &c {
                         break;
                     }
                 } else {
@@ -305,22 +497,18 @@ impl Row {
         &mut self,
         index: &mut usize,
         opts: &HighlightingOptions,
-        c: char,
         chars: &[char],
     ) -> bool {
-        if opts.comments() && c == '/' && *index < chars.len() {
-            if let Some(next_char) = chars.get(index.saturating_add(1)) {
-                if next_char == &'/' {
-                    for _ in *index..chars.len() {
-                        self.highlighting.push(highlighting::Type::Comment);
-                        *index += 1;
-                    }
-                    return true;
+        match opts.line_comment() {
+            Some(prefix) if starts_at(chars, *index, prefix) => {
+                for _ in *index..chars.len() {
+                    self.highlighting.push(highlighting::Type::Comment);
+                    *index += 1;
                 }
+                true
             }
+            _ => false,
         }
-
-        false
     }
 
     fn highlight_substring(
@@ -389,7 +577,7 @@ impl Row {
         self.highlight_keywords(
             index,
             chars,
-            &opts.primary_keywords(),
+            opts.primary_keywords(),
             highlighting::Type::PrimaryKeywords,
         )
     }
@@ -403,11 +591,103 @@ impl Row {
         self.highlight_keywords(
             index,
             chars,
-            &opts.secondary_keywords(),
+            opts.secondary_keywords(),
             highlighting::Type::SecondaryKeywords,
         )
     }
 
+    pub fn byte_index(&self, at: usize) -> usize {
+        self.content[..]
+            .grapheme_indices(true)
+            .nth(at)
+            .map_or(self.content.len(), |(index, _)| index)
+    }
+
+    pub fn grapheme_index(&self, byte: usize) -> usize {
+        self.content[..]
+            .grapheme_indices(true)
+            .position(|(index, grapheme)| byte < index.saturating_add(grapheme.len()))
+            .unwrap_or(self.len)
+    }
+
+    pub fn words(&self, subword: bool) -> Words {
+        Words {
+            chars: self.content[..]
+                .graphemes(true)
+                .map(|grapheme| grapheme.chars().next().unwrap_or(' '))
+                .collect(),
+            index: 0,
+            subword,
+        }
+    }
+
+    pub fn word_starts(&self, subword: bool) -> Vec<usize> {
+        self.words(subword).map(|(start, _)| start).collect()
+    }
+
+    pub fn word_at(&self, at: usize) -> Option<(usize, usize)> {
+        let is_member = |grapheme: &str| {
+            grapheme
+                .chars()
+                .next()
+                .is_some_and(|c| c.is_alphanumeric() || c == '_')
+        };
+        let graphemes: Vec<&str> = self.content[..].graphemes(true).collect();
+        let mut start = at;
+        while start < graphemes.len() && !is_member(graphemes[start]) {
+            start += 1;
+        }
+        if start >= graphemes.len() {
+            return None;
+        }
+        while start > 0 && is_member(graphemes[start - 1]) {
+            start -= 1;
+        }
+        let mut end = start;
+        while end < graphemes.len() && is_member(graphemes[end]) {
+            end += 1;
+        }
+        Some((start, end))
+    }
+
+    pub fn subword_bounds(&self, at: usize, around: bool) -> Option<(usize, usize)> {
+        let chars: Vec<char> = self.content[..]
+            .graphemes(true)
+            .map(|grapheme| grapheme.chars().next().unwrap_or(' '))
+            .collect();
+        let is_member = |c: char| c.is_alphanumeric();
+        if !chars.get(at).copied().is_some_and(is_member) {
+            return None;
+        }
+
+        let starts = self.word_starts(true);
+        let start = starts.iter().rev().find(|&&start| start <= at).copied()?;
+        let mut end = start.saturating_add(1);
+        while end < chars.len() && is_member(chars[end]) && !starts.contains(&end) {
+            end += 1;
+        }
+
+        if !around {
+            return Some((start, end));
+        }
+        let mut around_end = end;
+        while around_end < chars.len() && chars[around_end] == '_' {
+            around_end += 1;
+        }
+        if around_end > end {
+            return Some((start, around_end));
+        }
+        let mut around_start = start;
+        while around_start > 0 && chars[around_start - 1] == '_' {
+            around_start -= 1;
+        }
+        Some((around_start, end))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.content
+    }
+
     pub fn as_bytes(&self) -> &[u8] {
         self.content.as_bytes()
     }
@@ -421,6 +701,103 @@ impl Row {
     }
 }
 
+pub struct Words {
+    chars: Vec<char>,
+    index: usize,
+    subword: bool,
+}
+
+impl Words {
+    fn is_start(&self, i: usize) -> bool {
+        let c = self.chars[i];
+        let prev = i.checked_sub(1).map(|i| self.chars[i]);
+        if self.subword {
+            let next = self.chars.get(i.saturating_add(1)).copied();
+            is_subword_start(prev, c, next)
+        } else {
+            char_class(c) != CharClass::Whitespace
+                && prev.is_none_or(|prev| char_class(prev) != char_class(c))
+        }
+    }
+}
+
+impl Iterator for Words {
+    type Item = (usize, usize);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.index < self.chars.len() && !self.is_start(self.index) {
+            self.index += 1;
+        }
+        if self.index >= self.chars.len() {
+            return None;
+        }
+
+        let start = self.index;
+        self.index += 1;
+        while self.index < self.chars.len()
+            && char_class(self.chars[self.index]) != CharClass::Whitespace
+            && !self.is_start(self.index)
+        {
+            self.index += 1;
+        }
+        Some((start, self.index))
+    }
+}
+
+#[derive(PartialEq, Clone, Copy)]
+enum CharClass {
+    Whitespace,
+    Word,
+    Punctuation,
+}
+
+fn char_class(c: char) -> CharClass {
+    if c.is_whitespace() {
+        CharClass::Whitespace
+    } else if c.is_alphanumeric() || c == '_' {
+        CharClass::Word
+    } else {
+        CharClass::Punctuation
+    }
+}
+
+fn is_subword_start(prev: Option<char>, c: char, next: Option<char>) -> bool {
+    if c.is_alphanumeric() {
+        let Some(prev) = prev else {
+            return true;
+        };
+        if !prev.is_alphanumeric() {
+            return true;
+        }
+        return (prev.is_lowercase() && c.is_uppercase())
+            || (prev.is_ascii_digit() != c.is_ascii_digit())
+            || (prev.is_uppercase()
+                && c.is_uppercase()
+                && next.is_some_and(|next| next.is_lowercase()));
+    }
+
+    char_class(c) == CharClass::Punctuation
+        && c != '_'
+        && prev.is_none_or(|prev| char_class(prev) != CharClass::Punctuation || prev == '_')
+}
+
+fn grapheme_width(grapheme: &str, column: usize, tab_width: usize) -> usize {
+    if grapheme == "\t" {
+        let tab_width = usize::max(tab_width, 1);
+        tab_width.saturating_sub(column % tab_width)
+    } else {
+        1
+    }
+}
+
+fn starts_at(chars: &[char], index: usize, pattern: &str) -> bool {
+    !pattern.is_empty()
+        && pattern
+            .chars()
+            .enumerate()
+            .all(|(i, c)| chars.get(index.saturating_add(i)) == Some(&c))
+}
+
 fn is_separator(c: char) -> bool {
     c.is_ascii_punctuation() || c.is_ascii_whitespace()
 }
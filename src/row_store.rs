@@ -0,0 +1,345 @@
+use crate::Row;
+use std::cell::{Cell, OnceCell, Ref, RefCell};
+use std::ops::Range;
+use std::sync::Arc;
+
+const CHUNK_SIZE: usize = 1024;
+
+// Rows are only built when a chunk is first read, so opening a large file costs one scan for
+// line boundaries instead of a String and a grapheme count per line.
+#[derive(Default)]
+struct Chunk {
+    source: Option<(Arc<str>, Range<usize>)>,
+    rows: OnceCell<Vec<Row>>,
+}
+
+impl Chunk {
+    fn lines(&self) -> Option<std::str::Lines<'_>> {
+        let (text, range) = self.source.as_ref().filter(|_| self.rows.get().is_none())?;
+        Some(text[range.clone()].lines())
+    }
+
+    fn rows(&self) -> &Vec<Row> {
+        self.rows.get_or_init(|| {
+            self.source.as_ref().map_or_else(Vec::new, |(text, range)| {
+                text[range.clone()].lines().map(Row::from).collect()
+            })
+        })
+    }
+
+    fn rows_mut(&mut self) -> &mut Vec<Row> {
+        self.rows();
+        self.source = None;
+        self.rows.get_mut().expect("chunk rows were just built")
+    }
+}
+
+impl From<Vec<Row>> for Chunk {
+    fn from(rows: Vec<Row>) -> Self {
+        Self {
+            source: None,
+            rows: OnceCell::from(rows),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct RowStore {
+    chunks: Vec<Chunk>,
+    starts: Vec<usize>,
+    len: usize,
+    chunk_bytes: Vec<Cell<Option<usize>>>,
+    bytes_before: RefCell<Vec<usize>>,
+}
+
+impl RowStore {
+    pub fn from_text(contents: &str) -> Self {
+        let text: Arc<str> = Arc::from(contents);
+        let mut store = Self::default();
+        let mut start = 0;
+        while start < text.len() {
+            let end = text[start..]
+                .match_indices('\n')
+                .nth(CHUNK_SIZE - 1)
+                .map_or(text.len(), |(index, _)| start + index + 1);
+            let lines = text[start..end].lines().count();
+            store.starts.push(store.len);
+            store.len += lines;
+            store.chunks.push(Chunk {
+                source: Some((Arc::clone(&text), start..end)),
+                rows: OnceCell::new(),
+            });
+            store.chunk_bytes.push(Cell::new(None));
+            start = end;
+        }
+        store
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn get(&self, index: usize) -> Option<&Row> {
+        let (chunk, offset) = self.locate(index)?;
+        self.chunks[chunk].rows().get(offset)
+    }
+
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut Row> {
+        let (chunk, offset) = self.locate(index)?;
+        self.invalidate(chunk);
+        self.chunks[chunk].rows_mut().get_mut(offset)
+    }
+
+    pub fn push(&mut self, row: Row) {
+        self.insert(self.len, row);
+    }
+
+    pub fn insert(&mut self, index: usize, row: Row) {
+        let index = usize::min(index, self.len);
+        if self.chunks.is_empty() {
+            self.chunks.push(Chunk::default());
+            self.starts.push(0);
+            self.chunk_bytes.push(Cell::new(None));
+        }
+        let (chunk, offset) = if index == self.len {
+            let chunk = self.chunks.len() - 1;
+            (chunk, self.len - self.starts[chunk])
+        } else {
+            self.locate(index).unwrap_or((0, 0))
+        };
+
+        let rows = self.chunks[chunk].rows_mut();
+        rows.insert(offset, row);
+        let split = (rows.len() > CHUNK_SIZE * 2).then(|| rows.split_off(CHUNK_SIZE));
+        self.invalidate(chunk);
+        self.len += 1;
+        for start in &mut self.starts[chunk + 1..] {
+            *start += 1;
+        }
+
+        if let Some(tail) = split {
+            self.chunks.insert(chunk + 1, Chunk::from(tail));
+            self.starts
+                .insert(chunk + 1, self.starts[chunk] + CHUNK_SIZE);
+            self.chunk_bytes.insert(chunk + 1, Cell::new(None));
+        }
+    }
+
+    pub fn remove(&mut self, index: usize) -> Option<Row> {
+        let (chunk, offset) = self.locate(index)?;
+        let rows = self.chunks[chunk].rows_mut();
+        let row = rows.remove(offset);
+        let emptied = rows.is_empty();
+        self.invalidate(chunk);
+        self.len -= 1;
+        for start in &mut self.starts[chunk + 1..] {
+            *start -= 1;
+        }
+
+        if emptied {
+            self.chunks.remove(chunk);
+            self.starts.remove(chunk);
+            self.chunk_bytes.remove(chunk);
+        }
+        Some(row)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Row> {
+        self.chunks.iter().flat_map(Chunk::rows)
+    }
+
+    pub fn built_mut(&mut self) -> impl Iterator<Item = &mut Row> {
+        self.chunks
+            .iter_mut()
+            .filter_map(|chunk| chunk.rows.get_mut())
+            .flatten()
+    }
+
+    pub fn lines(&self) -> impl Iterator<Item = &str> {
+        self.chunks
+            .iter()
+            .flat_map(|chunk| -> Box<dyn Iterator<Item = &str>> {
+                match chunk.lines() {
+                    Some(lines) => Box::new(lines),
+                    None => Box::new(chunk.rows().iter().map(Row::as_str)),
+                }
+            })
+    }
+
+    pub fn bytes_before(&self, index: usize) -> usize {
+        let index = usize::min(index, self.len);
+        let Some((chunk, offset)) = self.locate(index).or_else(|| {
+            let chunk = self.chunks.len().checked_sub(1)?;
+            Some((chunk, self.len - self.starts[chunk]))
+        }) else {
+            return 0;
+        };
+        let within: usize = self.chunk_line_lengths(chunk).take(offset).sum();
+        self.prefixes()[chunk].saturating_add(within)
+    }
+
+    pub fn total_bytes(&self) -> usize {
+        self.bytes_before(self.len)
+    }
+
+    pub fn row_containing_byte(&self, byte: usize, newline: usize) -> usize {
+        let prefixes = self.prefixes();
+        let before = |chunk: usize| prefixes[chunk] + self.starts[chunk] * newline;
+        let (mut low, mut high) = (0, self.chunks.len());
+        while high - low > 1 {
+            let middle = low + (high - low) / 2;
+            if before(middle) <= byte {
+                low = middle;
+            } else {
+                high = middle;
+            }
+        }
+        if low >= self.chunks.len() {
+            return 0;
+        }
+        let mut remaining = byte.saturating_sub(before(low));
+        for (offset, len) in self.chunk_line_lengths(low).enumerate() {
+            if remaining <= len || remaining < len + newline {
+                return self.starts[low] + offset;
+            }
+            remaining -= len + newline;
+        }
+        self.len.saturating_sub(1)
+    }
+
+    fn prefixes(&self) -> Ref<'_, Vec<usize>> {
+        if self.bytes_before.borrow().len() != self.chunks.len() {
+            let mut total = 0;
+            let prefixes = (0..self.chunks.len())
+                .map(|chunk| {
+                    let before = total;
+                    total += self.chunk_size(chunk);
+                    before
+                })
+                .collect();
+            *self.bytes_before.borrow_mut() = prefixes;
+        }
+        self.bytes_before.borrow()
+    }
+
+    fn chunk_size(&self, chunk: usize) -> usize {
+        if let Some(bytes) = self.chunk_bytes[chunk].get() {
+            return bytes;
+        }
+        let bytes = self.chunk_line_lengths(chunk).sum();
+        self.chunk_bytes[chunk].set(Some(bytes));
+        bytes
+    }
+
+    fn chunk_line_lengths(&self, chunk: usize) -> Box<dyn Iterator<Item = usize> + '_> {
+        let chunk = &self.chunks[chunk];
+        match chunk.lines() {
+            Some(lines) => Box::new(lines.map(str::len)),
+            None => Box::new(chunk.rows().iter().map(|row| row.as_bytes().len())),
+        }
+    }
+
+    fn invalidate(&mut self, chunk: usize) {
+        if let Some(bytes) = self.chunk_bytes.get_mut(chunk) {
+            bytes.set(None);
+        }
+        self.bytes_before.get_mut().clear();
+    }
+
+    fn locate(&self, index: usize) -> Option<(usize, usize)> {
+        if index >= self.len {
+            return None;
+        }
+        let chunk = self
+            .starts
+            .partition_point(|&start| start <= index)
+            .saturating_sub(1);
+        Some((chunk, index - self.starts[chunk]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn numbered(count: usize) -> String {
+        (0..count).map(|line| format!("{}\n", line)).collect()
+    }
+
+    fn assert_rows(store: &RowStore, expected: &[String]) {
+        assert_eq!(store.len(), expected.len());
+        let lines: Vec<&str> = store.lines().collect();
+        assert_eq!(lines, expected);
+        for (index, line) in expected.iter().enumerate() {
+            assert_eq!(store.get(index).map(Row::as_str), Some(line.as_str()));
+        }
+        assert_eq!(
+            store.total_bytes(),
+            expected.iter().map(String::len).sum::<usize>()
+        );
+    }
+
+    #[test]
+    fn splits_a_chunk_that_grows_past_twice_the_chunk_size() {
+        let mut store = RowStore::default();
+        let mut expected = Vec::new();
+        for line in 0..=CHUNK_SIZE * 2 {
+            store.push(Row::from(line.to_string().as_str()));
+            expected.push(line.to_string());
+        }
+        assert_eq!(store.starts, [0, CHUNK_SIZE]);
+        assert_rows(&store, &expected);
+
+        store.insert(1, Row::from("new"));
+        expected.insert(1, String::from("new"));
+        assert_eq!(store.starts, [0, CHUNK_SIZE + 1]);
+        assert_rows(&store, &expected);
+    }
+
+    #[test]
+    fn drops_a_chunk_once_its_last_row_is_removed() {
+        let mut store = RowStore::from_text(&numbered(CHUNK_SIZE + 2));
+        let mut expected: Vec<String> = (0..CHUNK_SIZE + 2).map(|line| line.to_string()).collect();
+        assert_eq!(store.starts, [0, CHUNK_SIZE]);
+
+        assert_eq!(
+            store.remove(3).map(|row| row.as_str().to_string()),
+            Some(expected.remove(3))
+        );
+        assert_eq!(store.starts, [0, CHUNK_SIZE - 1]);
+        assert_rows(&store, &expected);
+
+        store.remove(CHUNK_SIZE - 1);
+        store.remove(CHUNK_SIZE - 1);
+        expected.truncate(CHUNK_SIZE - 1);
+        assert_eq!(store.chunks.len(), 1);
+        assert_eq!(store.starts, [0]);
+        assert_rows(&store, &expected);
+        assert!(store.remove(CHUNK_SIZE - 1).is_none());
+    }
+
+    #[test]
+    fn finds_rows_by_byte_across_crlf_line_endings() {
+        let store = RowStore::from_text("ab\r\ncd\r\n\r\nef");
+        let rows: Vec<usize> = (0..14)
+            .map(|byte| store.row_containing_byte(byte, 2))
+            .collect();
+        assert_eq!(rows, [0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 3, 3, 3, 3]);
+        assert_eq!(store.total_bytes(), 6);
+    }
+
+    #[test]
+    fn finds_rows_by_byte_across_chunks() {
+        let text = numbered(CHUNK_SIZE + 1).replace('\n', "\r\n");
+        let store = RowStore::from_text(&text);
+        assert_eq!(store.chunks.len(), 2);
+        let last_start = text.len() - format!("{}\r\n", CHUNK_SIZE).len();
+        assert_eq!(store.row_containing_byte(last_start - 1, 2), CHUNK_SIZE - 1);
+        assert_eq!(store.row_containing_byte(last_start, 2), CHUNK_SIZE);
+        assert_eq!(store.row_containing_byte(text.len(), 2), CHUNK_SIZE);
+    }
+}
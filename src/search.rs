@@ -0,0 +1,138 @@
+use regex::RegexBuilder;
+use std::ops::Range;
+
+pub trait Matcher {
+    fn find_all(&self, line: &str) -> Vec<Range<usize>>;
+}
+
+pub trait Provider {
+    fn compile(&self, query: &str, ignore_case: bool) -> Result<Box<dyn Matcher>, String>;
+}
+
+pub struct SearchProviders {
+    providers: Vec<(String, Box<dyn Provider>)>,
+}
+
+impl Default for SearchProviders {
+    fn default() -> Self {
+        let mut providers = Self {
+            providers: Vec::new(),
+        };
+        providers.register("literal", Box::new(Literal));
+        providers.register("regex", Box::new(Regex));
+        providers.register("fuzzy", Box::new(Fuzzy));
+        providers
+    }
+}
+
+impl SearchProviders {
+    pub fn register(&mut self, name: &str, provider: Box<dyn Provider>) {
+        self.providers.retain(|(existing, _)| existing != name);
+        self.providers.push((name.to_string(), provider));
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.providers.iter().map(|(name, _)| name.as_str())
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.names().any(|existing| existing == name)
+    }
+
+    pub fn compile(
+        &self,
+        name: &str,
+        query: &str,
+        ignore_case: bool,
+    ) -> Result<Box<dyn Matcher>, String> {
+        match self.providers.iter().find(|(existing, _)| existing == name) {
+            Some((_, provider)) => provider.compile(query, ignore_case),
+            None => Err(format!("Unknown search provider: {}", name)),
+        }
+    }
+}
+
+struct Literal;
+
+impl Provider for Literal {
+    fn compile(&self, query: &str, ignore_case: bool) -> Result<Box<dyn Matcher>, String> {
+        Regex.compile(&regex::escape(query), ignore_case)
+    }
+}
+
+struct Regex;
+
+impl Provider for Regex {
+    fn compile(&self, query: &str, ignore_case: bool) -> Result<Box<dyn Matcher>, String> {
+        RegexBuilder::new(query)
+            .case_insensitive(ignore_case)
+            .build()
+            .map(|regex| Box::new(RegexMatcher(regex)) as Box<dyn Matcher>)
+            .map_err(|err| format!("Invalid pattern: {}", err))
+    }
+}
+
+struct RegexMatcher(regex::Regex);
+
+impl Matcher for RegexMatcher {
+    fn find_all(&self, line: &str) -> Vec<Range<usize>> {
+        self.0
+            .find_iter(line)
+            .map(|found| found.range())
+            .filter(|range| !range.is_empty())
+            .collect()
+    }
+}
+
+struct Fuzzy;
+
+impl Provider for Fuzzy {
+    fn compile(&self, query: &str, ignore_case: bool) -> Result<Box<dyn Matcher>, String> {
+        Ok(Box::new(FuzzyMatcher {
+            chars: query.chars().filter(|c| !c.is_whitespace()).collect(),
+            ignore_case,
+        }))
+    }
+}
+
+struct FuzzyMatcher {
+    chars: Vec<char>,
+    ignore_case: bool,
+}
+
+impl FuzzyMatcher {
+    fn eq(&self, a: char, b: char) -> bool {
+        a == b || (self.ignore_case && a.to_lowercase().eq(b.to_lowercase()))
+    }
+}
+
+impl Matcher for FuzzyMatcher {
+    fn find_all(&self, line: &str) -> Vec<Range<usize>> {
+        let Some(&first) = self.chars.first() else {
+            return Vec::new();
+        };
+        let mut matches = Vec::new();
+        let mut chars = line.char_indices().peekable();
+        while let Some((start, c)) = chars.next() {
+            if !self.eq(c, first) {
+                continue;
+            }
+            let mut wanted = self.chars[1..].iter().peekable();
+            let mut end = start.saturating_add(c.len_utf8());
+            while let Some(&&next) = wanted.peek() {
+                let Some((at, c)) = chars.next() else {
+                    break;
+                };
+                if self.eq(c, next) {
+                    wanted.next();
+                    end = at.saturating_add(c.len_utf8());
+                }
+            }
+            if wanted.peek().is_some() {
+                break;
+            }
+            matches.push(start..end);
+        }
+        matches
+    }
+}
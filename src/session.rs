@@ -0,0 +1,80 @@
+use crate::paths;
+use crate::Position;
+use std::fs;
+use std::io::Error;
+use std::path::PathBuf;
+
+const SESSION_FILE: &str = "session";
+const BUFFER_FILE: &str = "buffer";
+
+pub struct Session {
+    pub filename: Option<String>,
+    pub cursor_position: Position,
+    pub buffer: Option<String>,
+}
+
+impl Session {
+    pub fn recovery_dir() -> Option<PathBuf> {
+        Some(paths::state_dir()?.join("recovery"))
+    }
+
+    pub fn save(&self) -> Result<(), Error> {
+        let dir = Self::recovery_dir().ok_or_else(|| Error::other("No recovery directory"))?;
+        fs::create_dir_all(&dir)?;
+
+        if let Some(buffer) = &self.buffer {
+            fs::write(dir.join(BUFFER_FILE), buffer)?;
+        }
+        let session = format!(
+            "file={}\ncursor={},{}\nbuffer={}\n",
+            self.filename.as_deref().unwrap_or(""),
+            self.cursor_position.x,
+            self.cursor_position.y,
+            if self.buffer.is_some() {
+                BUFFER_FILE
+            } else {
+                ""
+            },
+        );
+        fs::write(dir.join(SESSION_FILE), session)
+    }
+
+    pub fn load() -> Option<Self> {
+        let dir = Self::recovery_dir()?;
+        let contents = fs::read_to_string(dir.join(SESSION_FILE)).ok()?;
+        let mut session = Self {
+            filename: None,
+            cursor_position: Position::default(),
+            buffer: None,
+        };
+
+        for line in contents.lines() {
+            match line.split_once('=') {
+                Some(("file", name)) if !name.is_empty() => {
+                    session.filename = Some(name.to_string());
+                }
+                Some(("cursor", cursor)) => {
+                    if let Some((x, y)) = cursor.split_once(',') {
+                        session.cursor_position = Position {
+                            x: x.parse().unwrap_or(0),
+                            y: y.parse().unwrap_or(0),
+                        };
+                    }
+                }
+                Some(("buffer", buffer)) if !buffer.is_empty() => {
+                    session.buffer = fs::read_to_string(dir.join(buffer)).ok();
+                }
+                _ => (),
+            }
+        }
+
+        Some(session)
+    }
+
+    pub fn clear() {
+        if let Some(dir) = Self::recovery_dir() {
+            fs::remove_file(dir.join(SESSION_FILE)).ok();
+            fs::remove_file(dir.join(BUFFER_FILE)).ok();
+        }
+    }
+}
@@ -0,0 +1,73 @@
+use crate::transform;
+use std::collections::HashMap;
+use std::process::Stdio;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+
+#[derive(Default)]
+pub struct StatusCommands {
+    outputs: HashMap<String, String>,
+    pending: Option<Receiver<Vec<(String, String)>>>,
+}
+
+impl StatusCommands {
+    pub fn output(&self, name: &str) -> Option<&String> {
+        self.outputs.get(name)
+    }
+
+    pub fn request(&mut self, commands: Vec<(String, String)>, env: Vec<(&'static str, String)>) {
+        if commands.is_empty() || self.pending.is_some() {
+            return;
+        }
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            let outputs = commands
+                .into_iter()
+                .map(|(name, command)| {
+                    let output =
+                        run(&command, &env).unwrap_or_else(|err| format!("{}: {}", name, err));
+                    (name, output)
+                })
+                .collect();
+            sender.send(outputs).ok();
+        });
+        self.pending = Some(receiver);
+    }
+
+    pub fn poll(&mut self) -> bool {
+        let Some(pending) = &self.pending else {
+            return false;
+        };
+        let outputs = match pending.try_recv() {
+            Ok(outputs) => outputs,
+            Err(TryRecvError::Empty) => return false,
+            Err(TryRecvError::Disconnected) => Vec::new(),
+        };
+        self.pending = None;
+        let mut changed = false;
+        for (name, output) in outputs {
+            if self.outputs.get(&name) != Some(&output) {
+                self.outputs.insert(name, output);
+                changed = true;
+            }
+        }
+        changed
+    }
+}
+
+fn run(command: &str, env: &[(&'static str, String)]) -> Result<String, String> {
+    let output = transform::shell(command)
+        .envs(env.iter().map(|(key, value)| (key, value)))
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .map_err(|err| err.to_string())?;
+    if !output.status.success() {
+        return Err(format!("exited with {}", output.status));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .next()
+        .unwrap_or_default()
+        .to_string())
+}
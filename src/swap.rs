@@ -0,0 +1,65 @@
+use crate::lock;
+use std::fs::{self, File};
+use std::io::{Error, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+
+const HEADER: &str = "texty-swp";
+
+pub struct Swap {
+    path: PathBuf,
+    source: PathBuf,
+}
+
+pub struct Recovered {
+    pub pid: u32,
+    pub contents: String,
+}
+
+impl Swap {
+    pub fn for_file(filename: &str) -> Self {
+        Self {
+            path: lock::sibling(filename, "texty-swp"),
+            source: PathBuf::from(filename),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn write(&self, contents: &str) -> Result<(), Error> {
+        let temporary = self.path.with_extension("texty-swp.tmp");
+        let mut file = File::create(&temporary)?;
+        if let Ok(metadata) = fs::metadata(&self.source) {
+            file.set_permissions(metadata.permissions())?;
+        }
+        let written = write!(file, "{} {}\n{}", HEADER, process::id(), contents)
+            .and_then(|()| file.sync_all())
+            .and_then(|()| fs::rename(&temporary, &self.path));
+        if written.is_err() {
+            fs::remove_file(&temporary).ok();
+        }
+        written
+    }
+
+    pub fn read(&self) -> Option<Recovered> {
+        let swap = fs::read_to_string(&self.path).ok()?;
+        let (header, contents) = swap.split_once('\n')?;
+        let pid = header.strip_prefix(HEADER)?.trim().parse().ok()?;
+        Some(Recovered {
+            pid,
+            contents: contents.to_string(),
+        })
+    }
+
+    pub fn remove(&self) {
+        fs::remove_file(&self.path).ok();
+    }
+}
+
+impl Recovered {
+    pub fn is_stale(&self) -> bool {
+        self.pid == process::id() || !lock::is_running(self.pid)
+    }
+}
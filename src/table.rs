@@ -0,0 +1,129 @@
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Alignment {
+    None,
+    Left,
+    Centre,
+    Right,
+}
+
+pub fn is_table_row(line: &str) -> bool {
+    let line = line.trim();
+    line.len() > 1
+        && line.starts_with('|')
+        && line.ends_with('|')
+        && !line.ends_with("\\|")
+}
+
+pub fn align(lines: &[&str]) -> Vec<String> {
+    let indent: String = lines
+        .first()
+        .map(|line| line.chars().take_while(|c| c.is_whitespace()).collect())
+        .unwrap_or_default();
+    let rows: Vec<Vec<String>> = lines.iter().map(|line| split_cells(line)).collect();
+    let columns = rows.iter().map(Vec::len).max().unwrap_or(0);
+    let separator = rows.iter().position(|cells| is_separator(cells));
+
+    let mut alignments = vec![Alignment::None; columns];
+    if let Some(cells) = separator.and_then(|index| rows.get(index)) {
+        for (alignment, cell) in alignments.iter_mut().zip(cells) {
+            *alignment = match (cell.starts_with(':'), cell.ends_with(':')) {
+                (true, true) => Alignment::Centre,
+                (true, false) => Alignment::Left,
+                (false, true) => Alignment::Right,
+                (false, false) => Alignment::None,
+            };
+        }
+    }
+
+    let mut widths = vec![3; columns];
+    for (index, cells) in rows.iter().enumerate() {
+        if Some(index) == separator {
+            continue;
+        }
+        for (width, cell) in widths.iter_mut().zip(cells) {
+            *width = usize::max(*width, display_width(cell));
+        }
+    }
+
+    rows.iter()
+        .enumerate()
+        .map(|(index, cells)| {
+            let cells: Vec<String> = (0..columns)
+                .map(|column| {
+                    let width = widths[column];
+                    let alignment = alignments[column];
+                    if Some(index) == separator {
+                        separator_cell(alignment, width)
+                    } else {
+                        pad(
+                            cells.get(column).map_or("", String::as_str),
+                            alignment,
+                            width,
+                        )
+                    }
+                })
+                .collect();
+            format!("{}| {} |", indent, cells.join(" | "))
+        })
+        .collect()
+}
+
+fn split_cells(line: &str) -> Vec<String> {
+    let line = line.trim();
+    let line = line.strip_prefix('|').unwrap_or(line);
+    let line = match line.strip_suffix('|') {
+        Some(stripped) if !stripped.ends_with('\\') => stripped,
+        _ => line,
+    };
+
+    let mut cells = Vec::new();
+    let mut cell = String::new();
+    let mut escaped = false;
+    for c in line.chars() {
+        if c == '|' && !escaped {
+            cells.push(cell.trim().to_string());
+            cell.clear();
+            continue;
+        }
+        escaped = c == '\\' && !escaped;
+        cell.push(c);
+    }
+    cells.push(cell.trim().to_string());
+    cells
+}
+
+fn is_separator(cells: &[String]) -> bool {
+    cells.iter().all(|cell| {
+        let dashes = cell.strip_prefix(':').unwrap_or(cell);
+        let dashes = dashes.strip_suffix(':').unwrap_or(dashes);
+        !dashes.is_empty() && dashes.chars().all(|c| c == '-')
+    })
+}
+
+fn separator_cell(alignment: Alignment, width: usize) -> String {
+    match alignment {
+        Alignment::None => "-".repeat(width),
+        Alignment::Left => format!(":{}", "-".repeat(width.saturating_sub(1))),
+        Alignment::Right => format!("{}:", "-".repeat(width.saturating_sub(1))),
+        Alignment::Centre => format!(":{}:", "-".repeat(width.saturating_sub(2))),
+    }
+}
+
+fn pad(cell: &str, alignment: Alignment, width: usize) -> String {
+    let padding = width.saturating_sub(display_width(cell));
+    let (left, right) = match alignment {
+        Alignment::Right => (padding, 0),
+        Alignment::Centre => (padding / 2, padding - padding / 2),
+        Alignment::None | Alignment::Left => (0, padding),
+    };
+    format!("{}{}{}", " ".repeat(left), cell, " ".repeat(right))
+}
+
+fn display_width(text: &str) -> usize {
+    text.graphemes(true)
+        .map(|grapheme| usize::max(grapheme.width(), 1))
+        .sum()
+}
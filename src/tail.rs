@@ -0,0 +1,218 @@
+use crate::{Capabilities, Terminal, Theme};
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+use crossterm::style::{Color, Colors, ResetColor, SetColors, SetForegroundColor};
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+use std::time::Duration;
+
+const MAX_LINES: usize = 10_000;
+const TAG_COLOURS: [Color; 6] = [
+    Color::Cyan,
+    Color::Green,
+    Color::Yellow,
+    Color::Magenta,
+    Color::Blue,
+    Color::Red,
+];
+
+struct Watched {
+    filename: String,
+    tag: String,
+    offset: u64,
+    partial: String,
+}
+
+impl Watched {
+    fn open(filename: &str) -> Result<Self, std::io::Error> {
+        let offset = File::open(filename)?.metadata()?.len();
+        let tag = Path::new(filename)
+            .file_name()
+            .map_or_else(|| filename.to_string(), |name| name.to_string_lossy().into());
+        Ok(Self {
+            filename: filename.to_string(),
+            tag,
+            offset,
+            partial: String::new(),
+        })
+    }
+
+    fn read_appended(&mut self) -> Vec<String> {
+        let Ok(mut file) = File::open(&self.filename) else {
+            return Vec::new();
+        };
+        let len = file.metadata().map_or(self.offset, |metadata| metadata.len());
+        if len < self.offset {
+            self.offset = 0;
+            self.partial.clear();
+        }
+        if len == self.offset || file.seek(SeekFrom::Start(self.offset)).is_err() {
+            return Vec::new();
+        }
+        let mut bytes = Vec::new();
+        if file.read_to_end(&mut bytes).is_err() {
+            return Vec::new();
+        }
+        self.offset = self.offset.saturating_add(bytes.len() as u64);
+        self.partial.push_str(&String::from_utf8_lossy(&bytes));
+
+        let Some(end) = self.partial.rfind('\n') else {
+            return Vec::new();
+        };
+        let rest = self.partial.split_off(end.saturating_add(1));
+        let complete = std::mem::replace(&mut self.partial, rest);
+        complete
+            .lines()
+            .map(|line| line.trim_end_matches('\r').to_string())
+            .collect()
+    }
+}
+
+struct Tail {
+    terminal: Terminal,
+    files: Vec<Watched>,
+    lines: Vec<(usize, String)>,
+    scroll: usize,
+    theme: Theme,
+    should_quit: bool,
+}
+
+pub fn run(filenames: &[&str]) -> Result<(), String> {
+    if filenames.is_empty() {
+        return Err(String::from("--tail needs at least one file"));
+    }
+    let files = filenames
+        .iter()
+        .map(|filename| Watched::open(filename).map_err(|err| format!("{}: {}", filename, err)))
+        .collect::<Result<Vec<_>, _>>()?;
+    let terminal = Terminal::new(Capabilities::detect()).map_err(|err| err.to_string())?;
+
+    let mut tail = Tail {
+        terminal,
+        files,
+        lines: Vec::new(),
+        scroll: 0,
+        theme: Theme::default(),
+        should_quit: false,
+    };
+    Terminal::clear_screen();
+    let result = tail.run();
+    Terminal::quit();
+    result.map_err(|err| err.to_string())
+}
+
+impl Tail {
+    fn run(&mut self) -> Result<(), std::io::Error> {
+        while !self.should_quit {
+            self.poll_files();
+            self.refresh_screen()?;
+            if Terminal::poll_key(Duration::from_millis(250))? {
+                match Terminal::read_key()? {
+                    Some(Event::Key(key)) => self.process_key(key),
+                    Some(Event::Resize(width, height)) => self.terminal.resize(width, height),
+                    _ => (),
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn poll_files(&mut self) {
+        for (index, file) in self.files.iter_mut().enumerate() {
+            for line in file.read_appended() {
+                self.lines.push((index, line));
+                if self.scroll > 0 {
+                    self.scroll = self.scroll.saturating_add(1);
+                }
+            }
+        }
+        if self.lines.len() > MAX_LINES {
+            self.lines.drain(..self.lines.len() - MAX_LINES);
+        }
+        self.scroll = self.scroll.min(self.max_scroll());
+    }
+
+    fn max_scroll(&self) -> usize {
+        self.lines
+            .len()
+            .saturating_sub(self.terminal.size().height as usize)
+    }
+
+    fn process_key(&mut self, key: KeyEvent) {
+        let page = self.terminal.size().height as usize;
+        match (key.modifiers, key.code) {
+            (KeyModifiers::CONTROL, KeyCode::Char('q' | 'c')) => self.should_quit = true,
+            (_, KeyCode::Char('q') | KeyCode::Esc) => self.should_quit = true,
+            (_, KeyCode::Up | KeyCode::Char('k')) => self.scroll = self.scroll.saturating_add(1),
+            (_, KeyCode::Down | KeyCode::Char('j')) => self.scroll = self.scroll.saturating_sub(1),
+            (_, KeyCode::PageUp) => self.scroll = self.scroll.saturating_add(page),
+            (_, KeyCode::PageDown) => self.scroll = self.scroll.saturating_sub(page),
+            (_, KeyCode::Home | KeyCode::Char('g')) => self.scroll = self.max_scroll(),
+            (_, KeyCode::End | KeyCode::Char('G')) => self.scroll = 0,
+            _ => (),
+        }
+        self.scroll = self.scroll.min(self.max_scroll());
+    }
+
+    fn refresh_screen(&mut self) -> Result<(), std::io::Error> {
+        Terminal::hide_cursor();
+        let height = self.terminal.size().height as usize;
+        let width = self.terminal.size().width as usize;
+        let tag_width = self
+            .files
+            .iter()
+            .map(|file| file.tag.chars().count())
+            .max()
+            .unwrap_or(0);
+
+        let end = self.lines.len().saturating_sub(self.scroll);
+        let start = end.saturating_sub(height);
+        let mut lines: Vec<String> = self.lines[start..end]
+            .iter()
+            .map(|(index, line)| {
+                let text: String = line
+                    .chars()
+                    .take(width.saturating_sub(tag_width + 3))
+                    .collect();
+                format!(
+                    "{}[{:<width$}]{} {}",
+                    SetForegroundColor(TAG_COLOURS[index % TAG_COLOURS.len()]),
+                    self.files[*index].tag,
+                    ResetColor,
+                    text,
+                    width = tag_width,
+                )
+            })
+            .collect();
+        lines.resize(height, String::new());
+        lines.push(self.draw_status_bar(width));
+        lines.push(String::from("q = quit | j/k = scroll | G = follow"));
+        self.terminal.begin_frame();
+        for (y, line) in lines.iter().enumerate() {
+            self.terminal.draw_line(y, line);
+        }
+        self.terminal.end_frame();
+        Terminal::flush()
+    }
+
+    fn draw_status_bar(&self, width: usize) -> String {
+        let mut status = format!(
+            "tail: {} files | {} lines{}",
+            self.files.len(),
+            self.lines.len(),
+            if self.scroll > 0 {
+                format!(" | -{}", self.scroll)
+            } else {
+                String::from(" | following")
+            }
+        );
+        status.push_str(&" ".repeat(width.saturating_sub(status.len())));
+        status.truncate(width);
+        format!(
+            "{}{}{}",
+            SetColors(Colors::new(self.theme.status_fg.0, self.theme.status_bg.0)),
+            status,
+            ResetColor
+        )
+    }
+}
@@ -0,0 +1,143 @@
+use crate::clock;
+use crate::paths;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const PROJECT_DIR: &str = ".texty";
+const TEMPLATE_DIR: &str = "templates";
+const LICENSE: &str = "license";
+const HEADER_MARKERS: [&str; 3] = ["copyright", "license", "spdx-license-identifier"];
+
+pub struct Templates {
+    dirs: Vec<PathBuf>,
+}
+
+impl Templates {
+    pub fn discover(filename: &str) -> Self {
+        let start = env::current_dir()
+            .map(|dir| dir.join(filename))
+            .ok()
+            .and_then(|path| path.parent().map(Path::to_path_buf));
+        let project = start.and_then(|start| {
+            start
+                .ancestors()
+                .map(|dir| dir.join(PROJECT_DIR).join(TEMPLATE_DIR))
+                .find(|dir| dir.is_dir())
+        });
+        let user = paths::config_dir().map(|dir| dir.join(TEMPLATE_DIR));
+        Self {
+            dirs: project.into_iter().chain(user).collect(),
+        }
+    }
+
+    pub fn names_for(&self, filename: &str) -> Vec<String> {
+        let extension = extension(filename);
+        let mut names: Vec<String> = Vec::new();
+        for path in self.files() {
+            let Some(name) = stem(&path) else {
+                continue;
+            };
+            if name != LICENSE && path_extension(&path) == extension && !names.contains(&name) {
+                names.push(name);
+            }
+        }
+        names
+    }
+
+    pub fn load(&self, name: &str, filename: &str) -> Option<String> {
+        let extension = extension(filename);
+        let files = self.files();
+        let path = files
+            .iter()
+            .find(|path| stem(path).as_deref() == Some(name) && path_extension(path) == extension)
+            .or_else(|| {
+                files
+                    .iter()
+                    .find(|path| stem(path).as_deref() == Some(name))
+            })?;
+        fs::read_to_string(path).ok()
+    }
+
+    pub fn license(&self) -> Option<String> {
+        let path = self
+            .files()
+            .into_iter()
+            .find(|path| stem(path).as_deref() == Some(LICENSE))?;
+        fs::read_to_string(path).ok()
+    }
+
+    fn files(&self) -> Vec<PathBuf> {
+        let mut files = Vec::new();
+        for dir in &self.dirs {
+            let Ok(entries) = fs::read_dir(dir) else {
+                continue;
+            };
+            let mut paths: Vec<PathBuf> = entries
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|path| path.is_file())
+                .collect();
+            paths.sort();
+            files.extend(paths);
+        }
+        files
+    }
+}
+
+pub fn render(template: &str, filename: &str, author: &str) -> String {
+    let (year, month, day) = clock::today();
+    let name = Path::new(filename)
+        .file_stem()
+        .map_or_else(String::new, |stem| stem.to_string_lossy().into_owned());
+    let basename = Path::new(filename).file_name().map_or_else(
+        || filename.to_string(),
+        |name| name.to_string_lossy().into_owned(),
+    );
+    template
+        .replace("{{filename}}", &basename)
+        .replace("{{name}}", &name)
+        .replace("{{date}}", &format!("{:04}-{:02}-{:02}", year, month, day))
+        .replace("{{year}}", &year.to_string())
+        .replace("{{author}}", author)
+}
+
+pub fn comment_lines(text: &str, leader: &str) -> Vec<String> {
+    text.trim_end()
+        .lines()
+        .map(|line| format!("{} {}", leader, line).trim_end().to_string())
+        .collect()
+}
+
+pub fn header_span<'a, I>(lines: I, leader: &str) -> (usize, usize)
+where
+    I: Iterator<Item = &'a str>,
+{
+    let mut lines = lines.peekable();
+    let start = usize::from(lines.next_if(|line| line.starts_with("#!")).is_some());
+    let block: Vec<&str> = lines.take_while(|line| line.starts_with(leader)).collect();
+    let is_header = block.iter().any(|line| {
+        let line = line.to_lowercase();
+        HEADER_MARKERS.iter().any(|marker| line.contains(marker))
+    });
+    (start, if is_header { block.len() } else { 0 })
+}
+
+pub fn default_author() -> String {
+    env::var("USER")
+        .or_else(|_| env::var("USERNAME"))
+        .unwrap_or_default()
+}
+
+fn extension(filename: &str) -> Option<String> {
+    path_extension(Path::new(filename))
+}
+
+fn path_extension(path: &Path) -> Option<String> {
+    path.extension()
+        .map(|extension| extension.to_string_lossy().into_owned())
+}
+
+fn stem(path: &Path) -> Option<String> {
+    path.file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+}
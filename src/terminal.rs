@@ -1,13 +1,34 @@
-use std::io::{stdout, Write};
+use std::io::{self, stdout, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
 
 use crossterm::{
     cursor,
-    event::{read, Event},
+    event::{
+        poll, read, DisableBracketedPaste, DisableMouseCapture, EnableBracketedPaste,
+        EnableMouseCapture, Event, KeyEventKind,
+    },
     style::{Color, Colors, ResetColor, SetColors, SetForegroundColor},
-    terminal, ExecutableCommand,
+    terminal, ExecutableCommand, QueueableCommand,
 };
 
-use crate::Position;
+use crate::{Capabilities, Position};
+use serde::Deserialize;
+
+static HEADLESS: AtomicBool = AtomicBool::new(false);
+static MOUSE: AtomicBool = AtomicBool::new(false);
+static BRACKETED_PASTE: AtomicBool = AtomicBool::new(false);
+static IN_FRAME: AtomicBool = AtomicBool::new(false);
+
+#[derive(Deserialize, Clone, Copy, PartialEq)]
+pub enum ColourSupport {
+    #[serde(rename = "truecolour", alias = "truecolor")]
+    TrueColour,
+    #[serde(rename = "256")]
+    Ansi256,
+    #[serde(rename = "16")]
+    Ansi16,
+}
 
 pub struct Size {
     pub width: u16,
@@ -16,71 +37,234 @@ pub struct Size {
 
 pub struct Terminal {
     size: Size,
+    screen: Vec<String>,
+    capabilities: Capabilities,
+    top: u16,
 }
 
 impl Terminal {
-    #[allow(clippy::should_implement_trait)]
-    pub fn default() -> Result<Self, std::io::Error> {
+    pub fn new(capabilities: Capabilities) -> Result<Self, std::io::Error> {
         let (width, height) = terminal::size()?;
 
         terminal::enable_raw_mode()?;
+        if capabilities.mouse {
+            output().execute(EnableMouseCapture)?;
+            MOUSE.store(true, Ordering::Relaxed);
+        }
+        if capabilities.bracketed_paste {
+            output().execute(EnableBracketedPaste).ok();
+            BRACKETED_PASTE.store(true, Ordering::Relaxed);
+        }
 
         Ok(Self {
             size: Size {
                 width,
                 height: height.saturating_sub(2),
             },
+            screen: Vec::new(),
+            capabilities,
+            top: 0,
         })
     }
 
+    pub fn headless(width: u16, height: u16, capabilities: Capabilities) -> Self {
+        HEADLESS.store(true, Ordering::Relaxed);
+        Self {
+            size: Size {
+                width,
+                height: height.saturating_sub(2),
+            },
+            screen: Vec::new(),
+            capabilities,
+            top: 0,
+        }
+    }
+
+    pub fn capabilities(&self) -> &Capabilities {
+        &self.capabilities
+    }
+
+    pub fn begin_frame(&self) {
+        if self.capabilities.synchronized_output && !HEADLESS.load(Ordering::Relaxed) {
+            output().queue(terminal::BeginSynchronizedUpdate).ok();
+            IN_FRAME.store(true, Ordering::Relaxed);
+        }
+    }
+
+    pub fn end_frame(&self) {
+        if IN_FRAME.swap(false, Ordering::Relaxed) {
+            output().queue(terminal::EndSynchronizedUpdate).ok();
+        }
+    }
+
+    pub fn snapshot(&self) -> String {
+        self.screen
+            .iter()
+            .map(|line| format!("{}\n", strip_escapes(line).trim_end()))
+            .collect()
+    }
+
     pub fn size(&self) -> &Size {
         &self.size
     }
 
+    /// Reserves `rows` lines above the text area, e.g. for the tabline.
+    pub fn set_top(&mut self, rows: u16) {
+        self.size.height = self
+            .size
+            .height
+            .saturating_add(self.top)
+            .saturating_sub(rows);
+        self.top = rows;
+        self.invalidate();
+    }
+
+    pub fn resize(&mut self, width: u16, height: u16) {
+        self.size = Size {
+            width,
+            height: height.saturating_sub(2).saturating_sub(self.top),
+        };
+        self.invalidate();
+    }
+
+    pub fn invalidate(&mut self) {
+        self.screen.clear();
+    }
+
+    pub fn draw_line(&mut self, y: usize, line: &str) {
+        if self.screen.get(y).is_some_and(|drawn| drawn == line) {
+            return;
+        }
+        let mut output = output();
+        output.queue(cursor::MoveTo(0, y as u16)).ok();
+        // Clear before printing: a line that reaches the last column, as the scrollbar does,
+        // leaves the cursor there, and clearing afterwards would erase that column.
+        output
+            .queue(terminal::Clear(terminal::ClearType::UntilNewLine))
+            .ok();
+        write!(output, "{}", line).ok();
+        output.queue(ResetColor).ok();
+
+        if self.screen.len() <= y {
+            self.screen.resize(y.saturating_add(1), String::new());
+        }
+        self.screen[y] = line.to_string();
+    }
+
+    pub fn hide_cursor() {
+        output().queue(cursor::Hide).ok();
+    }
+
+    pub fn show_cursor() {
+        output().queue(cursor::Show).ok();
+    }
+
     pub fn clear_screen() {
-        stdout()
+        output()
             .execute(terminal::Clear(terminal::ClearType::All))
             .ok();
     }
 
     pub fn clear_current_line() {
-        stdout()
+        output()
             .execute(terminal::Clear(terminal::ClearType::CurrentLine))
             .ok();
     }
 
     pub fn set_colours(colours: Colors) {
-        stdout().execute(SetColors(colours)).ok();
+        output().execute(SetColors(colours)).ok();
     }
 
     pub fn set_text_colour(colour: Color) {
-        stdout().execute(SetForegroundColor(colour)).ok();
+        output().execute(SetForegroundColor(colour)).ok();
     }
 
     pub fn reset_colours() {
-        stdout().execute(ResetColor).ok();
+        output().execute(ResetColor).ok();
     }
 
-    #[allow(clippy::let_and_return)]
-    pub fn read_key() -> Result<Event, std::io::Error> {
-        let event = read();
-        event
+    pub fn read_key() -> Result<Option<Event>, std::io::Error> {
+        if HEADLESS.load(Ordering::Relaxed) {
+            return Err(end_of_script());
+        }
+        let event = read()?;
+        if matches!(&event, Event::Key(key) if key.kind == KeyEventKind::Release) {
+            return Ok(None);
+        }
+        Ok(Some(event))
+    }
+
+    pub fn poll_key(timeout: Duration) -> Result<bool, std::io::Error> {
+        if HEADLESS.load(Ordering::Relaxed) {
+            return Err(end_of_script());
+        }
+        poll(timeout)
     }
 
     pub fn flush() -> Result<(), std::io::Error> {
-        stdout().flush()
+        output().flush()
     }
 
     pub fn position_cursor(position: &Position) {
         let Position { x, y } = position;
         let x = *x as u16;
         let y = *y as u16;
-        stdout().execute(cursor::MoveTo(x, y)).ok();
+        output().queue(cursor::MoveTo(x, y)).ok();
+    }
+
+    pub fn restore() {
+        if HEADLESS.load(Ordering::Relaxed) {
+            return;
+        }
+        if IN_FRAME.swap(false, Ordering::Relaxed) {
+            output().execute(terminal::EndSynchronizedUpdate).ok();
+        }
+        if BRACKETED_PASTE.swap(false, Ordering::Relaxed) {
+            output().execute(DisableBracketedPaste).ok();
+        }
+        if MOUSE.swap(false, Ordering::Relaxed) {
+            output().execute(DisableMouseCapture).ok();
+        }
+        terminal::disable_raw_mode().ok();
     }
 
     pub fn quit() {
         Terminal::clear_screen();
-        terminal::disable_raw_mode().ok();
-        println!("uuuuuuuuuuuuuuuuuuuu ( ;´ - `;)\r");
+        Terminal::restore();
+        writeln!(output(), "uuuuuuuuuuuuuuuuuuuu ( ;´ - `;)\r").ok();
+    }
+}
+
+fn output() -> Box<dyn Write> {
+    if HEADLESS.load(Ordering::Relaxed) {
+        Box::new(io::sink())
+    } else {
+        Box::new(stdout())
+    }
+}
+
+fn end_of_script() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::UnexpectedEof,
+        "key script ended while waiting for input",
+    )
+}
+
+fn strip_escapes(line: &str) -> String {
+    let mut stripped = String::new();
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        if c != '\u{1b}' {
+            stripped.push(c);
+            continue;
+        }
+        if chars.next() == Some('[') {
+            for c in chars.by_ref() {
+                if ('@'..='~').contains(&c) {
+                    break;
+                }
+            }
+        }
     }
+    stripped
 }
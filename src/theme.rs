@@ -0,0 +1,224 @@
+use crate::config::{Colour, Colours};
+use crate::Config;
+use crate::{Capabilities, ColourSupport};
+use crossterm::style::Color;
+use serde::Deserialize;
+use std::fs;
+use std::path::PathBuf;
+
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+const ANSI_16: [(Color, (u8, u8, u8)); 16] = [
+    (Color::Black, (0, 0, 0)),
+    (Color::DarkRed, (128, 0, 0)),
+    (Color::DarkGreen, (0, 128, 0)),
+    (Color::DarkYellow, (128, 128, 0)),
+    (Color::DarkBlue, (0, 0, 128)),
+    (Color::DarkMagenta, (128, 0, 128)),
+    (Color::DarkCyan, (0, 128, 128)),
+    (Color::Grey, (192, 192, 192)),
+    (Color::DarkGrey, (128, 128, 128)),
+    (Color::Red, (255, 0, 0)),
+    (Color::Green, (0, 255, 0)),
+    (Color::Yellow, (255, 255, 0)),
+    (Color::Blue, (0, 0, 255)),
+    (Color::Magenta, (255, 0, 255)),
+    (Color::Cyan, (0, 255, 255)),
+    (Color::White, (255, 255, 255)),
+];
+
+const BUILTIN_THEMES: [(&str, &str); 2] = [
+    ("gruvbox", include_str!("../themes/gruvbox.toml")),
+    (
+        "solarized-dark",
+        include_str!("../themes/solarized-dark.toml"),
+    ),
+];
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct Theme {
+    #[serde(skip)]
+    pub name: String,
+    pub none: Colour,
+    pub number: Colour,
+    pub string: Colour,
+    pub character: Colour,
+    pub comment: Colour,
+    pub primary_keywords: Colour,
+    pub secondary_keywords: Colour,
+    #[serde(rename = "match")]
+    pub search_match: Colour,
+    pub bracket_match: Colour,
+    pub status_fg: Colour,
+    pub status_bg: Colour,
+    pub gutter_fg: Colour,
+    pub selection_bg: Colour,
+    pub diff_added: Colour,
+    pub diff_changed: Colour,
+    pub diff_removed: Colour,
+    #[serde(skip)]
+    pub italics: bool,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            name: String::from("default"),
+            none: Colour(Color::White),
+            number: Colour(Color::Rgb {
+                r: 244,
+                g: 162,
+                b: 97,
+            }),
+            string: Colour(Color::Rgb {
+                r: 233,
+                g: 237,
+                b: 201,
+            }),
+            character: Colour(Color::Rgb {
+                r: 255,
+                g: 200,
+                b: 221,
+            }),
+            comment: Colour(Color::Rgb {
+                r: 133,
+                g: 153,
+                b: 0,
+            }),
+            primary_keywords: Colour(Color::Green),
+            secondary_keywords: Colour(Color::Yellow),
+            search_match: Colour(Color::Cyan),
+            bracket_match: Colour(Color::Magenta),
+            status_fg: Colour(Color::Rgb {
+                r: 43,
+                g: 45,
+                b: 66,
+            }),
+            status_bg: Colour(Color::Rgb {
+                r: 153,
+                g: 217,
+                b: 140,
+            }),
+            gutter_fg: Colour(Color::DarkGrey),
+            selection_bg: Colour(Color::Rgb {
+                r: 68,
+                g: 71,
+                b: 90,
+            }),
+            diff_added: Colour(Color::Green),
+            diff_changed: Colour(Color::Yellow),
+            diff_removed: Colour(Color::Red),
+            italics: false,
+        }
+    }
+}
+
+impl Theme {
+    pub fn load(name: &str) -> Result<Self, String> {
+        if name == "default" {
+            return Ok(Self::default());
+        }
+
+        let source = if let Some(path) = Self::user_path(name).filter(|path| path.exists()) {
+            fs::read_to_string(&path).map_err(|err| format!("{}: {}", path.display(), err))?
+        } else if let Some((_, source)) = BUILTIN_THEMES.iter().find(|(theme, _)| *theme == name) {
+            source.to_string()
+        } else {
+            return Err(format!("Unknown theme: {}", name));
+        };
+
+        let mut theme: Self =
+            toml::from_str(&source).map_err(|err| format!("{}: {}", name, err.message()))?;
+        theme.name = name.to_string();
+        Ok(theme)
+    }
+
+    pub fn user_path(name: &str) -> Option<PathBuf> {
+        Config::path()
+            .and_then(|path| path.parent().map(|dir| dir.join("themes")))
+            .map(|dir| dir.join(format!("{}.toml", name)))
+    }
+
+    pub fn adapt(&mut self, capabilities: &Capabilities) {
+        self.italics = capabilities.italics;
+        self.quantise(capabilities.colour);
+    }
+
+    fn quantise(&mut self, support: ColourSupport) {
+        let colours = [
+            &mut self.none,
+            &mut self.number,
+            &mut self.string,
+            &mut self.character,
+            &mut self.comment,
+            &mut self.primary_keywords,
+            &mut self.secondary_keywords,
+            &mut self.search_match,
+            &mut self.bracket_match,
+            &mut self.status_fg,
+            &mut self.status_bg,
+            &mut self.gutter_fg,
+            &mut self.selection_bg,
+            &mut self.diff_added,
+            &mut self.diff_changed,
+            &mut self.diff_removed,
+        ];
+        for colour in colours {
+            if let Color::Rgb { r, g, b } = colour.0 {
+                colour.0 = match support {
+                    ColourSupport::TrueColour => continue,
+                    ColourSupport::Ansi256 => Color::AnsiValue(to_ansi256(r, g, b)),
+                    ColourSupport::Ansi16 => to_ansi16(r, g, b),
+                };
+            }
+        }
+    }
+
+    pub fn apply(&mut self, colours: &Colours) {
+        let overrides = [
+            (&mut self.status_fg, colours.status_fg),
+            (&mut self.status_bg, colours.status_bg),
+            (&mut self.gutter_fg, colours.gutter_fg),
+            (&mut self.selection_bg, colours.selection_bg),
+        ];
+        for (colour, setting) in overrides {
+            if let Some(setting) = setting {
+                *colour = setting;
+            }
+        }
+    }
+}
+
+fn distance((r1, g1, b1): (u8, u8, u8), (r2, g2, b2): (u8, u8, u8)) -> u32 {
+    let d = |a: u8, b: u8| (i32::from(a) - i32::from(b)).pow(2) as u32;
+    d(r1, r2) + d(g1, g2) + d(b1, b2)
+}
+
+fn to_ansi256(r: u8, g: u8, b: u8) -> u8 {
+    let nearest_level = |value: u8| {
+        (0..CUBE_LEVELS.len())
+            .min_by_key(|&i| CUBE_LEVELS[i].abs_diff(value))
+            .unwrap_or(0)
+    };
+    let (ri, gi, bi) = (nearest_level(r), nearest_level(g), nearest_level(b));
+    let cube = (CUBE_LEVELS[ri], CUBE_LEVELS[gi], CUBE_LEVELS[bi]);
+
+    let average = (u16::from(r) + u16::from(g) + u16::from(b)) / 3;
+    let grey_index = (average.saturating_sub(3) / 10).min(23) as u8;
+    let grey_level = 8 + grey_index * 10;
+    let grey = (grey_level, grey_level, grey_level);
+
+    if distance((r, g, b), grey) < distance((r, g, b), cube) {
+        232 + grey_index
+    } else {
+        16 + 36 * ri as u8 + 6 * gi as u8 + bi as u8
+    }
+}
+
+fn to_ansi16(r: u8, g: u8, b: u8) -> Color {
+    ANSI_16
+        .iter()
+        .min_by_key(|(_, rgb)| distance((r, g, b), *rgb))
+        .map_or(Color::Reset, |(colour, _)| *colour)
+}
@@ -0,0 +1,27 @@
+use std::time::Duration;
+
+#[derive(Default)]
+pub struct Timings {
+    pub event: Duration,
+    pub highlight: Duration,
+    pub render: Duration,
+    pub flush: Duration,
+    pub events: usize,
+    pub frames: usize,
+}
+
+impl Timings {
+    pub fn overlay(&self) -> Vec<String> {
+        let line = |name: &str, duration: Duration| {
+            format!("{:<9}{:>9.3} ms", name, duration.as_secs_f64() * 1000.0)
+        };
+        vec![
+            line("event", self.event),
+            line("highlight", self.highlight),
+            line("render", self.render),
+            line("flush", self.flush),
+            format!("{:<9}{:>12}", "events", self.events),
+            format!("{:<9}{:>12}", "frames", self.frames),
+        ]
+    }
+}
@@ -0,0 +1,271 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Output, Stdio};
+use std::thread;
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+const HTML_ENTITIES: [(char, &str); 5] = [
+    ('&', "&amp;"),
+    ('<', "&lt;"),
+    ('>', "&gt;"),
+    ('"', "&quot;"),
+    ('\'', "&#39;"),
+];
+
+type Builtin = fn(&str) -> Result<String, String>;
+
+enum Kind {
+    Builtin(Builtin),
+    Command(String),
+}
+
+pub struct Transforms {
+    transforms: Vec<(String, Kind)>,
+}
+
+impl Default for Transforms {
+    fn default() -> Self {
+        let builtins: [(&str, Builtin); 7] = [
+            ("base64-encode", |text| Ok(base64_encode(text.as_bytes()))),
+            ("base64-decode", base64_decode),
+            ("url-encode", |text| Ok(url_encode(text))),
+            ("url-decode", url_decode),
+            ("html-encode", |text| Ok(html_encode(text))),
+            ("html-decode", |text| Ok(html_decode(text))),
+            ("rot13", |text| Ok(rot13(text))),
+        ];
+        Self {
+            transforms: builtins
+                .into_iter()
+                .map(|(name, transform)| (name.to_string(), Kind::Builtin(transform)))
+                .collect(),
+        }
+    }
+}
+
+impl Transforms {
+    pub fn with_commands(commands: &BTreeMap<String, String>) -> Self {
+        let mut transforms = Self::default();
+        for (name, command) in commands {
+            transforms.register_command(name, command);
+        }
+        transforms
+    }
+
+    pub fn register_command(&mut self, name: &str, command: &str) {
+        self.transforms.retain(|(existing, _)| existing != name);
+        self.transforms
+            .push((name.to_string(), Kind::Command(command.to_string())));
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.transforms.iter().map(|(name, _)| name.as_str())
+    }
+
+    pub fn apply(&self, name: &str, text: &str) -> Result<String, String> {
+        match self
+            .transforms
+            .iter()
+            .find(|(existing, _)| existing == name)
+        {
+            Some((_, Kind::Builtin(transform))) => transform(text),
+            Some((_, Kind::Command(command))) => pipe_through(command, text)
+                .map(|output| output.strip_suffix('\n').unwrap_or(&output).to_string())
+                .map_err(|err| err.to_string()),
+            None => Err(format!("Unknown transform: {}", name)),
+        }
+    }
+}
+
+pub fn shell(command: &str) -> Command {
+    let (shell, flag) = if cfg!(windows) {
+        ("cmd", "/C")
+    } else {
+        ("sh", "-c")
+    };
+    let mut process = Command::new(shell);
+    process.arg(flag).arg(command);
+    process
+}
+
+pub fn run_with_input(mut command: Command, input: &str) -> Result<Output, std::io::Error> {
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let writer = child.stdin.take().map(|mut stdin| {
+        let input = input.to_string();
+        thread::spawn(move || stdin.write_all(input.as_bytes()))
+    });
+    let output = child.wait_with_output()?;
+    if let Some(writer) = writer {
+        match writer.join() {
+            Ok(Err(err)) if err.kind() != std::io::ErrorKind::BrokenPipe => return Err(err),
+            _ => (),
+        }
+    }
+    Ok(output)
+}
+
+pub fn pipe_through(command: &str, input: &str) -> Result<String, std::io::Error> {
+    let output = run_with_input(shell(command), input)?;
+    if !output.status.success() {
+        return Err(std::io::Error::other(format!(
+            "`{}` exited with {}",
+            command, output.status
+        )));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+pub fn missing_program(command: &str) -> Option<&str> {
+    let program = command
+        .split_whitespace()
+        .find(|word| !word.contains('=') || word.starts_with(['"', '\'']))?;
+    let found = if program.contains('/') {
+        Path::new(program).is_file()
+    } else {
+        env::var_os("PATH").is_some_and(|path| {
+            env::split_paths(&path).any(|dir| {
+                dir.join(program).is_file() || dir.join(format!("{}.exe", program)).is_file()
+            })
+        })
+    };
+    (!found).then_some(program)
+}
+
+pub fn base64_encode(bytes: &[u8]) -> String {
+    let mut encoded = String::new();
+    for chunk in bytes.chunks(3) {
+        let b = [
+            chunk[0],
+            chunk.get(1).copied().unwrap_or(0),
+            chunk.get(2).copied().unwrap_or(0),
+        ];
+        let n = (u32::from(b[0]) << 16) | (u32::from(b[1]) << 8) | u32::from(b[2]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(BASE64[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+fn base64_decode(text: &str) -> Result<String, String> {
+    let mut bytes = Vec::new();
+    let mut buffer: u32 = 0;
+    let mut bits = 0;
+    for c in text
+        .bytes()
+        .filter(|c| !c.is_ascii_whitespace() && *c != b'=')
+    {
+        let value = BASE64
+            .iter()
+            .position(|&b| b == c)
+            .ok_or_else(|| format!("Invalid base64 character `{}`", c as char))?;
+        buffer = (buffer << 6) | value as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((buffer >> bits) as u8);
+            buffer &= (1 << bits) - 1;
+        }
+    }
+    String::from_utf8(bytes).map_err(|_| String::from("Decoded base64 is not valid UTF-8"))
+}
+
+fn url_encode(text: &str) -> String {
+    let mut encoded = String::new();
+    for byte in text.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-_.~".contains(&byte) {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    encoded
+}
+
+fn url_decode(text: &str) -> Result<String, String> {
+    let mut bytes = Vec::new();
+    let mut rest = text.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        if byte == b'%' {
+            let hex = tail
+                .get(..2)
+                .and_then(|hex| std::str::from_utf8(hex).ok())
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                .ok_or_else(|| String::from("Invalid percent escape"))?;
+            bytes.push(hex);
+            rest = &tail[2..];
+        } else {
+            bytes.push(if byte == b'+' { b' ' } else { byte });
+            rest = tail;
+        }
+    }
+    String::from_utf8(bytes).map_err(|_| String::from("Decoded URL is not valid UTF-8"))
+}
+
+fn html_encode(text: &str) -> String {
+    let mut encoded = String::new();
+    for c in text.chars() {
+        match HTML_ENTITIES.iter().find(|(entity, _)| *entity == c) {
+            Some((_, escaped)) => encoded.push_str(escaped),
+            None => encoded.push(c),
+        }
+    }
+    encoded
+}
+
+fn html_decode(text: &str) -> String {
+    let mut decoded = String::new();
+    let mut rest = text;
+    while let Some(index) = rest.find('&') {
+        decoded.push_str(&rest[..index]);
+        rest = &rest[index..];
+        let entity = rest.find(';').map(|end| &rest[..=end]);
+        let c = entity.and_then(|entity| {
+            HTML_ENTITIES
+                .iter()
+                .find(|(_, escaped)| *escaped == entity)
+                .map(|(c, _)| *c)
+                .or_else(|| {
+                    let number = entity.strip_prefix("&#")?.strip_suffix(';')?;
+                    let code = match number.strip_prefix(['x', 'X']) {
+                        Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                        None => number.parse().ok()?,
+                    };
+                    char::from_u32(code)
+                })
+        });
+        match (c, entity) {
+            (Some(c), Some(entity)) => {
+                decoded.push(c);
+                rest = &rest[entity.len()..];
+            }
+            _ => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+fn rot13(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            'a'..='z' => (((c as u8 - b'a') + 13) % 26 + b'a') as char,
+            'A'..='Z' => (((c as u8 - b'A') + 13) % 26 + b'A') as char,
+            _ => c,
+        })
+        .collect()
+}
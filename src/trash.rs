@@ -0,0 +1,154 @@
+use crate::clock;
+use std::env;
+use std::fs;
+use std::io::Error;
+use std::path::{Path, PathBuf};
+
+pub struct Trash {
+    dir: PathBuf,
+}
+
+impl Trash {
+    pub fn home() -> Option<Self> {
+        let data_dir = env::var_os("XDG_DATA_HOME")
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share")))?;
+        Some(Self {
+            dir: data_dir.join("Trash"),
+        })
+    }
+
+    pub fn put(&self, path: &Path) -> Result<String, Error> {
+        let path = fs::canonicalize(path)?;
+        let files = self.dir.join("files");
+        let info = self.dir.join("info");
+        fs::create_dir_all(&files)?;
+        fs::create_dir_all(&info)?;
+
+        let file_name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .ok_or_else(|| Error::other("Not a file"))?;
+        let mut name = file_name.clone();
+        let mut counter = 1;
+        while files.join(&name).exists() || info.join(format!("{}.trashinfo", name)).exists() {
+            counter += 1;
+            name = format!("{}.{}", file_name, counter);
+        }
+
+        let trash_info = format!(
+            "[Trash Info]\nPath={}\nDeletionDate={}\n",
+            encode_path(&path),
+            deletion_date(),
+        );
+        fs::write(info.join(format!("{}.trashinfo", name)), trash_info)?;
+        if let Err(err) = move_file(&path, &files.join(&name)) {
+            fs::remove_file(info.join(format!("{}.trashinfo", name))).ok();
+            return Err(err);
+        }
+
+        Ok(name)
+    }
+
+    pub fn restore(&self, name: &str) -> Result<PathBuf, Error> {
+        let info_file = self.dir.join("info").join(format!("{}.trashinfo", name));
+        let trash_info = fs::read_to_string(&info_file)?;
+        let original = trash_info
+            .lines()
+            .find_map(|line| line.strip_prefix("Path="))
+            .map(|path| PathBuf::from(decode_path(path)))
+            .ok_or_else(|| Error::other("Malformed trash info"))?;
+
+        if original.exists() {
+            return Err(Error::other(format!(
+                "{} already exists",
+                original.display()
+            )));
+        }
+        move_file(&self.dir.join("files").join(name), &original)?;
+        fs::remove_file(info_file)?;
+
+        Ok(original)
+    }
+}
+
+fn move_file(from: &Path, to: &Path) -> Result<(), Error> {
+    if fs::rename(from, to).is_err() {
+        fs::copy(from, to)?;
+        fs::remove_file(from)?;
+    }
+    Ok(())
+}
+
+fn encode_path(path: &Path) -> String {
+    let mut encoded = String::new();
+    for byte in path.to_string_lossy().bytes() {
+        if byte.is_ascii_alphanumeric() || b"/-_.~".contains(&byte) {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    encoded
+}
+
+fn decode_path(path: &str) -> String {
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::new();
+    let mut index = 0;
+    while index < bytes.len() {
+        if bytes[index] == b'%' {
+            if let Some(byte) = path
+                .get(index + 1..index + 3)
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+            {
+                decoded.push(byte);
+                index += 3;
+                continue;
+            }
+        }
+        decoded.push(bytes[index]);
+        index += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn deletion_date() -> String {
+    let seconds = clock::local_seconds();
+    let (year, month, day) = clock::civil_date(seconds.div_euclid(86400));
+    let time = seconds.rem_euclid(86400);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+        year,
+        month,
+        day,
+        time / 3600,
+        time % 3600 / 60,
+        time % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn percent_encodes_reserved_bytes() {
+        assert_eq!(
+            encode_path(Path::new("/home/me/a b%c/ü~_-.txt")),
+            "/home/me/a%20b%25c/%C3%BC~_-.txt"
+        );
+    }
+
+    #[test]
+    fn decodes_what_it_encodes() {
+        let path = "/tmp/notes [draft]/100% #1/naïve.md";
+        assert_eq!(decode_path(&encode_path(Path::new(path))), path);
+    }
+
+    #[test]
+    fn keeps_malformed_escapes_literally() {
+        assert_eq!(decode_path("/a%2/b%zz%"), "/a%2/b%zz%");
+        assert_eq!(decode_path("%2f%2F"), "//");
+    }
+}
@@ -0,0 +1,433 @@
+use crate::paths;
+use crate::Position;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::env;
+use std::fs::{self, File, OpenOptions};
+use std::io::{Error, Read, Seek, SeekFrom, Write};
+use std::mem;
+use std::path::PathBuf;
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use unicode_segmentation::UnicodeSegmentation;
+
+static SPILL_FILES: AtomicUsize = AtomicUsize::new(0);
+
+pub struct Change {
+    pub start: Position,
+    pub removed: String,
+    pub inserted: String,
+}
+
+#[derive(Default)]
+pub struct UndoHistory {
+    undo: VecDeque<Vec<Change>>,
+    redo: Vec<Vec<Change>>,
+    open: bool,
+    depth: usize,
+    undo_bytes: usize,
+    redo_bytes: usize,
+    limit: Option<usize>,
+    spill: Option<Spill>,
+    spill_enabled: bool,
+    evicted: usize,
+}
+
+struct Spill {
+    file: File,
+    path: PathBuf,
+    offsets: Vec<u64>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SpilledChange {
+    x: usize,
+    y: usize,
+    removed: String,
+    inserted: String,
+}
+
+impl Change {
+    pub fn inserted_end(&self) -> Position {
+        end_of(&self.start, &self.inserted)
+    }
+
+    pub fn removed_end(&self) -> Position {
+        end_of(&self.start, &self.removed)
+    }
+
+    pub fn size(&self) -> usize {
+        mem::size_of::<Self>()
+            .saturating_add(self.removed.len())
+            .saturating_add(self.inserted.len())
+    }
+
+    pub fn map(&self, position: Position) -> Position {
+        let removed_end = self.removed_end();
+        let inserted_end = self.inserted_end();
+        if (position.y, position.x) < (self.start.y, self.start.x) {
+            position
+        } else if (position.y, position.x) < (removed_end.y, removed_end.x) {
+            self.start
+        } else if position.y == removed_end.y {
+            Position {
+                x: position.x - removed_end.x + inserted_end.x,
+                y: inserted_end.y,
+            }
+        } else {
+            Position {
+                x: position.x,
+                y: position.y - removed_end.y + inserted_end.y,
+            }
+        }
+    }
+}
+
+impl UndoHistory {
+    pub fn set_limit(&mut self, limit: usize, spill: bool) {
+        self.limit = (limit > 0).then_some(limit);
+        self.spill_enabled = spill;
+        self.enforce_limit();
+    }
+
+    pub fn record(&mut self, change: Change) {
+        self.redo.clear();
+        self.redo_bytes = 0;
+        if !self.open {
+            self.undo.push_back(Vec::new());
+            self.open = true;
+        }
+        let Some(group) = self.undo.back_mut() else {
+            return;
+        };
+        if let Some(last) = group.last_mut() {
+            if last.removed.is_empty()
+                && change.removed.is_empty()
+                && last.inserted_end() == change.start
+            {
+                self.undo_bytes = self.undo_bytes.saturating_add(change.inserted.len());
+                last.inserted.push_str(&change.inserted);
+                return;
+            }
+        }
+        self.undo_bytes = self.undo_bytes.saturating_add(change.size());
+        group.push(change);
+    }
+
+    pub fn seal(&mut self) {
+        if self.depth == 0 {
+            self.open = false;
+            self.enforce_limit();
+        }
+    }
+
+    pub fn begin(&mut self) {
+        self.seal();
+        self.depth = self.depth.saturating_add(1);
+    }
+
+    pub fn end(&mut self) {
+        self.depth = self.depth.saturating_sub(1);
+        self.seal();
+    }
+
+    pub fn pop_undo(&mut self) -> Option<Vec<Change>> {
+        self.open = false;
+        match self.undo.pop_back() {
+            Some(group) => {
+                self.undo_bytes = self.undo_bytes.saturating_sub(group_size(&group));
+                Some(group)
+            }
+            None => self.spill.as_mut()?.pop(),
+        }
+    }
+
+    pub fn push_undo(&mut self, group: Vec<Change>) {
+        self.undo_bytes = self.undo_bytes.saturating_add(group_size(&group));
+        self.undo.push_back(group);
+        self.enforce_limit();
+    }
+
+    pub fn pop_redo(&mut self) -> Option<Vec<Change>> {
+        self.open = false;
+        let group = self.redo.pop()?;
+        self.redo_bytes = self.redo_bytes.saturating_sub(group_size(&group));
+        Some(group)
+    }
+
+    pub fn push_redo(&mut self, group: Vec<Change>) {
+        self.redo_bytes = self.redo_bytes.saturating_add(group_size(&group));
+        self.redo.push(group);
+    }
+
+    pub fn undo_len(&self) -> usize {
+        self.undo.len()
+    }
+
+    pub fn redo_len(&self) -> usize {
+        self.redo.len()
+    }
+
+    pub fn memory(&self) -> usize {
+        self.undo_bytes.saturating_add(self.redo_bytes)
+    }
+
+    pub fn limit(&self) -> Option<usize> {
+        self.limit
+    }
+
+    pub fn spilled(&self) -> (usize, u64) {
+        self.spill.as_ref().map_or((0, 0), |spill| {
+            (
+                spill.offsets.len(),
+                spill.file.metadata().map_or(0, |metadata| metadata.len()),
+            )
+        })
+    }
+
+    pub fn evicted(&self) -> usize {
+        self.evicted
+    }
+
+    fn enforce_limit(&mut self) {
+        let Some(limit) = self.limit else {
+            return;
+        };
+        let sealed = if self.open {
+            self.undo.len().saturating_sub(1)
+        } else {
+            self.undo.len()
+        };
+        for _ in 0..sealed {
+            if self.memory() <= limit {
+                break;
+            }
+            let Some(group) = self.undo.pop_front() else {
+                break;
+            };
+            self.undo_bytes = self.undo_bytes.saturating_sub(group_size(&group));
+            if !self.spill_group(&group) {
+                self.evicted = self.evicted.saturating_add(1);
+            }
+        }
+    }
+
+    fn spill_group(&mut self, group: &[Change]) -> bool {
+        if !self.spill_enabled {
+            return false;
+        }
+        if self.spill.is_none() {
+            self.spill = Spill::create().ok();
+        }
+        self.spill
+            .as_mut()
+            .is_some_and(|spill| spill.push(group).is_ok())
+    }
+}
+
+impl Spill {
+    fn create() -> Result<Self, Error> {
+        let dir = paths::cache_dir().unwrap_or_else(env::temp_dir);
+        fs::create_dir_all(&dir)?;
+        let path = dir.join(format!(
+            "undo-{}-{}.jsonl",
+            process::id(),
+            SPILL_FILES.fetch_add(1, Ordering::Relaxed)
+        ));
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&path)?;
+        Ok(Self {
+            file,
+            path,
+            offsets: Vec::new(),
+        })
+    }
+
+    fn push(&mut self, group: &[Change]) -> Result<(), Error> {
+        let changes: Vec<SpilledChange> = group
+            .iter()
+            .map(|change| SpilledChange {
+                x: change.start.x,
+                y: change.start.y,
+                removed: change.removed.clone(),
+                inserted: change.inserted.clone(),
+            })
+            .collect();
+        let offset = self.file.seek(SeekFrom::End(0))?;
+        let mut line = serde_json::to_string(&changes)?;
+        line.push('\n');
+        self.file.write_all(line.as_bytes())?;
+        self.offsets.push(offset);
+        Ok(())
+    }
+
+    fn pop(&mut self) -> Option<Vec<Change>> {
+        let offset = self.offsets.pop()?;
+        let mut line = String::new();
+        self.file.seek(SeekFrom::Start(offset)).ok()?;
+        self.file.read_to_string(&mut line).ok()?;
+        self.file.set_len(offset).ok()?;
+        let changes: Vec<SpilledChange> = serde_json::from_str(line.trim_end()).ok()?;
+        Some(
+            changes
+                .into_iter()
+                .map(|change| Change {
+                    start: Position {
+                        x: change.x,
+                        y: change.y,
+                    },
+                    removed: change.removed,
+                    inserted: change.inserted,
+                })
+                .collect(),
+        )
+    }
+}
+
+impl Drop for Spill {
+    fn drop(&mut self) {
+        fs::remove_file(&self.path).ok();
+    }
+}
+
+fn group_size(group: &[Change]) -> usize {
+    group.iter().map(Change::size).sum()
+}
+
+pub fn end_of(start: &Position, text: &str) -> Position {
+    match text.rsplit_once('\n') {
+        Some((before, last)) => Position {
+            x: last.graphemes(true).count(),
+            y: start
+                .y
+                .saturating_add(before.matches('\n').count())
+                .saturating_add(1),
+        },
+        None => Position {
+            x: start.x.saturating_add(text.graphemes(true).count()),
+            y: start.y,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn change(x: usize, y: usize, removed: &str, inserted: &str) -> Change {
+        Change {
+            start: Position { x, y },
+            removed: removed.to_string(),
+            inserted: inserted.to_string(),
+        }
+    }
+
+    fn inserted(group: Option<Vec<Change>>) -> Vec<String> {
+        group
+            .unwrap_or_default()
+            .into_iter()
+            .map(|change| change.inserted)
+            .collect()
+    }
+
+    fn history(words: &[&str]) -> UndoHistory {
+        let mut history = UndoHistory::default();
+        for word in words {
+            history.record(change(0, 0, "", word));
+            history.seal();
+        }
+        history
+    }
+
+    #[test]
+    fn spill_round_trips_groups_in_reverse_order() {
+        let mut spill = Spill::create().unwrap();
+        spill.push(&[change(0, 0, "a\nb", "\"é\"")]).unwrap();
+        spill
+            .push(&[change(2, 1, "", "tab\there"), change(0, 3, "z", "\n")])
+            .unwrap();
+
+        let second = spill.pop().unwrap();
+        assert_eq!(second.len(), 2);
+        assert_eq!((second[0].start.x, second[0].start.y), (2, 1));
+        assert_eq!(second[0].inserted, "tab\there");
+        assert_eq!((second[1].start.x, second[1].start.y), (0, 3));
+        assert_eq!(
+            (second[1].removed.as_str(), second[1].inserted.as_str()),
+            ("z", "\n")
+        );
+
+        let first = spill.pop().unwrap();
+        assert_eq!(first.len(), 1);
+        assert_eq!(first[0].removed, "a\nb");
+        assert_eq!(first[0].inserted, "\"é\"");
+        assert!(spill.pop().is_none());
+        assert_eq!(spill.file.metadata().unwrap().len(), 0);
+
+        let path = spill.path.clone();
+        drop(spill);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn counts_coalesced_inserts_once() {
+        let mut history = UndoHistory::default();
+        history.record(change(0, 0, "", "ab"));
+        history.record(change(2, 0, "", "c"));
+        assert_eq!(history.memory(), mem::size_of::<Change>() + 3);
+        assert_eq!(inserted(history.pop_undo()), ["abc"]);
+        assert_eq!(history.memory(), 0);
+    }
+
+    #[test]
+    fn evicts_oldest_sealed_groups_over_the_limit() {
+        let mut history = history(&["one", "two", "three"]);
+        let size = |word: &str| mem::size_of::<Change>() + word.len();
+        let total = size("one") + size("two") + size("three");
+        assert_eq!(history.memory(), total);
+
+        history.set_limit(total - 1, false);
+        assert_eq!(history.undo_len(), 2);
+        assert_eq!(history.evicted(), 1);
+        assert_eq!(history.memory(), total - size("one"));
+
+        let group = history.pop_undo().unwrap();
+        assert_eq!(history.memory(), size("two"));
+        history.push_redo(group);
+        assert_eq!(history.memory(), total - size("one"));
+        assert_eq!(inserted(history.pop_redo()), ["three"]);
+        assert_eq!(inserted(history.pop_undo()), ["two"]);
+        assert!(history.pop_undo().is_none());
+        assert_eq!(history.memory(), 0);
+    }
+
+    #[test]
+    fn keeps_the_open_group_until_it_is_sealed() {
+        let mut history = UndoHistory::default();
+        history.set_limit(1, false);
+        history.record(change(0, 0, "", "typing"));
+        assert_eq!((history.undo_len(), history.evicted()), (1, 0));
+        history.seal();
+        assert_eq!((history.undo_len(), history.evicted()), (0, 1));
+        assert_eq!(history.memory(), 0);
+    }
+
+    #[test]
+    fn undoes_spilled_groups_from_disk() {
+        let mut history = history(&["one", "two", "three"]);
+        history.set_limit(1, true);
+        assert_eq!(history.undo_len(), 0);
+        assert_eq!(history.spilled().0, 3);
+        assert_eq!((history.evicted(), history.memory()), (0, 0));
+
+        assert_eq!(inserted(history.pop_undo()), ["three"]);
+        assert_eq!(inserted(history.pop_undo()), ["two"]);
+        assert_eq!(inserted(history.pop_undo()), ["one"]);
+        assert!(history.pop_undo().is_none());
+        assert_eq!(history.spilled(), (0, 0));
+    }
+}
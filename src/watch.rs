@@ -0,0 +1,36 @@
+use std::fs;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+pub struct Watcher {
+    stamps: Vec<(PathBuf, Option<SystemTime>)>,
+}
+
+impl Watcher {
+    pub fn new(paths: Vec<PathBuf>) -> Self {
+        Self {
+            stamps: stamps(paths),
+        }
+    }
+
+    pub fn changed(&mut self, paths: Vec<PathBuf>) -> bool {
+        let stamps = stamps(paths);
+        if stamps == self.stamps {
+            return false;
+        }
+        self.stamps = stamps;
+        true
+    }
+}
+
+fn stamps(paths: Vec<PathBuf>) -> Vec<(PathBuf, Option<SystemTime>)> {
+    paths
+        .into_iter()
+        .map(|path| {
+            let modified = fs::metadata(&path)
+                .and_then(|metadata| metadata.modified())
+                .ok();
+            (path, modified)
+        })
+        .collect()
+}
@@ -0,0 +1,7 @@
+use crate::Position;
+
+#[derive(Default, Clone, Copy)]
+pub struct Window {
+    pub offset: Position,
+    pub top_segment: usize,
+}
@@ -0,0 +1,5 @@
+// A small file for screen snapshots.
+fn main() {
+    let greeting = "hello";
+    println!("{} world", greeting);
+}
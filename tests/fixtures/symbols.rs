@@ -0,0 +1,38 @@
+// Symbols for the Ctrl-T snapshot.
+use std::fmt;
+
+pub struct Config {
+    name: String,
+}
+
+enum Mode {
+    Fast,
+    Slow,
+}
+
+impl Config {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for Config {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+fn parse_mode(text: &str) -> Mode {
+    if text == "fast" {
+        Mode::Fast
+    } else {
+        Mode::Slow
+    }
+}
+
+fn main() {
+    let config = Config::new("demo");
+    println!("{} {}", config, matches!(parse_mode("fast"), Mode::Fast));
+}
@@ -0,0 +1,321 @@
+use std::env;
+use std::fs;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::process::{self, Command};
+
+struct TempProject(PathBuf);
+
+impl TempProject {
+    fn new(name: &str) -> Self {
+        let path = env::temp_dir().join(format!("texty-{}-{}", name, process::id()));
+        fs::create_dir_all(&path).unwrap();
+        Self(path)
+    }
+
+    fn dir(&self, name: &str) {
+        fs::create_dir_all(self.0.join(name)).unwrap();
+    }
+
+    fn write(&self, name: &str, contents: &str) {
+        let path = self.0.join(name);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).unwrap();
+        }
+        fs::write(path, contents).unwrap();
+    }
+}
+
+impl Deref for TempProject {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempProject {
+    fn drop(&mut self) {
+        fs::remove_dir_all(&self.0).ok();
+    }
+}
+
+fn assert_screen(name: &str, file: &str, keys: &str) {
+    assert_screen_in(Path::new(env!("CARGO_MANIFEST_DIR")), name, file, keys);
+}
+
+fn assert_screen_in(dir: &Path, name: &str, file: &str, keys: &str) {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let output = Command::new(env!("CARGO_BIN_EXE_texty"))
+        .current_dir(dir)
+        .args(["--clean", "--snapshot", keys, file])
+        .output()
+        .expect("failed to run texty");
+    assert!(
+        output.status.success(),
+        "texty failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let screen = String::from_utf8(output.stdout).expect("screen is not UTF-8");
+
+    let path = root
+        .join("tests")
+        .join("snapshots")
+        .join(format!("{}.txt", name));
+    if env::var_os("UPDATE_SNAPSHOTS").is_some() {
+        fs::write(&path, &screen).expect("failed to write snapshot");
+        return;
+    }
+    let expected = fs::read_to_string(&path).unwrap_or_else(|err| {
+        panic!(
+            "cannot read {} ({}); rerun with UPDATE_SNAPSHOTS=1 to create it",
+            path.display(),
+            err
+        )
+    });
+    assert_eq!(
+        screen,
+        expected,
+        "screen differs from {}; rerun with UPDATE_SNAPSHOTS=1 to accept",
+        path.display()
+    );
+}
+
+#[test]
+fn renders_file_with_gutter_and_status_bar() {
+    assert_screen("open", "tests/fixtures/sample.rs", "");
+}
+
+#[test]
+fn marks_buffer_modified_after_edit() {
+    assert_screen("modified", "tests/fixtures/sample.rs", "jjwx");
+}
+
+#[test]
+fn scrolls_to_end_of_file() {
+    assert_screen("scroll_end", "tests/fixtures/lines.txt", "G");
+}
+
+#[test]
+fn scrolls_by_page() {
+    assert_screen(
+        "page_down",
+        "tests/fixtures/lines.txt",
+        "<PageDown><PageDown>",
+    );
+}
+
+#[test]
+fn reports_search_matches() {
+    assert_screen("search", "tests/fixtures/lines.txt", "/line 4<CR>n");
+}
+
+#[test]
+fn shows_goto_line_prompt() {
+    assert_screen("goto_prompt", "tests/fixtures/lines.txt", ":4");
+}
+
+#[test]
+fn pages_through_read_only_view() {
+    assert_screen("pager", "tests/fixtures/lines.txt", ":view<CR>  bx");
+}
+
+#[test]
+fn strips_ansi_escapes() {
+    assert_screen("ansi_strip", "tests/fixtures/ansi.log", ":ansi strip<CR>");
+}
+
+#[test]
+fn opens_file_from_finder() {
+    assert_screen(
+        "finder",
+        "tests/fixtures/sample.rs",
+        "<C-p>fixtureslines<CR>",
+    );
+}
+
+#[test]
+fn runs_buffer_key_mapping() {
+    assert_screen("map", "tests/fixtures/lines.txt", ":map Q 5<CR>Q");
+}
+
+#[test]
+fn jumps_to_fuzzy_line_match() {
+    assert_screen("lines", "tests/fixtures/lines.txt", "<C-l>line 1<Down><CR>");
+}
+
+#[test]
+fn jumps_to_fuzzy_symbol_match() {
+    assert_screen("symbols", "tests/fixtures/symbols.rs", "<C-t>pmode<CR>");
+}
+
+#[test]
+fn asks_again_for_invalid_line_number() {
+    assert_screen("goto_retry", "tests/fixtures/lines.txt", "<C-g>99<CR>");
+}
+
+#[test]
+fn opens_project_grep_result() {
+    let project = TempProject::new("grep");
+    project.write("notes.txt", "nothing here\n");
+    project.write(
+        "src/main.rs",
+        "fn main() {\n    let greeting = \"hello\";\n    println!(\"{}\", greeting);\n}\n",
+    );
+
+    assert_screen_in(&project, "grep", "notes.txt", ":grep greetX<BS>ing<CR><CR>");
+}
+
+#[test]
+fn renames_file_and_updates_references() {
+    let project = TempProject::new("rename");
+    project.dir(".git");
+    project.write("docs/old.md", "# Old\n");
+    project.write("index.md", "See [old](docs/old.md).\n");
+
+    assert_screen_in(
+        &project,
+        "rename",
+        "docs/old.md",
+        ":rename docs/new.md<CR>a",
+    );
+    let index = fs::read_to_string(project.join("index.md")).unwrap();
+    assert_eq!(index, "See [old](docs/new.md).\n");
+}
+
+#[test]
+fn browses_file_tree() {
+    let project = TempProject::new("tree");
+    project.write("docs/guide.md", "# Guide\n");
+    project.write("src/main.rs", "fn main() {}\n");
+    project.write("README.md", "# Project\n");
+
+    assert_screen_in(&project, "tree", "README.md", "<C-e>ggl<Down><CR>");
+}
+
+#[test]
+fn opens_file_from_directory_listing() {
+    let project = TempProject::new("listing");
+    project.write("docs/guide.md", "# Guide\n");
+    project.write("README.md", "# Project\n");
+
+    assert_screen_in(&project, "listing", ".", "<CR>-j<CR>");
+}
+
+#[test]
+fn marks_lines_changed_since_head() {
+    let project = TempProject::new("git");
+    project.write("notes.txt", "one\ntwo\nthree\nfour\n");
+    let git = |args: &[&str]| {
+        Command::new("git")
+            .current_dir(&*project)
+            .args(["-c", "user.name=texty", "-c", "user.email=texty@example.com"])
+            .args(args)
+            .output()
+            .expect("failed to run git");
+    };
+    git(&["init", "-q"]);
+    git(&["add", "notes.txt"]);
+    git(&["commit", "-q", "-m", "notes"]);
+
+    assert_screen_in(
+        &project,
+        "git_gutter",
+        "notes.txt",
+        "ggddjcwTWO<Esc>Goadded<Esc>:w<CR>",
+    );
+}
+
+#[test]
+fn shows_unified_diff_against_saved_file() {
+    assert_screen(
+        "diff",
+        "tests/fixtures/sample.rs",
+        "jjwcwconst<Esc>:diff<CR>",
+    );
+}
+
+#[test]
+fn reverts_unsaved_changes() {
+    assert_screen("revert", "tests/fixtures/sample.rs", "ddjx:revert<CR>");
+}
+
+#[test]
+fn goes_to_byte_inside_crlf_line_ending() {
+    assert_screen("goto_crlf", "tests/fixtures/crlf.txt", ":goto 3<CR>");
+}
+
+#[test]
+fn refuses_rename_when_read_only() {
+    let project = TempProject::new("rename-ro");
+    project.write("notes.md", "# Notes\n");
+
+    assert_screen_in(
+        &project,
+        "rename_read_only",
+        "notes.md",
+        ":set readonly<CR>:rename moved.md<CR>",
+    );
+    let kept = project.join("notes.md").exists();
+    assert!(kept);
+}
+
+#[test]
+fn batch_keeps_pipes_inside_substitutions() {
+    let project = TempProject::new("batch");
+    project.write("notes.txt", "foo|bar\nbaz\n");
+    let path = project.join("notes.txt");
+    let output = Command::new(env!("CARGO_BIN_EXE_texty"))
+        .args(["--clean", "--batch", "s/foo|bar/X/ | 2s#a#|# | w"])
+        .arg(&path)
+        .output()
+        .expect("failed to run texty");
+    let contents = fs::read_to_string(&path).unwrap();
+    assert!(
+        output.status.success(),
+        "texty failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(contents, "X\nb|z\n");
+}
+
+#[test]
+fn keeps_line_deletes_in_numbered_registers_after_small_deletes() {
+    assert_screen("small_delete", "tests/fixtures/lines.txt", "ddxx\"1p\"-p");
+}
+
+#[test]
+fn marks_buffer_modified_after_redo_past_save() {
+    let project = TempProject::new("redo");
+    project.write("notes.txt", "one\ntwo\n");
+
+    assert_screen_in(
+        &project,
+        "redo_modified",
+        "notes.txt",
+        "dd:w<CR>u:w<CR><C-r>",
+    );
+}
+
+#[test]
+fn scrolls_cursor_off_the_overflow_indicator() {
+    assert_screen("overflow_indicator", "tests/fixtures/long.txt", "77l");
+}
+
+#[test]
+fn line_picker_keeps_cursor_line_beyond_item_limit() {
+    let project = TempProject::new("lines");
+    let contents: String = (1..=3000).map(|line| format!("row {}\n", line)).collect();
+    project.write("rows.txt", &contents);
+
+    assert_screen_in(&project, "lines_far", "rows.txt", "2500G<C-l><Up><CR>");
+}
+
+#[test]
+fn aligns_only_the_pipe_delimited_table() {
+    assert_screen(
+        "align_table",
+        "tests/fixtures/table.md",
+        "j:align-table<CR>",
+    );
+}